@@ -0,0 +1,131 @@
+//! What each texture uses its alpha channel for, so formats without real alpha aren't picked for
+//! textures that need it.
+use crate::*;
+use image::GenericImageView;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum AlphaUsage {
+	/// Every pixel fully opaque
+	Opaque,
+	/// Every pixel fully opaque or fully transparent
+	OneBit,
+	/// Some pixels are partly transparent
+	Smooth,
+}
+
+impl AlphaUsage {
+	pub fn of(image: &DynamicImage) -> Self {
+		Self::of_pixels(image.to_rgba8().pixels().map(|pixel| pixel.0[3]))
+	}
+
+	fn of_pixels(alphas: impl Iterator<Item = u8>) -> Self {
+		let mut usage = Self::Opaque;
+		for alpha in alphas {
+			match alpha {
+				255 => {}
+				0 => usage = Self::OneBit,
+				_ => return Self::Smooth,
+			}
+		}
+		usage
+	}
+
+	/// Whether `format` keeps this much alpha intact.
+	pub fn fits(self, format: TextureFormat) -> bool {
+		match format {
+			TextureFormat::DXT1
+			| TextureFormat::RGB8
+			| TextureFormat::RGB5
+			| TextureFormat::L8
+			| TextureFormat::ATI1
+			| TextureFormat::ATI2
+			| TextureFormat::BC6H => self == Self::Opaque,
+			TextureFormat::DXT1a | TextureFormat::RGB5A1 => self != Self::Smooth,
+			_ => true,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureAlpha {
+	pub name: String,
+	pub usage: AlphaUsage,
+	/// Sprites on the texture with partly transparent pixels in their region, sorted
+	pub translucent_sprites: Vec<String>,
+}
+
+/// Alpha usage of every decoded texture, sorted by name. Raw and placeholder textures are left
+/// out since their pixels aren't known.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlphaReport {
+	pub textures: Vec<TextureAlpha>,
+}
+
+impl AlphaReport {
+	pub fn get(&self, texture: &str) -> Option<&TextureAlpha> {
+		self.textures.iter().find(|entry| entry.name == texture)
+	}
+}
+
+impl SprSet {
+	pub fn alpha_report(&self) -> AlphaReport {
+		let mut textures = self
+			.textures
+			.iter()
+			.filter(|(_, texture)| texture.raw.is_none() && !texture.placeholder)
+			.map(|(name, texture)| {
+				let image = texture.image.to_rgba8();
+				let usage = AlphaUsage::of_pixels(image.pixels().map(|pixel| pixel.0[3]));
+				let mut translucent_sprites = vec![];
+				if usage == AlphaUsage::Smooth {
+					for (sprite_name, sprite) in &self.sprites {
+						if sprite.texture_name != *name {
+							continue;
+						}
+						let (x, y, width, height) = SubPixel::Floor.bounds(&sprite.pixel_region);
+						let crop = image::imageops::crop_imm(&image, x, y, width, height);
+						let alphas = crop.pixels().map(|(_, _, pixel)| pixel.0[3]);
+						if AlphaUsage::of_pixels(alphas) == AlphaUsage::Smooth {
+							translucent_sprites.push(sprite_name.clone());
+						}
+					}
+					translucent_sprites.sort();
+				}
+				TextureAlpha {
+					name: name.clone(),
+					usage,
+					translucent_sprites,
+				}
+			})
+			.collect::<Vec<_>>();
+		textures.sort_by(|a, b| a.name.cmp(&b.name));
+		AlphaReport { textures }
+	}
+
+	/// A warning for every texture whose alpha would be lost or crushed by the format `options`
+	/// writes it as. Nothing is refused, forcing BC1 on purpose is allowed.
+	pub fn check_alpha(&self, options: &WriteOptions) -> Vec<Warning> {
+		self.alpha_report()
+			.textures
+			.into_iter()
+			.filter_map(|entry| {
+				let format = options.format_for(&entry.name);
+				if entry.usage.fits(format) {
+					return None;
+				}
+				let sprites = if entry.translucent_sprites.is_empty() {
+					String::new()
+				} else {
+					format!(", used by {}", entry.translucent_sprites.join(", "))
+				};
+				Some(Warning {
+					code: ErrorCode::AlphaLost,
+					message: format!(
+						"Texture {} has {:?} alpha which {format:?} can't store{sprites}",
+						entry.name, entry.usage
+					),
+				})
+			})
+			.collect()
+	}
+}
@@ -0,0 +1,593 @@
+//! Packing sprites onto texture pages.
+use crate::*;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PackAlgorithm {
+	/// Rows of sprites, each as tall as its tallest sprite
+	#[default]
+	Shelf,
+	/// Each sprite goes as low as it fits on the outline of those already placed, which wastes
+	/// less space when sprite heights vary a lot
+	Skyline,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackOptions {
+	/// Largest page side, pages are powers of two up to this
+	pub max_size: u32,
+	/// Transparent pixels kept between sprites so filtering doesn't bleed, none at page edges
+	pub padding: u32,
+	/// Spread sprites over pages so none is left nearly empty, merging pages where they fit
+	pub balance: bool,
+	/// Used for the texel regions of the moved sprites
+	pub texel_rounding: TexelRounding,
+	pub algorithm: PackAlgorithm,
+}
+
+impl Default for PackOptions {
+	fn default() -> Self {
+		Self {
+			max_size: 2048,
+			padding: 2,
+			balance: true,
+			texel_rounding: TexelRounding::Exact,
+			algorithm: PackAlgorithm::Shelf,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedSprite {
+	pub name: String,
+	pub page: usize,
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AtlasLayout {
+	pub pages: Vec<(u32, u32)>,
+	pub sprites: Vec<PackedSprite>,
+}
+
+impl AtlasLayout {
+	pub fn total_area(&self) -> u64 {
+		self.pages
+			.iter()
+			.map(|(width, height)| *width as u64 * *height as u64)
+			.sum()
+	}
+
+	/// Fraction of each page covered by sprites.
+	pub fn utilization(&self) -> Vec<f64> {
+		self.pages
+			.iter()
+			.enumerate()
+			.map(|(page, (width, height))| {
+				let used = self
+					.sprites
+					.iter()
+					.filter(|sprite| sprite.page == page)
+					.map(|sprite| sprite.width as u64 * sprite.height as u64)
+					.sum::<u64>();
+				used as f64 / (*width as u64 * *height as u64).max(1) as f64
+			})
+			.collect()
+	}
+}
+
+type Item<'a> = (&'a str, u32, u32);
+
+/// Shelf packs `items` (already sorted tallest first) into one `width`x`height` page, `None` if
+/// they don't all fit.
+fn pack_page(items: &[Item], width: u32, height: u32, padding: u32) -> Option<Vec<(u32, u32)>> {
+	let mut positions = Vec::with_capacity(items.len());
+	let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+	for (_, item_width, item_height) in items {
+		if *item_width > width {
+			return None;
+		}
+		if x + item_width > width {
+			y += shelf_height;
+			x = 0;
+			shelf_height = 0;
+		}
+		if y + item_height > height {
+			return None;
+		}
+		positions.push((x, y));
+		// Padding goes after each item, it only matters if another item follows
+		x += item_width + padding;
+		shelf_height = shelf_height.max(item_height + padding);
+	}
+	Some(positions)
+}
+
+/// Skyline bottom-left packing of `items` into one page, `None` if they don't all fit.
+fn skyline_page(items: &[Item], width: u32, height: u32, padding: u32) -> Option<Vec<(u32, u32)>> {
+	// Segments of the outline as (x, y, width), left to right and covering the whole page width
+	let mut skyline = vec![(0u32, 0u32, width)];
+	let mut positions = Vec::with_capacity(items.len());
+	for (_, item_width, item_height) in items {
+		let mut best: Option<(u32, u32, usize)> = None;
+		for (i, (x, _, _)) in skyline.iter().enumerate() {
+			if x + item_width > width {
+				break;
+			}
+			// The padding to the right has to clear the outline too, unless it is past the edge
+			let footprint = (item_width + padding).min(width - x);
+			let (mut y, mut covered) = (0, 0);
+			for (_, segment_y, segment_width) in &skyline[i..] {
+				if covered >= footprint {
+					break;
+				}
+				y = y.max(*segment_y);
+				covered += segment_width;
+			}
+			if y + item_height > height {
+				continue;
+			}
+			if !best.is_some_and(|(best_y, best_x, _)| (best_y, best_x) <= (y, *x)) {
+				best = Some((y, *x, i));
+			}
+		}
+		let (y, x, i) = best?;
+		positions.push((x, y));
+
+		let footprint = (item_width + padding).min(width - x);
+		let right = x + footprint;
+		let mut next = skyline[..i].to_vec();
+		next.push((x, y + item_height + padding, footprint));
+		for (segment_x, segment_y, segment_width) in &skyline[i..] {
+			let end = segment_x + segment_width;
+			if end <= right {
+				continue;
+			}
+			let start = (*segment_x).max(right);
+			next.push((start, *segment_y, end - start));
+		}
+		skyline.clear();
+		for segment in next {
+			match skyline.last_mut() {
+				Some(last) if last.1 == segment.1 => last.2 += segment.2,
+				_ => skyline.push(segment),
+			}
+		}
+	}
+	Some(positions)
+}
+
+fn place_page(
+	items: &[Item],
+	width: u32,
+	height: u32,
+	options: &PackOptions,
+) -> Option<Vec<(u32, u32)>> {
+	match options.algorithm {
+		PackAlgorithm::Shelf => pack_page(items, width, height, options.padding),
+		PackAlgorithm::Skyline => skyline_page(items, width, height, options.padding),
+	}
+}
+
+fn page_sizes(max_size: u32) -> Vec<(u32, u32)> {
+	let mut sides = vec![];
+	let mut side = 16;
+	while side <= max_size {
+		sides.push(side);
+		side *= 2;
+	}
+	let mut sizes = sides
+		.iter()
+		.flat_map(|width| sides.iter().map(move |height| (*width, *height)))
+		.collect::<Vec<_>>();
+	sizes.sort_by_key(|(width, height)| (*width as u64 * *height as u64, *width.max(height)));
+	sizes
+}
+
+/// The smallest page `items` fit on.
+fn smallest_page(items: &[Item], options: &PackOptions) -> Option<((u32, u32), Vec<(u32, u32)>)> {
+	page_sizes(options.max_size)
+		.into_iter()
+		.find_map(|(width, height)| {
+			place_page(items, width, height, options).map(|positions| ((width, height), positions))
+		})
+}
+
+fn layout_from_bins(bins: Vec<Vec<Item>>, options: &PackOptions) -> Option<AtlasLayout> {
+	let mut layout = AtlasLayout::default();
+	for bin in bins.into_iter().filter(|bin| !bin.is_empty()) {
+		let (size, positions) = smallest_page(&bin, options)?;
+		let page = layout.pages.len();
+		layout.pages.push(size);
+		for ((name, width, height), (x, y)) in bin.into_iter().zip(positions) {
+			layout.sprites.push(PackedSprite {
+				name: name.to_string(),
+				page,
+				x,
+				y,
+				width,
+				height,
+			});
+		}
+	}
+	Some(layout)
+}
+
+/// Fills pages one after another, each as full as it gets.
+fn pack_greedy<'a>(
+	items: &[Item<'a>],
+	options: &PackOptions,
+) -> Result<Vec<Vec<Item<'a>>>, SpriteError> {
+	let mut bins = vec![];
+	let mut remaining = items.to_vec();
+	while !remaining.is_empty() {
+		let mut bin = vec![];
+		let mut rest = vec![];
+		for item in remaining {
+			let mut candidate = bin.clone();
+			candidate.push(item);
+			if place_page(&candidate, options.max_size, options.max_size, options).is_some() {
+				bin = candidate;
+			} else {
+				rest.push(item);
+			}
+		}
+		if bin.is_empty() {
+			let (name, width, height) = rest[0];
+			return Err(SpriteError::Validation(
+				ErrorCode::SpriteTooLarge,
+				format!(
+					"Sprite {name} is {width}x{height}, larger than a {0}x{0} page",
+					options.max_size
+				),
+			));
+		}
+		bins.push(bin);
+		remaining = rest;
+	}
+	Ok(bins)
+}
+
+/// Spreads items over `count` pages by area, biggest first onto the emptiest page.
+fn pack_even<'a>(items: &[Item<'a>], count: usize) -> Vec<Vec<Item<'a>>> {
+	let mut by_area = items.to_vec();
+	by_area.sort_by_key(|(name, width, height)| {
+		(std::cmp::Reverse(*width as u64 * *height as u64), *name)
+	});
+	let mut bins = vec![(0u64, vec![]); count];
+	for item in by_area {
+		let bin = bins
+			.iter_mut()
+			.min_by_key(|(area, _)| *area)
+			.expect("count is at least 1");
+		bin.0 += item.1 as u64 * item.2 as u64;
+		bin.1.push(item);
+	}
+	bins.into_iter()
+		.map(|(_, mut bin)| {
+			sort_tallest_first(&mut bin);
+			bin
+		})
+		.collect()
+}
+
+fn sort_tallest_first(items: &mut [Item]) {
+	items.sort_by(|a, b| b.2.cmp(&a.2).then(b.1.cmp(&a.1)).then(a.0.cmp(b.0)));
+}
+
+/// Packs `sizes` (name, width, height) onto as few and as small pages as it can.
+pub fn pack(
+	sizes: &[(String, u32, u32)],
+	options: &PackOptions,
+) -> Result<AtlasLayout, SpriteError> {
+	let mut items = sizes
+		.iter()
+		.map(|(name, width, height)| (name.as_str(), *width, *height))
+		.collect::<Vec<_>>();
+	sort_tallest_first(&mut items);
+	let greedy = pack_greedy(&items, options)?;
+	let page_count = greedy.len();
+	let mut best = layout_from_bins(greedy, options).ok_or_else(|| {
+		SpriteError::Validation(
+			ErrorCode::SpriteTooLarge,
+			format!("Sprites don't fit a {0}x{0} page", options.max_size),
+		)
+	})?;
+	if !options.balance || page_count < 2 {
+		return Ok(best);
+	}
+
+	// Greedy filling leaves the last page nearly empty, try fewer pages and even spreads
+	let area = items
+		.iter()
+		.map(|(_, width, height)| {
+			(*width + options.padding) as u64 * (*height + options.padding) as u64
+		})
+		.sum::<u64>();
+	let page_area = options.max_size as u64 * options.max_size as u64;
+	let fewest = ((area + page_area - 1) / page_area).max(1) as usize;
+	for count in fewest..=page_count {
+		if let Some(layout) = layout_from_bins(pack_even(&items, count), options) {
+			let better =
+				(layout.total_area(), layout.pages.len()) < (best.total_area(), best.pages.len());
+			if better {
+				best = layout;
+			}
+		}
+	}
+	Ok(best)
+}
+
+impl SprSet {
+	/// Crops every sprite out of the current textures and packs them onto fresh pages named
+	/// `MERGE_NOCOMP_<page>`, replacing the old textures and moving the sprites over. Textures no
+	/// sprite is on are dropped.
+	///
+	/// Only plain textures are repacked. Raw, placeholder, layered, cube map and external
+	/// textures are kept as they are, along with the sprites on them. A page keeps the original
+	/// format of the textures its sprites came from when they all agree, RGBA8 otherwise.
+	pub fn repack(&mut self, options: &PackOptions) -> Result<AtlasLayout, SpriteError> {
+		let mut crops = HashMap::new();
+		let mut formats = HashMap::new();
+		let mut sizes = vec![];
+		for (name, sprite) in &self.sprites {
+			if self.external_textures.contains_key(&sprite.texture_name)
+				&& !self.textures.contains_key(&sprite.texture_name)
+			{
+				continue;
+			}
+			let texture = self
+				.textures
+				.get(&sprite.texture_name)
+				.ok_or_else(|| SpriteError::missing_texture(name, &sprite.texture_name))?;
+			if !repackable(texture) {
+				continue;
+			}
+			let crop = extract::crop_sprite(&texture.image, sprite).to_rgba8();
+			sizes.push((name.clone(), crop.width(), crop.height()));
+			crops.insert(name.clone(), crop);
+			formats.insert(name.clone(), texture.original_format);
+		}
+		sizes.sort();
+		let layout = pack(&sizes, options)?;
+
+		let kept = self
+			.textures
+			.iter()
+			.filter(|(_, texture)| !repackable(texture))
+			.map(|(name, texture)| (name.clone(), texture.clone()))
+			.collect::<IndexMap<_, _>>();
+		if let Some(page) =
+			(0..layout.pages.len()).find(|page| kept.contains_key(&page_name(*page)))
+		{
+			return Err(SpriteError::Validation(
+				ErrorCode::NameInUse,
+				format!(
+					"Texture name {} is already used by a texture that isn't repacked",
+					page_name(page)
+				),
+			));
+		}
+
+		let mut pages = paint_pages(&layout, &crops);
+		for (page, (_, texture)) in pages.iter_mut().enumerate() {
+			let mut page_formats = layout
+				.sprites
+				.iter()
+				.filter(|packed| packed.page == page)
+				.map(|packed| formats[&packed.name]);
+			let first = page_formats.next();
+			if let Some(format) = first.filter(|first| page_formats.all(|format| format == *first))
+			{
+				texture.original_format = format;
+			}
+		}
+		self.original_indices
+			.textures
+			.retain(|name, _| kept.contains_key(name));
+		self.textures = kept;
+		self.textures.extend(pages);
+		for packed in &layout.sprites {
+			let sprite = self
+				.sprites
+				.get_mut(&packed.name)
+				.ok_or_else(|| SpriteError::not_in_set("Sprite", &packed.name))?;
+			place_sprite(sprite, packed, &layout, options);
+		}
+		Ok(layout)
+	}
+}
+
+/// Whether `repack` may crop `texture` and drop it, i.e. it is nothing but its top level image.
+fn repackable(texture: &Texture) -> bool {
+	texture.raw.is_none()
+		&& !texture.placeholder
+		&& !texture.cubemap
+		&& texture.array_size <= 1
+		&& texture.layers.is_empty()
+}
+
+fn page_name(page: usize) -> String {
+	format!("MERGE_NOCOMP_{page}")
+}
+
+/// Draws every packed image onto its page.
+fn paint_pages(
+	layout: &AtlasLayout,
+	images: &HashMap<String, image::RgbaImage>,
+) -> IndexMap<String, Texture> {
+	let mut pages = layout
+		.pages
+		.iter()
+		.map(|(width, height)| image::RgbaImage::new(*width, *height))
+		.collect::<Vec<_>>();
+	for packed in &layout.sprites {
+		image::imageops::replace(
+			&mut pages[packed.page],
+			&images[&packed.name],
+			packed.x as i64,
+			packed.y as i64,
+		);
+	}
+	pages
+		.into_iter()
+		.enumerate()
+		.map(|(page, image)| {
+			(
+				page_name(page),
+				Texture::new(DynamicImage::ImageRgba8(image)),
+			)
+		})
+		.collect()
+}
+
+/// Points `sprite` at where `packed` ended up.
+fn place_sprite(
+	sprite: &mut Sprite,
+	packed: &PackedSprite,
+	layout: &AtlasLayout,
+	options: &PackOptions,
+) {
+	let (page_width, page_height) = layout.pages[packed.page];
+	sprite.texture_name = page_name(packed.page);
+	sprite.pixel_region = Vec4 {
+		x: packed.x as f32,
+		y: packed.y as f32,
+		z: packed.width as f32,
+		w: packed.height as f32,
+	};
+	sprite.texel_region =
+		options
+			.texel_rounding
+			.texel_region(&sprite.pixel_region, page_width, page_height);
+}
+
+/// Builds a set from loose images, packing them onto as few power of two pages as fit.
+#[derive(Debug, Clone)]
+pub struct AtlasBuilder {
+	name: String,
+	images: Vec<(String, image::RgbaImage, ScreenMode)>,
+	/// Rotation of sprites added with [`AtlasBuilder::add_rotated`]
+	rotations: HashMap<String, i32>,
+	options: PackOptions,
+}
+
+impl AtlasBuilder {
+	/// A builder for a set called `name`, packing with skyline by default.
+	pub fn new(name: &str) -> Self {
+		Self {
+			name: name.to_string(),
+			images: vec![],
+			rotations: HashMap::new(),
+			options: PackOptions {
+				algorithm: PackAlgorithm::Skyline,
+				..Default::default()
+			},
+		}
+	}
+
+	pub fn options(mut self, options: PackOptions) -> Self {
+		self.options = options;
+		self
+	}
+
+	pub fn add(
+		&mut self,
+		name: &str,
+		image: &DynamicImage,
+		screen_mode: ScreenMode,
+	) -> Result<(), SpriteError> {
+		if self.images.iter().any(|(other, _, _)| other == name) {
+			return Err(SpriteError::Validation(
+				ErrorCode::NameInUse,
+				format!("Sprite name {name} is already used"),
+			));
+		}
+		self.images
+			.push((name.to_string(), image.to_rgba8(), screen_mode));
+		Ok(())
+	}
+
+	/// Adds an upright image, like [`SprSet::extract_sprites`] writes, for a sprite the game draws
+	/// turned by `rotate` quarter turns. It is packed turned back the way it is stored.
+	pub fn add_rotated(
+		&mut self,
+		name: &str,
+		image: &DynamicImage,
+		screen_mode: ScreenMode,
+		rotate: i32,
+	) -> Result<(), SpriteError> {
+		let sprite = Sprite {
+			rotate,
+			..Sprite::new("", Vec4::default(), screen_mode)
+		};
+		self.add(
+			name,
+			&extract::restore_orientation(image, &sprite),
+			screen_mode,
+		)?;
+		self.rotations.insert(name.to_string(), rotate);
+		Ok(())
+	}
+
+	/// Adds an image file as a sprite named after the file without its extension.
+	pub fn add_file(&mut self, path: &Path, screen_mode: ScreenMode) -> Result<(), SpriteError> {
+		let name = path
+			.file_stem()
+			.map(|name| name.to_string_lossy().to_string())
+			.ok_or_else(|| {
+				SpriteError::Import(format!("{} has no file name", path.to_string_lossy()))
+			})?;
+		self.add(&name, &image::open(path)?, screen_mode)
+	}
+
+	/// Adds every PNG in `dir`, returning how many.
+	pub fn add_dir(&mut self, dir: &Path, screen_mode: ScreenMode) -> Result<usize, SpriteError> {
+		let mut paths = std::fs::read_dir(dir)?
+			.filter_map(|entry| entry.ok())
+			.map(|entry| entry.path())
+			.filter(|path| {
+				path.extension()
+					.is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+			})
+			.collect::<Vec<_>>();
+		paths.sort();
+		for path in &paths {
+			self.add_file(path, screen_mode)?;
+		}
+		Ok(paths.len())
+	}
+
+	pub fn build(&self) -> Result<(SprSet, AtlasLayout), SpriteError> {
+		let mut sizes = self
+			.images
+			.iter()
+			.map(|(name, image, _)| (name.clone(), image.width(), image.height()))
+			.collect::<Vec<_>>();
+		sizes.sort();
+		let layout = pack(&sizes, &self.options)?;
+		let images = self
+			.images
+			.iter()
+			.map(|(name, image, _)| (name.clone(), image.clone()))
+			.collect();
+		let textures = paint_pages(&layout, &images);
+		let mut sprites = HashMap::with_capacity(layout.sprites.len());
+		for packed in &layout.sprites {
+			let (_, _, screen_mode) = self
+				.images
+				.iter()
+				.find(|(name, _, _)| *name == packed.name)
+				.ok_or_else(|| SpriteError::not_in_set("Sprite", &packed.name))?;
+			let mut sprite = Sprite::new("", Vec4::default(), *screen_mode);
+			sprite.rotate = self.rotations.get(&packed.name).copied().unwrap_or(0);
+			place_sprite(&mut sprite, packed, &layout, &self.options);
+			sprites.insert(packed.name.clone(), sprite);
+		}
+		let set = SprSet::from_parts(&self.name, textures, sprites, 0)?;
+		Ok((set, layout))
+	}
+}
@@ -0,0 +1,144 @@
+//! Extracts sets to a folder of PNGs plus `manifest.json` describing the sprites, and builds them
+//! back.
+//!
+//! ```text
+//! spr-cli extract <file> <dir>
+//! spr-cli build <dir> <file>
+//! spr-cli info [--json] <file>
+//! ```
+use spr::*;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage:
+	spr-cli extract <file> <dir>
+	spr-cli build <dir> <file>
+	spr-cli info [--json] <file>";
+
+fn extract(file: &Path, dir: &Path) -> Result<(), SpriteError> {
+	let set = SprConfig::new().read_file(file, None)?;
+	set.export_dir(dir, image::ImageFormat::Png)?;
+	for warning in &set.warnings {
+		eprintln!("warning: {warning}");
+	}
+	// Only the first layer of each texture makes it into the folder
+	for name in set.texture_order() {
+		let texture = &set.textures[name];
+		if texture.cubemap {
+			eprintln!("warning: texture {name} is a cube map, only its first face was extracted");
+		} else if !texture.layers.is_empty() {
+			eprintln!(
+				"warning: texture {name} has {} array layers, only the first was extracted",
+				texture.layers.len() + 1
+			);
+		}
+	}
+	Ok(())
+}
+
+fn build(dir: &Path, file: &Path) -> Result<(), SpriteError> {
+	let set = SprSet::import_dir(dir)?;
+	SprConfig::new().write_file(&set, file)?;
+	Ok(())
+}
+
+/// Everything `info` prints, plus each sprite's metadata, as JSON.
+fn info_json(file: &Path, set: &SprSet) -> Result<(), SpriteError> {
+	let textures = set
+		.texture_order()
+		.into_iter()
+		.map(|name| {
+			let texture = &set.textures[name];
+			serde_json::json!({
+				"name": name,
+				"width": texture.image.width(),
+				"height": texture.image.height(),
+				"format": texture.original_format,
+				"mip_count": texture.mip_count,
+				"array_size": texture.array_size,
+				"cubemap": texture.cubemap,
+			})
+		})
+		.collect::<Vec<_>>();
+	let sprites = set
+		.sprite_order()
+		.into_iter()
+		.map(|name| {
+			let sprite = &set.sprites[name];
+			serde_json::json!({
+				"name": name,
+				"texture": sprite.texture_name,
+				"x": sprite.pixel_region.x,
+				"y": sprite.pixel_region.y,
+				"width": sprite.pixel_region.z,
+				"height": sprite.pixel_region.w,
+				"screen_mode": sprite.screen_mode,
+				"rotate": sprite.rotate,
+			})
+		})
+		.collect::<Vec<_>>();
+	let info = serde_json::json!({
+		"file": file.to_string_lossy(),
+		"name": set.name,
+		"flags": set.flags(),
+		"textures": textures,
+		"sprites": sprites,
+		"stats": set.stats(),
+		"warnings": set.warnings,
+	});
+	let json =
+		serde_json::to_string_pretty(&info).map_err(|err| SpriteError::Import(err.to_string()))?;
+	println!("{json}");
+	Ok(())
+}
+
+fn info(file: &Path, json: bool) -> Result<(), SpriteError> {
+	let set = SprConfig::new().read_file(file, None)?;
+	if json {
+		return info_json(file, &set);
+	}
+	let stats = set.stats();
+	println!("{}", file.display());
+	println!("  textures: {}", stats.texture_count);
+	for name in set.texture_order() {
+		let texture = &set.textures[name];
+		println!(
+			"    {name}: {}x{} {:?}",
+			texture.image.width(),
+			texture.image.height(),
+			texture.original_format
+		);
+	}
+	println!("  sprites: {}", stats.sprite_count);
+	for (mode, count) in &stats.screen_modes {
+		println!("    {mode}: {count}");
+	}
+	println!("  stored bytes: {}", stats.stored_bytes);
+	println!("  sprite coverage: {:.1}%", stats.sprite_coverage * 100.0);
+	for warning in &set.warnings {
+		println!("  warning: {warning}");
+	}
+	Ok(())
+}
+
+fn main() -> ExitCode {
+	let args = std::env::args().skip(1).collect::<Vec<_>>();
+	let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+	let result = match args.as_slice() {
+		["extract", file, dir] => extract(Path::new(file), Path::new(dir)),
+		["build", dir, file] => build(Path::new(dir), Path::new(file)),
+		["info", file] => info(Path::new(file), false),
+		["info", "--json", file] => info(Path::new(file), true),
+		_ => {
+			eprintln!("{USAGE}");
+			return ExitCode::FAILURE;
+		}
+	};
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(err) => {
+			eprintln!("error {}: {err}", err.code());
+			ExitCode::FAILURE
+		}
+	}
+}
@@ -0,0 +1,293 @@
+use crate::stream::{MipHeader, Tables, TextureHeader, TextureKind};
+use crate::*;
+use std::io::Seek;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MipRef<'a> {
+	pub width: u32,
+	pub height: u32,
+	pub format: TextureFormat,
+	pub indices: MipIndex,
+	/// As stored, 16 bit texels are in the set's byte order
+	pub data: &'a [u8],
+	pub endian: Endian,
+}
+
+impl MipRef<'_> {
+	pub fn decode(&self) -> Option<image::RgbaImage> {
+		decode_region(
+			&swap_texture_data(self.format, self.data, self.endian),
+			self.format,
+			self.width,
+			self.height,
+			(0, 0, self.width, self.height),
+			FlipConvention::Auto,
+		)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureRef<'a> {
+	pub name: &'a str,
+	pub kind: TextureKind,
+	pub mip_count: u32,
+	pub array_size: u32,
+	/// Every mip of the first layer, then every mip of the second and so on
+	pub mips: Vec<MipRef<'a>>,
+}
+
+impl<'a> TextureRef<'a> {
+	pub fn mip(&self, array_index: u32, mip: u32) -> Option<&MipRef<'a>> {
+		self.mips.get((array_index * self.mip_count + mip) as usize)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteRef<'a> {
+	pub name: &'a str,
+	pub texture_index: u32,
+	pub rotate: i32,
+	pub texel_region: Vec4,
+	pub pixel_region: Vec4,
+	pub screen_mode: ScreenMode,
+}
+
+/// A set parsed straight out of an in-memory file. Mip data is borrowed from the buffer instead of
+/// being copied into a `Vec` per texture, so scanning many files only costs the file buffers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SprSetRef<'a> {
+	pub endian: Endian,
+	pub flags: u32,
+	pub textures: Vec<TextureRef<'a>>,
+	pub sprites: Vec<SpriteRef<'a>>,
+}
+
+impl<'a> SprSetRef<'a> {
+	pub fn parse(data: &'a [u8]) -> Result<Self, SpriteError> {
+		let mut reader = Cursor::new(data);
+		let endian = detect_endianness(&mut reader)?;
+		structure::check_structure_with_endian(&mut reader, endian)?;
+		let tables = Tables::read(&mut reader, endian)?;
+
+		let mut textures = Vec::with_capacity(tables.texture_offsets.len());
+		for (i, pos) in tables.texture_offsets.iter().enumerate() {
+			let name_ptr = tables.texture_names.get(i).ok_or_else(|| {
+				SpriteError::Malformed(format!("Texture {i} has no entry in the name table"))
+			})?;
+			let name = read_name(&reader, *name_ptr)?;
+			textures.push(read_texture(&mut reader, *pos, name, endian)?);
+		}
+
+		let mut sprites = Vec::with_capacity(tables.sprite_count as usize);
+		for (i, name_ptr) in tables.sprite_names.iter().enumerate() {
+			let (sprite_pos, extra_pos) = tables.sprite_pos(i as u32);
+			reader.seek(SeekFrom::Start(sprite_pos))?;
+			let sprite: SpriteReader = reader.read_type(endian)?;
+			reader.seek(SeekFrom::Start(extra_pos))?;
+			let (_, screen_mode): (u32, ScreenMode) = reader.read_type(endian)?;
+			sprites.push(SpriteRef {
+				name: read_name(&reader, *name_ptr)?,
+				texture_index: sprite.texture_index as u32,
+				rotate: sprite.rotate,
+				texel_region: sprite.texel_region,
+				pixel_region: sprite.pixel_region,
+				screen_mode,
+			});
+		}
+
+		Ok(Self {
+			endian,
+			flags: tables.flags,
+			textures,
+			sprites,
+		})
+	}
+
+	/// Reads only the header and name tables, skipping all texture and sprite data.
+	pub fn read_names(data: &'a [u8]) -> Result<(Vec<&'a str>, Vec<&'a str>), SpriteError> {
+		let mut reader = Cursor::new(data);
+		let endian = detect_endianness(&mut reader)?;
+		let Tables {
+			texture_names,
+			sprite_names,
+			..
+		} = Tables::read(&mut reader, endian)?;
+		let texture_names = texture_names
+			.into_iter()
+			.map(|ptr| read_name(&reader, ptr))
+			.collect::<Result<_, _>>()?;
+		let sprite_names = sprite_names
+			.into_iter()
+			.map(|ptr| read_name(&reader, ptr))
+			.collect::<Result<_, _>>()?;
+		Ok((texture_names, sprite_names))
+	}
+
+	pub fn texture_of(&self, sprite: &SpriteRef<'a>) -> Option<&TextureRef<'a>> {
+		self.textures.get(sprite.texture_index as usize)
+	}
+}
+
+fn read_texture<'a>(
+	reader: &mut Cursor<&'a [u8]>,
+	pos: u64,
+	name: &'a str,
+	endian: Endian,
+) -> Result<TextureRef<'a>, SpriteError> {
+	let header = TextureHeader::read(reader, pos, endian)?;
+	let mut mips = Vec::with_capacity(header.mip_offsets.len());
+	for pos in header.mip_offsets {
+		let mip = MipHeader::read(reader, pos, endian)?;
+		let start = mip.data_pos as usize;
+		let data: &'a [u8] = *reader.get_ref();
+		let data = data
+			.get(start..start + mip.data_size as usize)
+			.ok_or_else(|| {
+				SpriteError::Malformed(format!(
+					"Mip data at {start:#x} runs past the end of the file"
+				))
+			})?;
+		mips.push(MipRef {
+			width: mip.width as u32,
+			height: mip.height as u32,
+			format: mip.format,
+			indices: mip.indices,
+			data,
+			endian,
+		});
+	}
+
+	Ok(TextureRef {
+		name,
+		kind: header.kind,
+		mip_count: header.mip_count,
+		array_size: header.array_size,
+		mips,
+	})
+}
+
+/// Borrows a NUL terminated name out of the buffer, failing if it runs off the end or is not
+/// valid UTF-8.
+fn read_name<'a>(reader: &Cursor<&'a [u8]>, ptr: u32) -> Result<&'a str, SpriteError> {
+	let data: &'a [u8] = *reader.get_ref();
+	let past_end =
+		|| SpriteError::Malformed(format!("Name at {ptr:#x} runs past the end of the file"));
+	let data = data.get(ptr as usize..).ok_or_else(past_end)?;
+	let len = data
+		.iter()
+		.position(|byte| *byte == 0)
+		.ok_or_else(past_end)?;
+	std::str::from_utf8(&data[..len])
+		.map_err(|_| SpriteError::Malformed(format!("Name at {ptr:#x} is not valid UTF-8")))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::patch::patch_sprites;
+	use crate::stream::{SprEvent, SprEventReader};
+
+	fn big_endian_set() -> Vec<u8> {
+		let region = Vec4 {
+			x: 1.0,
+			y: 2.0,
+			z: 3.0,
+			w: 4.0,
+		};
+		let set = SprSet::from_parts(
+			"test",
+			[(
+				"tex".to_string(),
+				Texture::new(DynamicImage::new_rgba8(8, 8)),
+			)],
+			[(
+				"icon".to_string(),
+				Sprite::new("tex", region, ScreenMode::HDTV1080),
+			)],
+			0,
+		)
+		.unwrap();
+		let options = WriteOptions {
+			endianness: Endian::Big,
+			..Default::default()
+		};
+		let mut out = Cursor::new(vec![]);
+		set.to_writer_with_options(&mut out, &options).unwrap();
+		out.into_inner()
+	}
+
+	#[test]
+	fn big_endian_round_trip() {
+		let mut data = big_endian_set();
+		assert_eq!(
+			detect_endianness(&mut Cursor::new(&data)).unwrap(),
+			Endian::Big
+		);
+
+		let set = SprSetRef::parse(&data).unwrap();
+		assert_eq!(set.endian, Endian::Big);
+		assert_eq!(set.textures[0].name, "tex");
+		assert_eq!(set.textures[0].mips[0].width, 8);
+		assert_eq!(set.sprites[0].name, "icon");
+		assert_eq!(set.sprites[0].pixel_region.z, 3.0);
+		assert_eq!(set.sprites[0].screen_mode, ScreenMode::HDTV1080);
+
+		let events = SprEventReader::new(Cursor::new(&data))
+			.collect::<Result<Vec<_>, _>>()
+			.unwrap();
+		assert!(matches!(
+			events[0],
+			SprEvent::Header {
+				endian: Endian::Big,
+				texture_count: 1,
+				sprite_count: 1,
+				..
+			}
+		));
+
+		let edit = SpriteEdit {
+			sprite: "icon".to_string(),
+			pixel_region: Some(Vec4 {
+				x: 0.0,
+				y: 0.0,
+				z: 8.0,
+				w: 8.0,
+			}),
+			screen_mode: Some(ScreenMode::HDTV720),
+		};
+		assert_eq!(patch_sprites(&mut data, &[edit]).unwrap(), 1);
+		let set = SprSet::from_reader(&mut Cursor::new(&data), None).unwrap();
+		let sprite = &set.sprites["icon"];
+		assert_eq!(sprite.pixel_region.w, 8.0);
+		assert_eq!(sprite.texel_region.w, 1.0);
+		assert_eq!(sprite.screen_mode, ScreenMode::HDTV720);
+	}
+
+	#[test]
+	fn patch_reference_only_screen_mode() {
+		let region = Vec4 {
+			x: 0.0,
+			y: 0.0,
+			z: 8.0,
+			w: 8.0,
+		};
+		let mut set = SprSet::new("test");
+		set.add_external_texture("shared", 2);
+		set.sprites.insert(
+			"icon".to_string(),
+			Sprite::new("shared", region, ScreenMode::HDTV1080),
+		);
+		let mut out = Cursor::new(vec![]);
+		set.to_writer(&mut out).unwrap();
+		let mut data = out.into_inner();
+
+		let edit = SpriteEdit {
+			sprite: "icon".to_string(),
+			pixel_region: None,
+			screen_mode: Some(ScreenMode::HDTV720),
+		};
+		assert_eq!(patch_sprites(&mut data, &[edit]).unwrap(), 1);
+		let set = SprSet::from_reader(&mut Cursor::new(&data), None).unwrap();
+		assert_eq!(set.sprites["icon"].screen_mode, ScreenMode::HDTV720);
+	}
+}
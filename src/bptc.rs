@@ -0,0 +1,98 @@
+//! BC6H and BC7 blocks, which texpresso doesn't handle. BC6H holds HDR colour, it is clamped to
+//! 0..1 since everything else here is 8 bits per channel. Only BC7 can be encoded, with Intel's
+//! ISPC texture compressor.
+use crate::*;
+
+const BLOCK_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bptc {
+	Bc7,
+	Bc6h { signed: bool },
+}
+
+impl Bptc {
+	pub(crate) fn from_dxgi_format(format: &DxgiFormat) -> Option<Self> {
+		match format {
+			DxgiFormat::BC7_UNorm | DxgiFormat::BC7_UNorm_sRGB => Some(Self::Bc7),
+			DxgiFormat::BC6H_UF16 => Some(Self::Bc6h { signed: false }),
+			DxgiFormat::BC6H_SF16 => Some(Self::Bc6h { signed: true }),
+			_ => None,
+		}
+	}
+
+	pub(crate) fn from_format(format: TextureFormat) -> Option<Self> {
+		match format {
+			TextureFormat::BC7 => Some(Self::Bc7),
+			TextureFormat::BC6H => Some(Self::Bc6h { signed: false }),
+			_ => None,
+		}
+	}
+
+	pub(crate) fn block_size(self) -> usize {
+		BLOCK_SIZE
+	}
+
+	fn decode_block(self, block: &[u8], out: &mut [u8; 64]) {
+		match self {
+			Self::Bc7 => bcdec_rs::bc7(block, out, 16),
+			Self::Bc6h { signed } => {
+				let mut rgb = [0f32; 48];
+				bcdec_rs::bc6h_float(block, &mut rgb, 12, signed);
+				for (pixel, rgb) in out.chunks_exact_mut(4).zip(rgb.chunks_exact(3)) {
+					for (channel, value) in pixel.iter_mut().zip(rgb) {
+						*channel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+					}
+					pixel[3] = 255;
+				}
+			}
+		}
+	}
+
+	/// Same contract as texpresso's `Format::decompress`: `output` is `width`x`height` RGBA8 and
+	/// blocks hanging over the edge are cut off. Blocks missing from `data` are left transparent
+	/// black.
+	pub(crate) fn decompress(self, data: &[u8], width: usize, height: usize, output: &mut [u8]) {
+		let blocks_wide = (width + 3) / 4;
+		let mut pixels = [0u8; 64];
+		for (i, block) in data
+			.chunks_exact(BLOCK_SIZE)
+			.take(blocks_wide * ((height + 3) / 4))
+			.enumerate()
+		{
+			self.decode_block(block, &mut pixels);
+			let (block_x, block_y) = (i % blocks_wide * 4, i / blocks_wide * 4);
+			for row in 0..4.min(height - block_y) {
+				let columns = 4.min(width - block_x);
+				let start = ((block_y + row) * width + block_x) * 4;
+				output[start..start + columns * 4]
+					.copy_from_slice(&pixels[row * 16..row * 16 + columns * 4]);
+			}
+		}
+	}
+}
+
+/// BC7 blocks for `image`, which is padded out to whole blocks by repeating its edges since the
+/// compressor only takes multiples of 4.
+pub(crate) fn encode_bc7(image: &image::RgbaImage, encoder: Encoder) -> Vec<u8> {
+	let (width, height) = image.dimensions();
+	let padded = image::RgbaImage::from_fn((width + 3) / 4 * 4, (height + 3) / 4 * 4, |x, y| {
+		*image.get_pixel(x.min(width - 1), y.min(height - 1))
+	});
+	let opaque = padded.pixels().all(|pixel| pixel.0[3] == 255);
+	let settings = match (encoder, opaque) {
+		(Encoder::Fast, true) => intel_tex_2::bc7::opaque_very_fast_settings(),
+		(Encoder::Fast, false) => intel_tex_2::bc7::alpha_very_fast_settings(),
+		(Encoder::Balanced, true) => intel_tex_2::bc7::opaque_basic_settings(),
+		(Encoder::Balanced, false) => intel_tex_2::bc7::alpha_basic_settings(),
+		(Encoder::Best, true) => intel_tex_2::bc7::opaque_slow_settings(),
+		(Encoder::Best, false) => intel_tex_2::bc7::alpha_slow_settings(),
+	};
+	let surface = intel_tex_2::RgbaSurface {
+		data: padded.as_raw(),
+		width: padded.width(),
+		height: padded.height(),
+		stride: padded.width() * 4,
+	};
+	intel_tex_2::bc7::compress_blocks(&settings, &surface)
+}
@@ -0,0 +1,254 @@
+//! Keeping a whole set's textures under a VRAM budget.
+use crate::resample::ResamplePreset;
+use crate::*;
+
+impl TextureFormat {
+	/// Bytes one mip of `width`x`height` takes up, `None` for formats the size isn't known of.
+	pub fn data_size(&self, width: u32, height: u32) -> Option<u64> {
+		let (width, height) = (width as u64, height as u64);
+		let blocks = ((width + 3) / 4) * ((height + 3) / 4);
+		match self {
+			Self::DXT1 | Self::DXT1a | Self::ATI1 => Some(blocks * 8),
+			Self::DXT3 | Self::DXT5 | Self::ATI2 | Self::BC7 | Self::BC6H => Some(blocks * 16),
+			Self::RGBA8 => Some(width * height * 4),
+			Self::RGB8 => Some(width * height * 3),
+			Self::RGB5 | Self::RGB5A1 | Self::RGBA4 | Self::L8A8 => Some(width * height * 2),
+			Self::A8 | Self::L8 => Some(width * height),
+			Self::Unknown => None,
+		}
+	}
+
+	/// Bytes a whole texture takes up: `mip_count` levels halving from `width`x`height`, in each
+	/// of `array_size` layers.
+	pub fn texture_size(
+		&self,
+		width: u32,
+		height: u32,
+		mip_count: u32,
+		array_size: u32,
+	) -> Option<u64> {
+		let chain = (0..mip_count.max(1))
+			.map(|level| self.data_size((width >> level).max(1), (height >> level).max(1)))
+			.sum::<Option<u64>>()?;
+		Some(chain * array_size.max(1) as u64)
+	}
+
+	/// The next smaller format that still keeps as much alpha as the texture uses.
+	fn downgrade(&self, alpha: AlphaUsage) -> Option<Self> {
+		match self {
+			Self::RGBA8 | Self::RGB8 | Self::RGB5A1 | Self::RGBA4 | Self::RGB5 | Self::BC7 => {
+				Some(Self::DXT5)
+			}
+			Self::DXT3 | Self::DXT5 => match alpha {
+				AlphaUsage::Opaque => Some(Self::DXT1),
+				AlphaUsage::OneBit => Some(Self::DXT1a),
+				AlphaUsage::Smooth => None,
+			},
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureBudget {
+	pub max_bytes: u64,
+	/// Allow switching textures to smaller formats
+	pub downgrade_formats: bool,
+	/// Allow halving textures, never below `min_size` on either side
+	pub downscale: bool,
+	pub min_size: u32,
+	pub resample: ResamplePreset,
+}
+
+impl TextureBudget {
+	pub fn megabytes(megabytes: u64) -> Self {
+		Self {
+			max_bytes: megabytes * 1024 * 1024,
+			downgrade_formats: true,
+			downscale: false,
+			min_size: 64,
+			resample: ResamplePreset::LineArt,
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetChange {
+	Format {
+		texture: String,
+		from: TextureFormat,
+		to: TextureFormat,
+		saved: u64,
+	},
+	Downscale {
+		texture: String,
+		from: (u32, u32),
+		to: (u32, u32),
+		saved: u64,
+	},
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetReport {
+	pub max_bytes: u64,
+	pub before: u64,
+	pub after: u64,
+	/// Cheapest changes first, the order they were (or would be) applied in
+	pub changes: Vec<BudgetChange>,
+}
+
+impl BudgetReport {
+	pub fn fits(&self) -> bool {
+		self.after <= self.max_bytes
+	}
+}
+
+/// How much a texture would suffer from losing precision: mean difference between neighbouring
+/// pixels, so flat fills and soft gradients score low and detailed art and text score high.
+fn detail(image: &DynamicImage) -> f64 {
+	let image = image.to_rgba8();
+	let (width, height) = image.dimensions();
+	let mut total = 0u64;
+	let mut count = 0u64;
+	for y in 0..height {
+		for x in 1..width {
+			let (a, b) = (image.get_pixel(x - 1, y).0, image.get_pixel(x, y).0);
+			total += (0..4).map(|c| a[c].abs_diff(b[c]) as u64).sum::<u64>();
+			count += 1;
+		}
+	}
+	if count == 0 {
+		return 0.0;
+	}
+	total as f64 / count as f64
+}
+
+impl SprSet {
+	/// Bytes the textures take up when written with `options`, every mip level
+	/// [`WriteOptions::mip_mode`] writes of every layer.
+	pub fn texture_bytes(&self, options: &WriteOptions) -> u64 {
+		self.textures
+			.iter()
+			.map(|(name, texture)| match &texture.raw {
+				Some(raw) => raw.data.len() as u64,
+				None => options
+					.format_for(name)
+					.texture_size(
+						texture.image.width(),
+						texture.image.height(),
+						options.mip_mode.levels(texture),
+						1 + texture.layers.len() as u32,
+					)
+					.unwrap_or(0),
+			})
+			.sum()
+	}
+
+	/// Works out format downgrades and downscales that bring the set under `budget`, least
+	/// detailed textures first. Formats are downgraded on every candidate before anything gets
+	/// downscaled. With `apply` the changes are made to `options`, the textures and the sprites
+	/// on them; otherwise they are only reported.
+	pub fn fit_budget(
+		&mut self,
+		options: &mut WriteOptions,
+		budget: &TextureBudget,
+		apply: bool,
+	) -> BudgetReport {
+		let before = self.texture_bytes(options);
+		let mut total = before;
+		let mut changes = vec![];
+		// Levels and layers as the writer lays them out, a halved texture can't have more levels
+		// than a full chain of its new size
+		let size =
+			|format: TextureFormat, (width, height): (u32, u32), (levels, layers): (u32, u32)| {
+				format
+					.texture_size(
+						width,
+						height,
+						levels.min(mipmap::full_mip_count(width, height)),
+						layers,
+					)
+					.unwrap_or(0)
+			};
+
+		let mut candidates = self
+			.textures
+			.iter()
+			.filter(|(_, texture)| texture.raw.is_none())
+			.map(|(name, texture)| {
+				(
+					name.clone(),
+					detail(&texture.image),
+					AlphaUsage::of(&texture.image),
+					options.format_for(name),
+					(texture.image.width(), texture.image.height()),
+					(
+						options.mip_mode.levels(texture),
+						1 + texture.layers.len() as u32,
+					),
+				)
+			})
+			.collect::<Vec<_>>();
+		candidates.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+
+		if budget.downgrade_formats {
+			for (name, _, alpha, format, dimensions, chain) in candidates.iter_mut() {
+				while total > budget.max_bytes {
+					let to = match format.downgrade(*alpha) {
+						Some(to) => to,
+						None => break,
+					};
+					let from_size = size(*format, *dimensions, *chain);
+					let to_size = size(to, *dimensions, *chain);
+					total -= from_size.saturating_sub(to_size).min(total);
+					changes.push(BudgetChange::Format {
+						texture: name.clone(),
+						from: *format,
+						to,
+						saved: from_size.saturating_sub(to_size),
+					});
+					*format = to;
+				}
+			}
+		}
+		if budget.downscale {
+			for (name, _, _, format, (width, height), chain) in candidates.iter_mut() {
+				while total > budget.max_bytes
+					&& *width / 2 >= budget.min_size
+					&& *height / 2 >= budget.min_size
+				{
+					let to = (*width / 2, *height / 2);
+					let from_size = size(*format, (*width, *height), *chain);
+					let to_size = size(*format, to, *chain);
+					total -= from_size.saturating_sub(to_size).min(total);
+					changes.push(BudgetChange::Downscale {
+						texture: name.clone(),
+						from: (*width, *height),
+						to,
+						saved: from_size.saturating_sub(to_size),
+					});
+					(*width, *height) = to;
+				}
+			}
+		}
+
+		if apply {
+			for change in &changes {
+				match change {
+					BudgetChange::Format { texture, to, .. } => {
+						options.texture_formats.insert(texture.clone(), *to);
+					}
+					BudgetChange::Downscale { texture, to, .. } => {
+						self.scale_texture(texture, *to, budget.resample)
+					}
+				}
+			}
+		}
+		BudgetReport {
+			max_bytes: budget.max_bytes,
+			before,
+			after: total,
+			changes,
+		}
+	}
+}
@@ -0,0 +1,49 @@
+//! What this build of the crate can do, for front-ends to grey out options up front instead of
+//! failing halfway through an operation.
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatSupport {
+	pub format: TextureFormat,
+	/// Whole textures in this format can be read
+	pub decode: bool,
+	/// Single sprites can be read without decoding the whole texture
+	pub decode_region: bool,
+	pub encode: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+	pub version: &'static str,
+	/// Layouts that can be read and written
+	pub variants: Vec<FormatVariant>,
+	pub texture_formats: Vec<FormatSupport>,
+	/// Cargo features this build was compiled with
+	pub features: Vec<&'static str>,
+}
+
+pub fn capabilities() -> Capabilities {
+	let features = [
+		("cli", cfg!(feature = "cli")),
+		("test-support", cfg!(feature = "test-support")),
+		("text", cfg!(feature = "text")),
+	];
+	Capabilities {
+		version: env!("CARGO_PKG_VERSION"),
+		variants: FormatVariant::ALL.to_vec(),
+		texture_formats: TextureFormat::ALL
+			.into_iter()
+			.filter(|format| *format != TextureFormat::Unknown)
+			.map(|format| FormatSupport {
+				format,
+				decode: format.can_decode(),
+				decode_region: format.can_decode_region(),
+				encode: format.can_encode(),
+			})
+			.collect(),
+		features: features
+			.into_iter()
+			.filter_map(|(name, enabled)| enabled.then_some(name))
+			.collect(),
+	}
+}
@@ -0,0 +1,87 @@
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+	Red,
+	Green,
+	Blue,
+	Alpha,
+	/// Rec. 601 luma of the source RGB
+	Luminance,
+	Zero,
+	One,
+}
+
+/// Where each of the output red, green, blue and alpha channels is taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelRemap(pub [Channel; 4]);
+
+impl Default for ChannelRemap {
+	fn default() -> Self {
+		Self::IDENTITY
+	}
+}
+
+impl ChannelRemap {
+	pub const IDENTITY: Self = Self([Channel::Red, Channel::Green, Channel::Blue, Channel::Alpha]);
+	pub const SWAP_RED_BLUE: Self =
+		Self([Channel::Blue, Channel::Green, Channel::Red, Channel::Alpha]);
+	/// Greyscale from the alpha channel, fully opaque
+	pub const ALPHA_TO_LUMINANCE: Self =
+		Self([Channel::Alpha, Channel::Alpha, Channel::Alpha, Channel::One]);
+	/// Alpha from the luminance, colour left as is
+	pub const LUMINANCE_TO_ALPHA: Self = Self([
+		Channel::Red,
+		Channel::Green,
+		Channel::Blue,
+		Channel::Luminance,
+	]);
+
+	pub fn is_identity(&self) -> bool {
+		*self == Self::IDENTITY
+	}
+
+	fn pick(channel: Channel, pixel: &[u8; 4]) -> u8 {
+		match channel {
+			Channel::Red => pixel[0],
+			Channel::Green => pixel[1],
+			Channel::Blue => pixel[2],
+			Channel::Alpha => pixel[3],
+			Channel::Luminance => {
+				((pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000)
+					as u8
+			}
+			Channel::Zero => 0,
+			Channel::One => 255,
+		}
+	}
+
+	pub fn apply(&self, image: &mut image::RgbaImage) {
+		if self.is_identity() {
+			return;
+		}
+		for pixel in image.pixels_mut() {
+			let source = pixel.0;
+			pixel.0 = self.0.map(|channel| Self::pick(channel, &source));
+		}
+	}
+
+	pub fn apply_dynamic(&self, image: &DynamicImage) -> DynamicImage {
+		let mut rgba = image.to_rgba8();
+		self.apply(&mut rgba);
+		DynamicImage::ImageRgba8(rgba)
+	}
+
+	/// Texture hook that remaps every texture read from `format` data. Pass it to
+	/// [`SprSet::from_reader_with_hooks`] to fix channel order at decode time.
+	pub fn decode_hook(
+		remaps: HashMap<TextureFormat, ChannelRemap>,
+	) -> impl FnMut(&str, &mut Texture) -> HookAction {
+		move |_, texture| {
+			if let Some(remap) = remaps.get(&texture.original_format) {
+				texture.image = remap.apply_dynamic(&texture.image);
+			}
+			HookAction::Keep
+		}
+	}
+}
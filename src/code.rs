@@ -0,0 +1,170 @@
+//! Stable codes for every error and warning, so tools can map them to help text and suppressions
+//! without matching on messages. Codes are never renumbered or reused; retired ones stay listed.
+use crate::*;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ErrorCode {
+	TextureSizeMismatch,
+	Io,
+	BinRead,
+	NulInName,
+	Dds,
+	Image,
+	Import,
+	Farc,
+	Malformed,
+	MissingData,
+	PlaceholderTexture,
+	UnsupportedTextureFormat,
+	ReservedSpriteIndex,
+	DuplicatePinnedIndex,
+	DbCollision,
+	NameInUse,
+	SpriteTooLarge,
+	UnsupportedVariant,
+	UnknownTextureVersion,
+	MissingTexture,
+	RegionOutOfBounds,
+	InvalidName,
+	InvalidOrder,
+	MissingDbEntry,
+	NameTooLong,
+	AlphaLost,
+}
+
+impl ErrorCode {
+	pub const ALL: &'static [ErrorCode] = &[
+		Self::TextureSizeMismatch,
+		Self::Io,
+		Self::BinRead,
+		Self::NulInName,
+		Self::Dds,
+		Self::Image,
+		Self::Import,
+		Self::Farc,
+		Self::Malformed,
+		Self::MissingData,
+		Self::PlaceholderTexture,
+		Self::UnsupportedTextureFormat,
+		Self::ReservedSpriteIndex,
+		Self::DuplicatePinnedIndex,
+		Self::DbCollision,
+		Self::NameInUse,
+		Self::SpriteTooLarge,
+		Self::UnsupportedVariant,
+		Self::UnknownTextureVersion,
+		Self::MissingTexture,
+		Self::RegionOutOfBounds,
+		Self::InvalidName,
+		Self::InvalidOrder,
+		Self::MissingDbEntry,
+		Self::NameTooLong,
+		Self::AlphaLost,
+	];
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::TextureSizeMismatch => "SPR0001",
+			Self::Io => "SPR0002",
+			Self::BinRead => "SPR0003",
+			Self::NulInName => "SPR0004",
+			Self::Dds => "SPR0005",
+			Self::Image => "SPR0006",
+			Self::Import => "SPR0007",
+			Self::Farc => "SPR0008",
+			Self::Malformed => "SPR0009",
+			Self::MissingData => "SPR0010",
+			Self::PlaceholderTexture => "SPR0011",
+			Self::UnsupportedTextureFormat => "SPR0012",
+			Self::ReservedSpriteIndex => "SPR0013",
+			Self::DuplicatePinnedIndex => "SPR0014",
+			Self::DbCollision => "SPR0015",
+			Self::NameInUse => "SPR0016",
+			Self::SpriteTooLarge => "SPR0017",
+			Self::UnsupportedVariant => "SPR0018",
+			Self::UnknownTextureVersion => "SPR0019",
+			Self::MissingTexture => "SPR0020",
+			Self::RegionOutOfBounds => "SPR0021",
+			Self::InvalidName => "SPR0022",
+			Self::InvalidOrder => "SPR0023",
+			Self::MissingDbEntry => "SPR0024",
+			Self::NameTooLong => "SPR0025",
+			Self::AlphaLost => "SPR0026",
+		}
+	}
+
+	pub fn parse(code: &str) -> Option<Self> {
+		Self::ALL.iter().copied().find(|c| c.as_str() == code)
+	}
+
+	/// One line of help text.
+	pub fn summary(&self) -> &'static str {
+		match self {
+			Self::TextureSizeMismatch => "texture size mismatch",
+			Self::Io => "could not read or write the file",
+			Self::BinRead => "file does not parse as a spr set",
+			Self::NulInName => "name contains a nul byte",
+			Self::Dds => "texture data could not be converted to or from DDS",
+			Self::Image => "image could not be encoded or decoded",
+			Self::Import => "imported data is invalid",
+			Self::Farc => "FARC archive is invalid",
+			Self::Malformed => "file structure is broken",
+			Self::MissingData => "data referenced by the set is missing",
+			Self::PlaceholderTexture => "texture is a placeholder for data that failed to decode",
+			Self::UnsupportedTextureFormat => "texture format not supported by the target",
+			Self::ReservedSpriteIndex => "sprite pinned to a reserved index",
+			Self::DuplicatePinnedIndex => "two sprites pinned to the same index",
+			Self::DbCollision => "name or id collides with another set in spr_db",
+			Self::NameInUse => "name already in use",
+			Self::SpriteTooLarge => "sprite larger than a texture page",
+			Self::UnsupportedVariant => "file layout variant not supported",
+			Self::UnknownTextureVersion => "texture uses an unknown TXP version",
+			Self::MissingTexture => "sprite refers to a texture that is not in the set",
+			Self::RegionOutOfBounds => "sprite region lies outside its texture",
+			Self::InvalidName => "name is empty, contains a nul byte or is not a plain file name",
+			Self::InvalidOrder => "order does not list every texture or sprite exactly once",
+			Self::MissingDbEntry => {
+				"spr_db has no entry for a texture or sprite, a generated name was used"
+			}
+			Self::NameTooLong => "name longer than the target build accepts",
+			Self::AlphaLost => "texture format can't store the alpha the texture uses",
+		}
+	}
+}
+
+impl fmt::Display for ErrorCode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+/// Something that was worked around while reading.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Warning {
+	pub code: ErrorCode,
+	pub message: String,
+}
+
+impl fmt::Display for Warning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.code, self.message)
+	}
+}
+
+impl SpriteError {
+	pub fn code(&self) -> ErrorCode {
+		match self {
+			Self::Io(_) => ErrorCode::Io,
+			Self::BinRead(_) => ErrorCode::BinRead,
+			Self::NulError(_) => ErrorCode::NulInName,
+			Self::Dds(_) => ErrorCode::Dds,
+			Self::Image(_) => ErrorCode::Image,
+			Self::Import(_) => ErrorCode::Import,
+			Self::Farc(_) => ErrorCode::Farc,
+			Self::Validation(code, _) => *code,
+			Self::Malformed(_) => ErrorCode::Malformed,
+			Self::Context(_, err) => err.code(),
+		}
+	}
+}
@@ -0,0 +1,233 @@
+//! Edits as plain values. Applying one hands back the command that undoes it, which gives
+//! editors undo/redo, and since they serialize a list of them doubles as a macro or edit script.
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+	/// Swaps a texture's image for a PNG of the same size
+	ReplaceTexture {
+		texture: String,
+		png: Vec<u8>,
+	},
+	/// Sets a sprite's pixel region. The texel region is worked out from it with `rounding` when
+	/// not given.
+	MoveSprite {
+		sprite: String,
+		pixel_region: Vec4,
+		texel_region: Option<Vec4>,
+		#[serde(default)]
+		rounding: TexelRounding,
+	},
+	RenameSprite {
+		old: String,
+		new: String,
+	},
+	/// Renames a texture and points its sprites at the new name
+	RenameTexture {
+		old: String,
+		new: String,
+	},
+	SetScreenMode {
+		sprite: String,
+		mode: ScreenMode,
+	},
+}
+
+fn name_in_use(what: &str, name: &str) -> SpriteError {
+	SpriteError::Validation(
+		ErrorCode::NameInUse,
+		format!("{what} name {name} is already used"),
+	)
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>, SpriteError> {
+	let mut png = Cursor::new(vec![]);
+	image.write_to(&mut png, image::ImageFormat::Png)?;
+	Ok(png.into_inner())
+}
+
+impl Command {
+	/// Applies the command, returning its inverse. Nothing changes when it fails.
+	pub fn apply(&self, set: &mut SprSet) -> Result<Command, SpriteError> {
+		match self {
+			Self::ReplaceTexture { texture, png } => {
+				let image = image::load_from_memory_with_format(png, image::ImageFormat::Png)?;
+				let target = set
+					.textures
+					.get_mut(texture)
+					.ok_or_else(|| SpriteError::not_in_set("Texture", texture))?;
+				if (image.width(), image.height()) != (target.image.width(), target.image.height())
+				{
+					return Err(SpriteError::Validation(
+						ErrorCode::TextureSizeMismatch,
+						format!(
+							"Replacement for {texture} is {}x{}, the texture is {}x{}",
+							image.width(),
+							image.height(),
+							target.image.width(),
+							target.image.height()
+						),
+					));
+				}
+				let inverse = Self::ReplaceTexture {
+					texture: texture.clone(),
+					png: encode_png(&target.image)?,
+				};
+				target.image = image;
+				Ok(inverse)
+			}
+			Self::MoveSprite {
+				sprite,
+				pixel_region,
+				texel_region,
+				rounding,
+			} => {
+				let target = set
+					.sprites
+					.get_mut(sprite)
+					.ok_or_else(|| SpriteError::not_in_set("Sprite", sprite))?;
+				let texture = set
+					.textures
+					.get(&target.texture_name)
+					.ok_or_else(|| SpriteError::missing_texture(sprite, &target.texture_name))?;
+				let inverse = Self::MoveSprite {
+					sprite: sprite.clone(),
+					pixel_region: target.pixel_region,
+					texel_region: Some(target.texel_region),
+					rounding: *rounding,
+				};
+				target.pixel_region = *pixel_region;
+				target.texel_region = texel_region.unwrap_or_else(|| {
+					rounding.texel_region(
+						pixel_region,
+						texture.image.width(),
+						texture.image.height(),
+					)
+				});
+				Ok(inverse)
+			}
+			Self::RenameSprite { old, new } => {
+				if set.sprites.contains_key(new) {
+					return Err(name_in_use("Sprite", new));
+				}
+				if !set.rename_sprite_key(old, new) {
+					return Err(SpriteError::not_in_set("Sprite", old));
+				}
+				set.usage.rename_sprite(old, new);
+				Ok(Self::RenameSprite {
+					old: new.clone(),
+					new: old.clone(),
+				})
+			}
+			Self::RenameTexture { old, new } => {
+				if set.textures.contains_key(new) {
+					return Err(name_in_use("Texture", new));
+				}
+				if !set.rename_texture_key(old, new) {
+					return Err(SpriteError::not_in_set("Texture", old));
+				}
+				for sprite in set.sprites.values_mut() {
+					if &sprite.texture_name == old {
+						sprite.texture_name = new.clone();
+					}
+				}
+				Ok(Self::RenameTexture {
+					old: new.clone(),
+					new: old.clone(),
+				})
+			}
+			Self::SetScreenMode { sprite, mode } => {
+				let target = set
+					.sprites
+					.get_mut(sprite)
+					.ok_or_else(|| SpriteError::not_in_set("Sprite", sprite))?;
+				let old = std::mem::replace(&mut target.screen_mode, *mode);
+				Ok(Self::SetScreenMode {
+					sprite: sprite.clone(),
+					mode: old,
+				})
+			}
+		}
+	}
+}
+
+/// Applied commands with their inverses, for undo and redo. [`CommandHistory::commands`] is the
+/// edit script that reproduces the current state from the one the history started at.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandHistory {
+	done: Vec<(Command, Command)>,
+	undone: Vec<(Command, Command)>,
+}
+
+impl CommandHistory {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Applies `command` and records it. Anything undone can't be redone afterwards.
+	pub fn apply(&mut self, set: &mut SprSet, command: Command) -> Result<(), SpriteError> {
+		let inverse = command.apply(set)?;
+		self.done.push((command, inverse));
+		self.undone.clear();
+		Ok(())
+	}
+
+	/// Undoes the last command, `false` when there is nothing to undo.
+	pub fn undo(&mut self, set: &mut SprSet) -> Result<bool, SpriteError> {
+		let Some((command, inverse)) = self.done.pop() else {
+			return Ok(false);
+		};
+		match inverse.apply(set) {
+			Ok(_) => {
+				self.undone.push((command, inverse));
+				Ok(true)
+			}
+			Err(err) => {
+				self.done.push((command, inverse));
+				Err(err)
+			}
+		}
+	}
+
+	/// Redoes the last undone command, `false` when there is nothing to redo.
+	pub fn redo(&mut self, set: &mut SprSet) -> Result<bool, SpriteError> {
+		let Some((command, inverse)) = self.undone.pop() else {
+			return Ok(false);
+		};
+		match command.apply(set) {
+			Ok(_) => {
+				self.done.push((command, inverse));
+				Ok(true)
+			}
+			Err(err) => {
+				self.undone.push((command, inverse));
+				Err(err)
+			}
+		}
+	}
+
+	pub fn commands(&self) -> impl Iterator<Item = &Command> {
+		self.done.iter().map(|(command, _)| command)
+	}
+
+	pub fn can_undo(&self) -> bool {
+		!self.done.is_empty()
+	}
+
+	pub fn can_redo(&self) -> bool {
+		!self.undone.is_empty()
+	}
+}
+
+impl SprSet {
+	/// Applies `commands` in order, stopping at the first that fails.
+	pub fn apply_commands<'a>(
+		&mut self,
+		commands: impl IntoIterator<Item = &'a Command>,
+	) -> Result<(), SpriteError> {
+		for command in commands {
+			command.apply(self)?;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,74 @@
+//! Options picked once for a whole tool instead of passed to every call.
+use crate::*;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SprConfig {
+	pub load: LoadOptions,
+	/// Includes the block compressor through [`WriteOptions::encoder`]
+	pub write: WriteOptions,
+	/// Threads used for parallel work like extraction, all cores when unset
+	pub threads: Option<usize>,
+}
+
+impl SprConfig {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn for_profile(profile: WriteProfile) -> Self {
+		Self {
+			write: profile.options(),
+			..Default::default()
+		}
+	}
+
+	pub fn read<R: io::Read + io::Seek>(
+		&self,
+		reader: &mut R,
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+	) -> Result<SprSet, SpriteError> {
+		SprSet::from_reader_with_options(reader, spr_db_set, &mut Hooks::new(), &self.load)
+	}
+
+	pub fn read_file(
+		&self,
+		path: impl AsRef<Path>,
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+	) -> Result<SprSet, SpriteError> {
+		let bytes = std::fs::read(path)?;
+		self.read(&mut Cursor::new(bytes), spr_db_set)
+	}
+
+	pub fn write<W: io::Write + io::Seek>(
+		&self,
+		set: &SprSet,
+		writer: &mut W,
+	) -> Result<WriteReport, SpriteError> {
+		set.to_writer_with_options(writer, &self.write)
+	}
+
+	pub fn write_file(
+		&self,
+		set: &SprSet,
+		path: impl AsRef<Path>,
+	) -> Result<WriteReport, SpriteError> {
+		let mut out = Cursor::new(vec![]);
+		let report = self.write(set, &mut out)?;
+		std::fs::write(path, out.into_inner())?;
+		Ok(report)
+	}
+
+	/// Runs `f` with parallel work limited to `threads`.
+	pub fn install<T: Send>(&self, f: impl FnOnce() -> T + Send) -> Result<T, SpriteError> {
+		let threads = match self.threads {
+			Some(threads) => threads,
+			None => return Ok(f()),
+		};
+		let pool = rayon::ThreadPoolBuilder::new()
+			.num_threads(threads)
+			.build()
+			.map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+		Ok(pool.install(f))
+	}
+}
@@ -0,0 +1,120 @@
+//! Resolving sets through diva_db's spr_db.
+use crate::*;
+
+impl SprSet {
+	/// Reads the set at `path`, naming it through the spr_db entry with the same file name when
+	/// `spr_db` lists one.
+	pub fn read(path: &str, spr_db: Option<&diva_db::spr::SprDb>) -> Result<Self, SpriteError> {
+		let path = std::path::Path::new(path);
+		let bytes = std::fs::read(path)?;
+		let spr_db_set = spr_db.and_then(|spr_db| {
+			let filename = path.file_name()?.to_str()?;
+			spr_db
+				.sets
+				.iter()
+				.find(|(_, set)| set.filename == filename)
+				.map(|(_, set)| set)
+		});
+		Self::from_reader(&mut Cursor::new(bytes), spr_db_set)
+	}
+
+	/// Loads the set `set_id` from a game data folder, looking for its file under `2d/` (where the
+	/// game keeps them) or the root itself, either packed in a FARC or loose.
+	pub fn read_by_id(
+		data_root: &std::path::Path,
+		set_id: u32,
+		spr_db: &diva_db::spr::SprDb,
+	) -> Result<Self, SpriteError> {
+		Self::read_by_id_with_config(data_root, set_id, spr_db, &SprConfig::default())
+	}
+
+	/// [`SprSet::read_by_id`] reading with `config`'s load options.
+	pub fn read_by_id_with_config(
+		data_root: &std::path::Path,
+		set_id: u32,
+		spr_db: &diva_db::spr::SprDb,
+		config: &SprConfig,
+	) -> Result<Self, SpriteError> {
+		let (_, spr_db_set) = spr_db
+			.sets
+			.iter()
+			.find(|(id, _)| u32::from(**id) == set_id)
+			.ok_or_else(|| SpriteError::Import(format!("Set {set_id} is not in spr_db")))?;
+		let filename = &spr_db_set.filename;
+		let farc_name = format!("{}.farc", filename.trim_end_matches(".bin"));
+
+		for dir in [data_root.join("2d"), data_root.to_path_buf()] {
+			let farc_path = dir.join(&farc_name);
+			if farc_path.is_file() {
+				let farc = farc::Farc::read(&farc_path)?;
+				let entry = farc.get(filename).ok_or_else(|| {
+					SpriteError::Farc(format!(
+						"{} does not contain {filename}",
+						farc_path.to_string_lossy()
+					))
+				})?;
+				return config.read(&mut Cursor::new(&entry.data), Some(spr_db_set));
+			}
+			let path = dir.join(filename);
+			if path.is_file() {
+				return config.read_file(path, Some(spr_db_set));
+			}
+		}
+		Err(SpriteError::Import(format!(
+			"Could not find {filename} under {}",
+			data_root.to_string_lossy()
+		)))
+	}
+}
+
+pub fn get_spr_db_set<'a>(
+	filename: &str,
+	spr_db: &'a diva_db::spr::SprDb,
+) -> Option<&'a diva_db::spr::SprDbSet> {
+	let (_, set) = spr_db.sets.iter().find(|x| x.1.filename == filename)?;
+	Some(set)
+}
+
+/// How names in spr_db relate to the names inside a set. The db holds full names like
+/// `SPR_FOO_BUTTON`, the set only `BUTTON`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum NamePolicy {
+	/// The set name and an underscore for sprites, with `SPR` swapped for `SPRTEX` for textures
+	#[default]
+	SetName,
+	/// Db names are used as they are
+	NoStrip,
+	Custom {
+		sprite: String,
+		texture: String,
+	},
+}
+
+impl NamePolicy {
+	pub fn sprite_prefix(&self, set_name: &str) -> String {
+		match self {
+			Self::SetName => format!("{set_name}_"),
+			Self::NoStrip => String::new(),
+			Self::Custom { sprite, .. } => sprite.clone(),
+		}
+	}
+
+	pub fn texture_prefix(&self, set_name: &str) -> String {
+		match self {
+			Self::SetName => self.sprite_prefix(set_name).replace("SPR", "SPRTEX"),
+			Self::NoStrip => String::new(),
+			Self::Custom { texture, .. } => texture.clone(),
+		}
+	}
+
+	/// Set name of a sprite called `db_name` in the db. Names without the prefix are kept whole.
+	pub fn strip_sprite(&self, set_name: &str, db_name: &str) -> String {
+		let prefix = self.sprite_prefix(set_name);
+		db_name.strip_prefix(&prefix).unwrap_or(db_name).to_string()
+	}
+
+	pub fn strip_texture(&self, set_name: &str, db_name: &str) -> String {
+		let prefix = self.texture_prefix(set_name);
+		db_name.strip_prefix(&prefix).unwrap_or(db_name).to_string()
+	}
+}
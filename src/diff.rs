@@ -0,0 +1,153 @@
+use crate::*;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<T> {
+	Added(T),
+	Removed,
+	Modified(T),
+}
+
+/// The changes one mod makes to a base set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SprSetDiff {
+	pub label: String,
+	pub sprites: BTreeMap<String, Change<Sprite>>,
+	pub textures: BTreeMap<String, Change<Texture>>,
+}
+
+fn diff_maps<T: Clone + PartialEq>(
+	base: &IndexMap<String, T>,
+	modified: &IndexMap<String, T>,
+) -> BTreeMap<String, Change<T>> {
+	let mut changes = BTreeMap::new();
+	for (name, value) in modified {
+		match base.get(name) {
+			None => {
+				changes.insert(name.clone(), Change::Added(value.clone()));
+			}
+			Some(base_value) if base_value != value => {
+				changes.insert(name.clone(), Change::Modified(value.clone()));
+			}
+			Some(_) => {}
+		}
+	}
+	for name in base.keys() {
+		if !modified.contains_key(name) {
+			changes.insert(name.clone(), Change::Removed);
+		}
+	}
+	changes
+}
+
+fn apply_changes<T: Clone>(map: &mut IndexMap<String, T>, changes: &BTreeMap<String, Change<T>>) {
+	for (name, change) in changes {
+		match change {
+			Change::Added(value) | Change::Modified(value) => {
+				map.insert(name.clone(), value.clone());
+			}
+			Change::Removed => {
+				map.shift_remove(name);
+			}
+		}
+	}
+}
+
+impl SprSetDiff {
+	pub fn new(label: &str, base: &SprSet, modified: &SprSet) -> Self {
+		Self {
+			label: label.to_string(),
+			sprites: diff_maps(&base.sprites, &modified.sprites),
+			textures: diff_maps(&base.textures, &modified.textures),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.sprites.is_empty() && self.textures.is_empty()
+	}
+
+	pub fn apply(&self, set: &mut SprSet) {
+		apply_changes(&mut set.sprites, &self.sprites);
+		apply_changes(&mut set.textures, &self.textures);
+	}
+
+	fn texture_of(&self, sprite: &str) -> Option<&str> {
+		match self.sprites.get(sprite)? {
+			Change::Added(sprite) | Change::Modified(sprite) => Some(&sprite.texture_name),
+			Change::Removed => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictKind {
+	/// Several mods change the same sprite in different ways
+	Sprite,
+	/// Several mods change the same texture in different ways
+	Texture,
+	/// One mod repaints a texture that another mod places sprites on
+	SpriteOnTexture,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+	pub kind: ConflictKind,
+	pub name: String,
+	pub mods: Vec<String>,
+}
+
+fn entry_conflicts<T: PartialEq>(
+	kind: ConflictKind,
+	diffs: &[SprSetDiff],
+	entries: impl Fn(&SprSetDiff) -> &BTreeMap<String, Change<T>>,
+) -> Vec<Conflict> {
+	let mut touched: BTreeMap<&str, Vec<(&str, &Change<T>)>> = BTreeMap::new();
+	for diff in diffs {
+		for (name, change) in entries(diff) {
+			touched
+				.entry(name.as_str())
+				.or_default()
+				.push((diff.label.as_str(), change));
+		}
+	}
+	touched
+		.into_iter()
+		.filter(|(_, changes)| {
+			changes.len() > 1 && changes.iter().any(|(_, change)| *change != changes[0].1)
+		})
+		.map(|(name, changes)| Conflict {
+			kind,
+			name: name.to_string(),
+			mods: changes.iter().map(|(label, _)| label.to_string()).collect(),
+		})
+		.collect()
+}
+
+/// Finds places where diffs from several mods against the same base set step on each other.
+/// Mods making the exact same change to an entry are not reported.
+pub fn detect_conflicts(diffs: &[SprSetDiff]) -> Vec<Conflict> {
+	let mut conflicts = entry_conflicts(ConflictKind::Sprite, diffs, |diff| &diff.sprites);
+	conflicts.extend(entry_conflicts(ConflictKind::Texture, diffs, |diff| {
+		&diff.textures
+	}));
+
+	for texture_diff in diffs {
+		for texture in texture_diff.textures.keys() {
+			for sprite_diff in diffs {
+				if sprite_diff.label == texture_diff.label {
+					continue;
+				}
+				for sprite in sprite_diff.sprites.keys() {
+					if sprite_diff.texture_of(sprite) == Some(texture.as_str()) {
+						conflicts.push(Conflict {
+							kind: ConflictKind::SpriteOnTexture,
+							name: sprite.clone(),
+							mods: vec![texture_diff.label.clone(), sprite_diff.label.clone()],
+						});
+					}
+				}
+			}
+		}
+	}
+	conflicts
+}
@@ -0,0 +1,231 @@
+//! A set as a folder of images plus `manifest.json`, for editing in tools that know nothing about
+//! the format. Images are stored top-down like any other image file.
+use crate::*;
+use image::ImageFormat;
+use std::path::{Component, Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirTexture {
+	pub name: String,
+	/// Relative to the folder
+	pub file: String,
+	pub format: TextureFormat,
+	pub width: u32,
+	pub height: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirSprite {
+	pub name: String,
+	pub texture: String,
+	pub x: f32,
+	pub y: f32,
+	pub width: f32,
+	pub height: f32,
+	pub screen_mode: ScreenMode,
+	pub rotate: i32,
+}
+
+/// Contents of `manifest.json`. Textures and sprites are listed in write order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DirManifest {
+	pub name: String,
+	pub flags: u32,
+	pub textures: Vec<DirTexture>,
+	pub sprites: Vec<DirSprite>,
+}
+
+fn write_dds(texture: &Texture, path: &Path) -> Result<(), SpriteError> {
+	let format = if texture.original_format.can_encode() {
+		texture.original_format
+	} else {
+		TextureFormat::RGBA8
+	};
+	let dds = encode_texture(
+		&texture.image,
+		format,
+		FlipConvention::TopDown,
+		Encoder::default(),
+	)?;
+	let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+	dds.write(&mut file)?;
+	Ok(())
+}
+
+/// `dir/<name>.<extension>`, refusing names that would put the file anywhere else: separators,
+/// `.` or `..`, and absolute paths.
+pub(crate) fn file_in_dir(dir: &Path, name: &str, extension: &str) -> Result<PathBuf, SpriteError> {
+	let mut components = Path::new(name).components();
+	let plain = matches!(
+		(components.next(), components.next()),
+		(Some(Component::Normal(_)), None)
+	);
+	if !plain || name.contains(['/', '\\']) {
+		return Err(SpriteError::Validation(
+			ErrorCode::InvalidName,
+			format!("{name:?} can't be used as a file name"),
+		));
+	}
+	Ok(dir.join(format!("{name}.{extension}")))
+}
+
+impl SprSet {
+	/// Writes every texture to `path` as `<name>.<ext>` in `format` and the sprites to
+	/// `manifest.json`. DDS keeps each texture's original format where it can be encoded, other
+	/// formats go through the image crate.
+	pub fn export_dir(&self, path: &Path, format: ImageFormat) -> Result<DirManifest, SpriteError> {
+		std::fs::create_dir_all(path)?;
+		let extension = format.extensions_str().first().copied().unwrap_or("png");
+		let mut textures = vec![];
+		for name in self.texture_order() {
+			let texture = &self.textures[name];
+			let file = file_in_dir(path, name, extension)
+				.map_err(|err| err.in_texture(textures.len(), name))?;
+			match format {
+				ImageFormat::Dds => write_dds(texture, &file)?,
+				_ => texture.image.save_with_format(&file, format)?,
+			}
+			let file = format!("{name}.{extension}");
+			textures.push(DirTexture {
+				name: name.clone(),
+				file,
+				format: texture.original_format,
+				width: texture.image.width(),
+				height: texture.image.height(),
+			});
+		}
+		let sprites = self
+			.sprite_order()
+			.into_iter()
+			.map(|name| {
+				let sprite = &self.sprites[name];
+				DirSprite {
+					name: name.clone(),
+					texture: sprite.texture_name.clone(),
+					x: sprite.pixel_region.x,
+					y: sprite.pixel_region.y,
+					width: sprite.pixel_region.z,
+					height: sprite.pixel_region.w,
+					screen_mode: sprite.screen_mode,
+					rotate: sprite.rotate,
+				}
+			})
+			.collect();
+		let manifest = DirManifest {
+			name: self.name.clone(),
+			flags: self.flags,
+			textures,
+			sprites,
+		};
+		let json = serde_json::to_string_pretty(&manifest)
+			.map_err(|err| SpriteError::Import(err.to_string()))?;
+		std::fs::write(path.join(MANIFEST_FILE), json)?;
+		Ok(manifest)
+	}
+}
+
+pub(crate) fn read_image(path: &Path) -> Result<DynamicImage, SpriteError> {
+	let is_dds = path
+		.extension()
+		.is_some_and(|extension| extension.eq_ignore_ascii_case("dds"));
+	if !is_dds {
+		return Ok(image::open(path)?);
+	}
+	let dds = Dds::read(std::io::BufReader::new(std::fs::File::open(path)?))?;
+	dds_to_dynamic(&dds, FlipConvention::TopDown).ok_or_else(|| {
+		SpriteError::Dds(format!(
+			"Cannot decode {}, its format is not supported",
+			path.to_string_lossy()
+		))
+	})
+}
+
+impl SprSet {
+	/// Reads a folder written by [`SprSet::export_dir`] or laid out the same way. Every image has
+	/// to be the size the manifest gives and every sprite has to lie inside its texture.
+	pub fn import_dir(path: &Path) -> Result<Self, SpriteError> {
+		let manifest_path = path.join(MANIFEST_FILE);
+		let json = std::fs::read_to_string(&manifest_path)?;
+		let manifest: DirManifest = serde_json::from_str(&json).map_err(|err| {
+			SpriteError::Import(format!(
+				"{} is not a valid manifest: {err}",
+				manifest_path.to_string_lossy()
+			))
+		})?;
+
+		let mut textures = IndexMap::with_capacity(manifest.textures.len());
+		for entry in &manifest.textures {
+			let file = path.join(&entry.file);
+			let image = read_image(&file).map_err(|err| {
+				SpriteError::Import(format!(
+					"Texture {} could not be read from {}: {err}",
+					entry.name,
+					file.to_string_lossy()
+				))
+			})?;
+			if (image.width(), image.height()) != (entry.width, entry.height) {
+				return Err(SpriteError::Validation(
+					ErrorCode::TextureSizeMismatch,
+					format!(
+						"Texture {} is {}x{} in {} but the manifest says {}x{}",
+						entry.name,
+						image.width(),
+						image.height(),
+						file.to_string_lossy(),
+						entry.width,
+						entry.height
+					),
+				));
+			}
+			let texture = Texture {
+				original_format: entry.format,
+				..Texture::new(image)
+			};
+			if textures.insert(entry.name.clone(), texture).is_some() {
+				return Err(SpriteError::Validation(
+					ErrorCode::NameInUse,
+					format!("Texture {} is listed twice", entry.name),
+				));
+			}
+		}
+
+		let mut sprites = IndexMap::with_capacity(manifest.sprites.len());
+		for entry in &manifest.sprites {
+			let pixel_region = Vec4 {
+				x: entry.x,
+				y: entry.y,
+				z: entry.width,
+				w: entry.height,
+			};
+			let sprite = Sprite {
+				rotate: entry.rotate,
+				..Sprite::new(&entry.texture, pixel_region, entry.screen_mode)
+			};
+			if sprites.insert(entry.name.clone(), sprite).is_some() {
+				return Err(SpriteError::Validation(
+					ErrorCode::NameInUse,
+					format!("Sprite {} is listed twice", entry.name),
+				));
+			}
+		}
+
+		let mut set = Self::from_parts(&manifest.name, textures, sprites, manifest.flags)?;
+		set.set_texture_order(
+			manifest
+				.textures
+				.into_iter()
+				.map(|entry| entry.name)
+				.collect(),
+		)?;
+		set.set_sprite_order(
+			manifest
+				.sprites
+				.into_iter()
+				.map(|entry| entry.name)
+				.collect(),
+		)?;
+		Ok(set)
+	}
+}
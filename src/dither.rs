@@ -0,0 +1,91 @@
+use crate::*;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Dithering {
+	#[default]
+	None,
+	/// 4x4 Bayer matrix. Stable pattern, nothing bleeds across sprite edges
+	Ordered,
+	/// Floyd-Steinberg error diffusion. Smoother gradients, noisier flat areas
+	FloydSteinberg,
+}
+
+const BAYER_4X4: [[f32; 4]; 4] = [
+	[0.0, 8.0, 2.0, 10.0],
+	[12.0, 4.0, 14.0, 6.0],
+	[3.0, 11.0, 1.0, 9.0],
+	[15.0, 7.0, 13.0, 5.0],
+];
+
+impl TextureFormat {
+	/// Bits kept per RGBA channel by formats that store less than 8, which are the ones worth
+	/// dithering for. Only formats the writer can encode are listed, the 16 bit packed ones
+	/// can't be written.
+	pub fn channel_bits(&self) -> Option<[u8; 4]> {
+		match self {
+			Self::DXT1 => Some([5, 6, 5, 8]),
+			Self::DXT1a => Some([5, 6, 5, 1]),
+			_ => None,
+		}
+	}
+}
+
+fn quantize(value: f32, bits: u8) -> f32 {
+	let levels = ((1u32 << bits) - 1) as f32;
+	(value.clamp(0.0, 255.0) / 255.0 * levels).round() / levels * 255.0
+}
+
+/// Quantizes `image` to `bits` per channel in place, spreading the rounding error with `mode`.
+pub fn dither(image: &mut image::RgbaImage, bits: [u8; 4], mode: Dithering) {
+	match mode {
+		Dithering::None => {}
+		Dithering::Ordered => {
+			for (x, y, pixel) in image.enumerate_pixels_mut() {
+				let threshold = BAYER_4X4[(y % 4) as usize][(x % 4) as usize] / 16.0 - 0.5;
+				for (channel, bits) in pixel.0.iter_mut().zip(bits) {
+					if bits >= 8 {
+						continue;
+					}
+					let step = 255.0 / ((1u32 << bits) - 1) as f32;
+					*channel = quantize(*channel as f32 + threshold * step, bits) as u8;
+				}
+			}
+		}
+		Dithering::FloydSteinberg => {
+			let (width, height) = (image.width() as usize, image.height() as usize);
+			let mut values = image
+				.as_raw()
+				.iter()
+				.map(|value| *value as f32)
+				.collect::<Vec<_>>();
+			for y in 0..height {
+				for x in 0..width {
+					for (c, bits) in bits.into_iter().enumerate() {
+						if bits >= 8 {
+							continue;
+						}
+						let i = (y * width + x) * 4 + c;
+						let old = values[i];
+						let new = quantize(old, bits);
+						values[i] = new;
+						let error = old - new;
+						let mut spread = |dx: isize, dy: usize, weight: f32| {
+							let nx = x as isize + dx;
+							if nx < 0 || nx as usize >= width || y + dy >= height {
+								return;
+							}
+							values[((y + dy) * width + nx as usize) * 4 + c] += error * weight;
+						};
+						spread(1, 0, 7.0 / 16.0);
+						spread(-1, 1, 3.0 / 16.0);
+						spread(0, 1, 5.0 / 16.0);
+						spread(1, 1, 1.0 / 16.0);
+					}
+				}
+			}
+			for (out, value) in image.iter_mut().zip(values) {
+				*out = value.clamp(0.0, 255.0).round() as u8;
+			}
+		}
+	}
+}
@@ -0,0 +1,87 @@
+//! Plain text listing of a set file, for reviewing binary changes with an ordinary diff.
+use crate::*;
+use std::fmt;
+
+fn format_region(region: &Vec4) -> String {
+	format!("{} {} {} {}", region.x, region.y, region.z, region.w)
+}
+
+fn offset_in(data: &[u8], slice: &[u8]) -> usize {
+	slice.as_ptr() as usize - data.as_ptr() as usize
+}
+
+/// Lists the header, every texture with its mips and where their data sits, and every sprite
+/// record, in file order so the same bytes always give the same text.
+pub fn dump_bytes<W: fmt::Write>(data: &[u8], out: &mut W) -> Result<(), SpriteError> {
+	let set = SprSetRef::parse(data)?;
+	let mut header = Cursor::new(data);
+	let fields: [u32; 8] = header.read_type(set.endian)?;
+	let mut lines = vec![
+		format!("size {:#x}", data.len()),
+		format!("endian {:?}", set.endian),
+		"header".to_string(),
+	];
+	for (name, value) in [
+		"flags",
+		"textures_pos",
+		"texture_count",
+		"sprite_count",
+		"sprites_pos",
+		"texture_names_pos",
+		"sprite_names_pos",
+		"sprite_extras_pos",
+	]
+	.iter()
+	.zip(fields)
+	{
+		lines.push(format!("  {name:<18} {value:#010x}"));
+	}
+
+	lines.push(format!("textures {}", set.textures.len()));
+	for (i, texture) in set.textures.iter().enumerate() {
+		lines.push(format!(
+			"  [{i}] {} {:?} mips {} layers {}",
+			texture.name, texture.kind, texture.mip_count, texture.array_size
+		));
+		for (j, mip) in texture.mips.iter().enumerate() {
+			let (layer, level) = match texture.mip_count {
+				0 => (0, j as u32),
+				count => (j as u32 / count, j as u32 % count),
+			};
+			lines.push(format!(
+				"    layer {layer} mip {level} {}x{} {:?} index {} array_index {} data {:#x}+{:#x}",
+				mip.width,
+				mip.height,
+				mip.format,
+				mip.indices.index,
+				mip.indices.array_index,
+				offset_in(data, mip.data),
+				mip.data.len()
+			));
+		}
+	}
+
+	lines.push(format!("sprites {}", set.sprites.len()));
+	for (i, sprite) in set.sprites.iter().enumerate() {
+		lines.push(format!(
+			"  [{i}] {} texture {} rotate {} {:?}",
+			sprite.name, sprite.texture_index, sprite.rotate, sprite.screen_mode
+		));
+		lines.push(format!("    texel {}", format_region(&sprite.texel_region)));
+		lines.push(format!("    pixel {}", format_region(&sprite.pixel_region)));
+	}
+
+	for line in lines {
+		writeln!(out, "{line}").map_err(|_| SpriteError::Io(io::ErrorKind::Other.into()))?;
+	}
+	Ok(())
+}
+
+impl SprSet {
+	/// [`dump_bytes`] of the set as [`SprSet::to_writer`] would write it.
+	pub fn dump<W: fmt::Write>(&self, out: &mut W) -> Result<(), SpriteError> {
+		let mut buffer = Cursor::new(vec![]);
+		self.to_writer_with_options(&mut buffer, &WriteOptions::default())?;
+		dump_bytes(buffer.get_ref(), out)
+	}
+}
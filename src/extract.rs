@@ -0,0 +1,371 @@
+use crate::*;
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedSprite {
+	pub name: String,
+	pub path: PathBuf,
+	pub width: u32,
+	pub height: u32,
+}
+
+/// How pixel regions with fractional coordinates are cut out. Regions are floats and some
+/// original sets place sprites on half pixels; the region itself is never changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SubPixel {
+	/// Drop the fractions
+	#[default]
+	Floor,
+	/// Round position and size to the nearest pixel
+	Round,
+	/// Sample the texture at the exact region, the crop is the region's size rounded
+	Bilinear,
+}
+
+impl SubPixel {
+	/// Whole pixels for `region` as x, y, width and height. For [`SubPixel::Bilinear`] that is
+	/// every pixel the region touches.
+	pub fn bounds(self, region: &Vec4) -> (u32, u32, u32, u32) {
+		let (x, y) = (region.x.max(0.0), region.y.max(0.0));
+		let (width, height) = (region.z.max(0.0), region.w.max(0.0));
+		match self {
+			Self::Floor => (x as u32, y as u32, width as u32, height as u32),
+			Self::Round => (
+				x.round() as u32,
+				y.round() as u32,
+				width.round() as u32,
+				height.round() as u32,
+			),
+			Self::Bilinear => (
+				x.floor() as u32,
+				y.floor() as u32,
+				((x + width).ceil() - x.floor()) as u32,
+				((y + height).ceil() - y.floor()) as u32,
+			),
+		}
+	}
+
+	pub fn crop(self, texture: &DynamicImage, region: &Vec4) -> DynamicImage {
+		let (width, height) = (region.z.round(), region.w.round());
+		if self != Self::Bilinear
+			|| width < 1.0
+			|| height < 1.0
+			|| texture.width() == 0
+			|| texture.height() == 0
+		{
+			let (x, y, width, height) = self.bounds(region);
+			return texture.crop_imm(x, y, width, height);
+		}
+		let source = texture.to_rgba8();
+		let (scale_x, scale_y) = (region.z / width, region.w / height);
+		let image = image::RgbaImage::from_fn(width as u32, height as u32, |x, y| {
+			sample_bilinear(
+				&source,
+				region.x + (x as f32 + 0.5) * scale_x - 0.5,
+				region.y + (y as f32 + 0.5) * scale_y - 0.5,
+			)
+		});
+		DynamicImage::ImageRgba8(image)
+	}
+}
+
+/// Colour is weighted by alpha so transparent neighbours don't bleed into the edges.
+fn sample_bilinear(image: &image::RgbaImage, x: f32, y: f32) -> image::Rgba<u8> {
+	let x = x.clamp(0.0, (image.width() - 1) as f32);
+	let y = y.clamp(0.0, (image.height() - 1) as f32);
+	let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+	let (x1, y1) = (
+		(x0 + 1).min(image.width() - 1),
+		(y0 + 1).min(image.height() - 1),
+	);
+	let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+	let mut color = [0f32; 3];
+	let mut alpha = 0f32;
+	for (px, py, weight) in [
+		(x0, y0, (1.0 - fx) * (1.0 - fy)),
+		(x1, y0, fx * (1.0 - fy)),
+		(x0, y1, (1.0 - fx) * fy),
+		(x1, y1, fx * fy),
+	] {
+		let pixel = image.get_pixel(px, py).0;
+		let weight = weight * pixel[3] as f32;
+		for (sum, channel) in color.iter_mut().zip(pixel) {
+			*sum += channel as f32 * weight;
+		}
+		alpha += weight;
+	}
+	if alpha <= 0.0 {
+		return image::Rgba([0, 0, 0, 0]);
+	}
+	image::Rgba([
+		(color[0] / alpha).round() as u8,
+		(color[1] / alpha).round() as u8,
+		(color[2] / alpha).round() as u8,
+		alpha.round() as u8,
+	])
+}
+
+pub(crate) fn crop_sprite(texture: &DynamicImage, sprite: &Sprite) -> DynamicImage {
+	SubPixel::Floor.crop(texture, &sprite.pixel_region)
+}
+
+/// Whether `region` is finite and lies within a `width`x`height` texture.
+pub(crate) fn region_inside(region: &Vec4, width: u32, height: u32) -> bool {
+	let (width, height) = (width as f32, height as f32);
+	[region.x, region.y, region.z, region.w]
+		.iter()
+		.all(|value| value.is_finite())
+		&& region.x >= 0.0
+		&& region.y >= 0.0
+		&& region.z >= 0.0
+		&& region.w >= 0.0
+		&& region.x + region.z <= width
+		&& region.y + region.w <= height
+}
+
+/// `region` with negative sizes made positive, and whether it ran right to left and bottom to
+/// top, which the game draws mirrored.
+fn unflipped_region(region: &Vec4) -> (Vec4, bool, bool) {
+	let (flip_x, flip_y) = (region.z < 0.0, region.w < 0.0);
+	let region = Vec4 {
+		x: if flip_x {
+			region.x + region.z
+		} else {
+			region.x
+		},
+		y: if flip_y {
+			region.y + region.w
+		} else {
+			region.y
+		},
+		z: region.z.abs(),
+		w: region.w.abs(),
+	};
+	(region, flip_x, flip_y)
+}
+
+/// `sprite` cut out of `texture` the way the game shows it, mirrored where its region is and
+/// turned by its quarter turns.
+pub(crate) fn crop_sprite_oriented(
+	texture: &DynamicImage,
+	sprite: &Sprite,
+	sub_pixel: SubPixel,
+) -> DynamicImage {
+	let (region, flip_x, flip_y) = unflipped_region(&sprite.pixel_region);
+	let mut image = sub_pixel.crop(texture, &region);
+	if flip_x {
+		image = image.fliph();
+	}
+	if flip_y {
+		image = image.flipv();
+	}
+	match sprite.rotate.rem_euclid(4) {
+		1 => image.rotate90(),
+		2 => image.rotate180(),
+		3 => image.rotate270(),
+		_ => image,
+	}
+}
+
+/// Turns an upright image of `sprite`, like [`crop_sprite_oriented`] gives, back into the way it
+/// is stored on the texture.
+pub(crate) fn restore_orientation(image: &DynamicImage, sprite: &Sprite) -> DynamicImage {
+	let (_, flip_x, flip_y) = unflipped_region(&sprite.pixel_region);
+	let mut image = match sprite.rotate.rem_euclid(4) {
+		1 => image.rotate270(),
+		2 => image.rotate180(),
+		3 => image.rotate90(),
+		_ => image.clone(),
+	};
+	if flip_x {
+		image = image.fliph();
+	}
+	if flip_y {
+		image = image.flipv();
+	}
+	image
+}
+
+/// A sprite whose region reaches past its texture. It is still extracted, cut off at the edge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutOfBoundsSprite {
+	pub name: String,
+	pub texture: String,
+	pub region: Vec4,
+	pub texture_width: u32,
+	pub texture_height: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtractReport {
+	/// By name
+	pub extracted: Vec<ExtractedSprite>,
+	pub out_of_bounds: Vec<OutOfBoundsSprite>,
+}
+
+impl SprSet {
+	/// Crops every sprite upright as the game shows it, mirrored and rotated, and saves it to
+	/// `dir` as `<sprite name>.<ext>`. Sprites reaching past their texture are listed in the
+	/// report.
+	pub fn extract_sprites(
+		&self,
+		dir: &Path,
+		format: image::ImageFormat,
+	) -> Result<ExtractReport, SpriteError> {
+		self.extract_sprites_from(dir, format, None)
+	}
+
+	/// [`SprSet::extract_sprites`] looking up textures the set doesn't have in `provider`.
+	pub fn extract_sprites_with_provider(
+		&self,
+		dir: &Path,
+		format: image::ImageFormat,
+		provider: &dyn TextureProvider,
+	) -> Result<ExtractReport, SpriteError> {
+		self.extract_sprites_from(dir, format, Some(provider))
+	}
+
+	fn extract_sprites_from(
+		&self,
+		dir: &Path,
+		format: image::ImageFormat,
+		provider: Option<&dyn TextureProvider>,
+	) -> Result<ExtractReport, SpriteError> {
+		std::fs::create_dir_all(dir)?;
+		let extension = format.extensions_str().first().copied().unwrap_or("png");
+		let mut sprites = self.sprites.iter().collect::<Vec<_>>();
+		sprites.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+		let mut report = ExtractReport::default();
+		for (name, sprite) in sprites {
+			let texture = self
+				.sprite_texture(sprite, provider)
+				.map_err(|err| err.in_sprite(name))?;
+			let (width, height) = (texture.width(), texture.height());
+			let (region, _, _) = unflipped_region(&sprite.pixel_region);
+			if !region_inside(&region, width, height) {
+				report.out_of_bounds.push(OutOfBoundsSprite {
+					name: name.clone(),
+					texture: sprite.texture_name.clone(),
+					region: sprite.pixel_region,
+					texture_width: width,
+					texture_height: height,
+				});
+			}
+			let image = crop_sprite_oriented(&texture, sprite, SubPixel::Floor);
+			let path = dir::file_in_dir(dir, name, extension).map_err(|err| err.in_sprite(name))?;
+			image
+				.save_with_format(&path, format)
+				.map_err(|err| SpriteError::from(err).in_sprite(name))?;
+			report.extracted.push(ExtractedSprite {
+				name: name.clone(),
+				path,
+				width: image.width(),
+				height: image.height(),
+			});
+		}
+		Ok(report)
+	}
+
+	/// Draws `image`, upright as [`SprSet::extract_sprites`] writes it, over sprite `name` on its
+	/// texture, turned and mirrored back the way the sprite is stored. The image has to be the
+	/// size of the sprite once upright.
+	pub fn replace_sprite_image(
+		&mut self,
+		name: &str,
+		image: &DynamicImage,
+	) -> Result<(), SpriteError> {
+		let sprite = self
+			.sprites
+			.get(name)
+			.ok_or_else(|| SpriteError::not_in_set("Sprite", name))?;
+		let stored = restore_orientation(image, sprite).to_rgba8();
+		let (region, _, _) = unflipped_region(&sprite.pixel_region);
+		let (x, y, width, height) = SubPixel::Floor.bounds(&region);
+		if (stored.width(), stored.height()) != (width, height) {
+			return Err(SpriteError::Validation(
+				ErrorCode::TextureSizeMismatch,
+				format!(
+					"Sprite {name} is {width}x{height} as stored, the image turned back is {}x{}",
+					stored.width(),
+					stored.height()
+				),
+			));
+		}
+		let texture = self.textures.get_mut(&sprite.texture_name).ok_or_else(|| {
+			SpriteError::Validation(
+				ErrorCode::MissingTexture,
+				format!(
+					"Sprite {name} is on texture {} which is not in the set",
+					sprite.texture_name
+				),
+			)
+		})?;
+		let mut pixels = texture.image.to_rgba8();
+		image::imageops::replace(&mut pixels, &stored, x as i64, y as i64);
+		texture.image = DynamicImage::ImageRgba8(pixels);
+		Ok(())
+	}
+
+	/// Crops every sprite, upright like [`SprSet::extract_sprites`], and saves it to `dir` as
+	/// `<sprite name>.<ext>`, one sprite per rayon task, each file written as soon as its sprite is
+	/// done. With `ordered` the results come back sorted by name, otherwise in the order they
+	/// finished.
+	pub fn extract_sprites_parallel(
+		&self,
+		dir: &Path,
+		format: image::ImageFormat,
+		ordered: bool,
+	) -> Result<Vec<ExtractedSprite>, SpriteError> {
+		self.extract_sprites_parallel_with_mode(dir, format, ordered, SubPixel::Floor)
+	}
+
+	/// [`SprSet::extract_sprites_parallel`] choosing how fractional regions are cropped.
+	pub fn extract_sprites_parallel_with_mode(
+		&self,
+		dir: &Path,
+		format: image::ImageFormat,
+		ordered: bool,
+		sub_pixel: SubPixel,
+	) -> Result<Vec<ExtractedSprite>, SpriteError> {
+		std::fs::create_dir_all(dir)?;
+		let extension = format.extensions_str().first().copied().unwrap_or("png");
+		let mut sprites = self.sprites.iter().collect::<Vec<_>>();
+		sprites.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+		let extract = |(name, sprite): &(&String, &Sprite)| {
+			let texture = self
+				.textures
+				.get(&sprite.texture_name)
+				.ok_or_else(|| SpriteError::missing_texture(name, &sprite.texture_name))?;
+			let image = crop_sprite_oriented(&texture.image, sprite, sub_pixel);
+			let path = dir::file_in_dir(dir, name, extension).map_err(|err| err.in_sprite(name))?;
+			image.save_with_format(&path, format)?;
+			Ok::<_, SpriteError>(ExtractedSprite {
+				name: name.to_string(),
+				path,
+				width: image.width(),
+				height: image.height(),
+			})
+		};
+
+		if ordered {
+			return sprites.par_iter().map(extract).collect();
+		}
+		let finished = Mutex::new(Vec::with_capacity(sprites.len()));
+		sprites.par_iter().try_for_each(|sprite| {
+			let extracted = extract(sprite)?;
+			// A task that panicked can't have left the list half pushed
+			finished
+				.lock()
+				.unwrap_or_else(PoisonError::into_inner)
+				.push(extracted);
+			Ok::<_, SpriteError>(())
+		})?;
+		Ok(finished
+			.into_inner()
+			.unwrap_or_else(PoisonError::into_inner))
+	}
+}
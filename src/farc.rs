@@ -0,0 +1,189 @@
+use crate::*;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Eq)]
+pub struct FarcEntry {
+	pub name: String,
+	pub data: Vec<u8>,
+	/// Compressed bytes as read and a hash of the data they hold, reused when writing if the data
+	/// hasn't changed so untouched entries aren't recompressed
+	stored: Option<(u64, Vec<u8>)>,
+}
+
+impl PartialEq for FarcEntry {
+	fn eq(&self, other: &Self) -> bool {
+		self.name == other.name && self.data == other.data
+	}
+}
+
+fn data_hash(data: &[u8]) -> u64 {
+	let mut hasher = std::collections::hash_map::DefaultHasher::new();
+	data.hash(&mut hasher);
+	hasher.finish()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Farc {
+	pub compressed: bool,
+	pub alignment: u32,
+	pub entries: Vec<FarcEntry>,
+}
+
+impl Default for Farc {
+	fn default() -> Self {
+		Self {
+			compressed: true,
+			alignment: 0x10,
+			entries: vec![],
+		}
+	}
+}
+
+impl Farc {
+	pub fn from_reader<R: io::Read + io::Seek>(reader: &mut R) -> Result<Self, SpriteError> {
+		let mut magic = [0u8; 4];
+		reader.read_exact(&mut magic)?;
+		let compressed = match &magic {
+			b"FArc" => false,
+			b"FArC" => true,
+			b"FARC" => {
+				return Err(SpriteError::Farc(
+					"Encrypted FARCs are not supported".into(),
+				))
+			}
+			_ => return Err(SpriteError::Farc("Not a FARC file".into())),
+		};
+		let header_size: u32 = reader.read_be()?;
+		let header_end = 8 + header_size as u64;
+		let alignment: u32 = reader.read_be()?;
+
+		let mut headers = vec![];
+		while reader.stream_position()? < header_end {
+			let name: NullString = reader.read_be()?;
+			let offset: u32 = reader.read_be()?;
+			let stored_size: u32 = reader.read_be()?;
+			let size = if compressed {
+				reader.read_be::<u32>()?
+			} else {
+				stored_size
+			};
+			headers.push((name.to_string(), offset, stored_size, size));
+		}
+
+		let mut entries = Vec::with_capacity(headers.len());
+		for (name, offset, stored_size, size) in headers {
+			reader.seek(SeekFrom::Start(offset as u64))?;
+			let mut stored = vec![0u8; stored_size as usize];
+			reader.read_exact(&mut stored)?;
+			let (data, stored) = if compressed && stored_size != size {
+				let mut data = Vec::with_capacity(size as usize);
+				GzDecoder::new(stored.as_slice()).read_to_end(&mut data)?;
+				let hash = data_hash(&data);
+				(data, Some((hash, stored)))
+			} else {
+				(stored, None)
+			};
+			entries.push(FarcEntry { name, data, stored });
+		}
+
+		Ok(Self {
+			compressed,
+			alignment,
+			entries,
+		})
+	}
+
+	pub fn read(path: impl AsRef<Path>) -> Result<Self, SpriteError> {
+		let bytes = std::fs::read(path)?;
+		Self::from_reader(&mut Cursor::new(bytes))
+	}
+
+	pub fn to_writer<W: io::Write + io::Seek>(&self, writer: &mut W) -> Result<(), SpriteError> {
+		let alignment = self.alignment.max(1) as u64;
+		let stored = self
+			.entries
+			.iter()
+			.map(|entry| {
+				if !self.compressed {
+					return Ok(entry.data.clone());
+				}
+				if let Some((hash, stored)) = &entry.stored {
+					if *hash == data_hash(&entry.data) {
+						return Ok(stored.clone());
+					}
+				}
+				let mut encoder = GzEncoder::new(vec![], Compression::default());
+				encoder.write_all(&entry.data)?;
+				encoder.finish()
+			})
+			.collect::<Result<Vec<_>, io::Error>>()?;
+
+		let entry_header_size = if self.compressed { 12 } else { 8 };
+		let header_size = 4 + self
+			.entries
+			.iter()
+			.map(|entry| entry.name.len() + 1 + entry_header_size)
+			.sum::<usize>() as u64;
+
+		let mut offsets = vec![];
+		let mut offset = 8 + header_size;
+		for data in stored.iter() {
+			offset = offset.next_multiple_of(alignment);
+			offsets.push(offset);
+			offset += data.len() as u64;
+		}
+
+		writer.write_all(if self.compressed { b"FArC" } else { b"FArc" })?;
+		writer.write_be(&(header_size as u32))?;
+		writer.write_be(&self.alignment)?;
+		for ((entry, data), offset) in self.entries.iter().zip(stored.iter()).zip(offsets.iter()) {
+			writer.write_all(std::ffi::CString::new(entry.name.clone())?.as_bytes_with_nul())?;
+			writer.write_be(&(*offset as u32))?;
+			writer.write_be(&(data.len() as u32))?;
+			if self.compressed {
+				writer.write_be(&(entry.data.len() as u32))?;
+			}
+		}
+		for (data, offset) in stored.iter().zip(offsets.iter()) {
+			let pos = writer.stream_position()?;
+			writer.write_all(&vec![0u8; (offset - pos) as usize])?;
+			writer.write_all(data)?;
+		}
+
+		Ok(())
+	}
+
+	pub fn write(&self, path: impl AsRef<Path>) -> Result<(), SpriteError> {
+		let mut data = vec![];
+		self.to_writer(&mut Cursor::new(&mut data))?;
+		std::fs::write(path, data)?;
+		Ok(())
+	}
+
+	pub fn get(&self, name: &str) -> Option<&FarcEntry> {
+		self.entries.iter().find(|entry| entry.name == name)
+	}
+
+	pub fn get_mut(&mut self, name: &str) -> Option<&mut FarcEntry> {
+		self.entries.iter_mut().find(|entry| entry.name == name)
+	}
+
+	pub fn insert(&mut self, name: &str, data: Vec<u8>) {
+		match self.entries.iter_mut().find(|entry| entry.name == name) {
+			Some(entry) => entry.data = data,
+			None => self.entries.push(FarcEntry {
+				name: name.to_string(),
+				data,
+				stored: None,
+			}),
+		}
+	}
+
+	pub fn remove(&mut self, name: &str) -> Option<FarcEntry> {
+		let index = self.entries.iter().position(|entry| entry.name == name)?;
+		Some(self.entries.remove(index))
+	}
+}
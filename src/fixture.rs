@@ -0,0 +1,126 @@
+//! Builders for synthetic sets, so tests can run against realistic data without game files.
+//! Only available with the `test-support` feature.
+use crate::*;
+
+/// A texture whose pixels depend only on its size and `seed`: a gradient with a grid every 16
+/// pixels and an alpha ramp, so crops and flips show up in comparisons.
+pub fn synthetic_texture(width: u32, height: u32, seed: u32) -> DynamicImage {
+	let image = image::RgbaImage::from_fn(width, height, |x, y| {
+		let mix = (x.wrapping_mul(31) ^ y.wrapping_mul(17)).wrapping_add(seed.wrapping_mul(97));
+		if x % 16 == 0 || y % 16 == 0 {
+			return image::Rgba([255, 255, 255, 255]);
+		}
+		image::Rgba([
+			(x * 255 / width.max(1)) as u8,
+			(y * 255 / height.max(1)) as u8,
+			(mix % 256) as u8,
+			(128 + (x + y) * 127 / (width + height).max(1)) as u8,
+		])
+	});
+	DynamicImage::ImageRgba8(image)
+}
+
+#[derive(Debug, Clone)]
+pub struct SprSetFixture {
+	name: String,
+	textures: Vec<(String, u32, u32)>,
+	sprites: Vec<(String, String, Vec4)>,
+	screen_mode: ScreenMode,
+}
+
+impl SprSetFixture {
+	pub fn new(name: &str) -> Self {
+		Self {
+			name: name.to_string(),
+			textures: vec![],
+			sprites: vec![],
+			screen_mode: ScreenMode::HDTV720,
+		}
+	}
+
+	/// One 128x64 texture holding three sprites, one of them touching the texture edges.
+	pub fn simple() -> Self {
+		Self::new("SPR_FIXTURE")
+			.texture("MERGE_NOCOMP_0", 128, 64)
+			.sprite("ICON", "MERGE_NOCOMP_0", 0, 0, 32, 32)
+			.sprite("BANNER", "MERGE_NOCOMP_0", 32, 0, 96, 24)
+			.sprite("CORNER", "MERGE_NOCOMP_0", 112, 48, 16, 16)
+	}
+
+	/// Two textures with sprites on both, for anything that cares about texture indices.
+	pub fn multi_texture() -> Self {
+		Self::simple()
+			.texture("MERGE_NOCOMP_1", 64, 64)
+			.sprite("BUTTON", "MERGE_NOCOMP_1", 8, 8, 48, 16)
+			.sprite("CURSOR", "MERGE_NOCOMP_1", 0, 32, 16, 16)
+	}
+
+	/// Adds a synthetic texture, seeded by its position so every texture differs.
+	pub fn texture(mut self, name: &str, width: u32, height: u32) -> Self {
+		self.textures.push((name.to_string(), width, height));
+		self
+	}
+
+	/// Adds a sprite covering `width`x`height` pixels at `x`, `y` of `texture`.
+	pub fn sprite(
+		mut self,
+		name: &str,
+		texture: &str,
+		x: u32,
+		y: u32,
+		width: u32,
+		height: u32,
+	) -> Self {
+		self.sprites.push((
+			name.to_string(),
+			texture.to_string(),
+			Vec4 {
+				x: x as f32,
+				y: y as f32,
+				z: width as f32,
+				w: height as f32,
+			},
+		));
+		self
+	}
+
+	pub fn screen_mode(mut self, screen_mode: ScreenMode) -> Self {
+		self.screen_mode = screen_mode;
+		self
+	}
+
+	pub fn build(&self) -> SprSet {
+		let mut set = SprSet::new(&self.name);
+		for (seed, (name, width, height)) in self.textures.iter().enumerate() {
+			set.textures.insert(
+				name.clone(),
+				Texture::new(synthetic_texture(*width, *height, seed as u32)),
+			);
+		}
+		for (name, texture, pixel_region) in &self.sprites {
+			let (width, height) = self
+				.textures
+				.iter()
+				.find(|(name, _, _)| name == texture)
+				.map_or((1, 1), |(_, width, height)| (*width, *height));
+			set.sprites.insert(
+				name.clone(),
+				Sprite {
+					screen_mode: self.screen_mode,
+					texel_region: TexelRounding::Exact.texel_region(pixel_region, width, height),
+					pixel_region: *pixel_region,
+					texture_name: texture.clone(),
+					rotate: 0,
+				},
+			);
+		}
+		set
+	}
+
+	/// The built set written with `options`.
+	pub fn to_bytes(&self, options: &WriteOptions) -> Result<Vec<u8>, SpriteError> {
+		let mut cursor = Cursor::new(vec![]);
+		self.build().to_writer_with_options(&mut cursor, options)?;
+		Ok(cursor.into_inner())
+	}
+}
@@ -0,0 +1,2049 @@
+//! The on-disk spr layout: binrw readers for every structure, the plain data types stored in
+//! the file, and reading and writing whole sets.
+use crate::*;
+
+/// Everything of a set but its texture set: the header, names and sprites. Sets opened lazily
+/// stop here and read textures one at a time through [`SetHeader::texture_offsets`].
+#[derive(Debug, BinRead)]
+pub(crate) struct SetHeader {
+	pub(crate) flags: u32,
+	pub(crate) tex_sets_ptr: u32,
+	pub(crate) tex_sets_count: u32,
+	pub(crate) sprite_count: u32,
+	#[br(count = sprite_count)]
+	pub(crate) sprites: FilePtr32<Vec<SpriteReader>>,
+	#[br(count = tex_sets_count)]
+	pub(crate) tex_names: FilePtr32<Vec<FilePtr32<NullString>>>,
+	#[br(count = sprite_count)]
+	pub(crate) sprite_names: FilePtr32<Vec<FilePtr32<NullString>>>,
+	#[br(count = sprite_count)]
+	pub(crate) sprite_extras: FilePtr32<Vec<(u32, ScreenMode)>>,
+}
+
+impl SetHeader {
+	/// Where every texture starts in the file, see [`read_texture_offsets`].
+	pub(crate) fn texture_offsets<R: io::Read + io::Seek>(
+		&self,
+		reader: &mut R,
+		endian: Endian,
+	) -> Result<Vec<u64>, SpriteError> {
+		read_texture_offsets(reader, self.tex_sets_ptr, endian)
+	}
+}
+
+/// Reads the `TXP\x03` table at `tex_sets_ptr` and gives where every texture starts in the file,
+/// nothing for texture-less sets with a null pointer. The texture data itself is not touched.
+pub(crate) fn read_texture_offsets<R: io::Read + io::Seek>(
+	reader: &mut R,
+	tex_sets_ptr: u32,
+	endian: Endian,
+) -> Result<Vec<u64>, SpriteError> {
+	if tex_sets_ptr == 0 {
+		return Ok(vec![]);
+	}
+	let pos = tex_sets_ptr as u64;
+	reader.seek(SeekFrom::Start(pos))?;
+	let magic: [u8; 4] = reader.read_type(endian)?;
+	if &magic != b"TXP\x03" {
+		return Err(SpriteError::bad_magic("Texture set", pos, "TXP\\x03"));
+	}
+	let count: u32 = reader.read_type(endian)?;
+	let _padding: u32 = reader.read_type(endian)?;
+	(0..count)
+		.map(|_| -> Result<u64, SpriteError> {
+			let offset: u32 = reader.read_type(endian)?;
+			Ok(pos + offset as u64)
+		})
+		.collect()
+}
+
+/// A whole set, its textures read and decoded along with everything in [`SetHeader`].
+#[derive(Debug, BinRead)]
+pub(crate) struct SprSetReader {
+	pub(crate) header: SetHeader,
+	// Texture-less stub sets may leave the pointer null
+	#[br(if(header.tex_sets_ptr != 0))]
+	#[br(seek_before = SeekFrom::Start(header.tex_sets_ptr as u64), restore_position)]
+	pub(crate) tex_sets: Option<TexSetReader>,
+}
+
+impl Deref for SprSetReader {
+	type Target = SetHeader;
+
+	fn deref(&self) -> &SetHeader {
+		&self.header
+	}
+}
+
+impl SprSetReader {
+	/// Where whatever structure starts at `pos` ends: the next thing any pointer points at, or the
+	/// end of the file.
+	pub(crate) fn structure_end_after<R: io::Seek>(
+		&self,
+		pos: u64,
+		reader: &mut R,
+	) -> Result<u64, SpriteError> {
+		let tex_set = self.tex_sets_ptr as u64;
+		// Name strings can sit anywhere, including between textures
+		let names = self
+			.tex_names
+			.iter()
+			.chain(self.sprite_names.iter())
+			.map(|name| name.ptr as u64);
+		let starts = self
+			.textures()
+			.iter()
+			.map(|texture| tex_set + texture.ptr as u64)
+			.chain(names)
+			.chain([
+				self.sprites.ptr as u64,
+				self.tex_names.ptr as u64,
+				self.sprite_names.ptr as u64,
+				self.sprite_extras.ptr as u64,
+			]);
+		match starts.filter(|start| *start > pos).min() {
+			Some(end) => Ok(end),
+			None => Ok(reader.seek(SeekFrom::End(0))?),
+		}
+	}
+
+	/// Reads a set stored in `endian`, swapping 16 bit texel data back to the order the decoders
+	/// expect.
+	pub(crate) fn read<R: io::Read + io::Seek>(
+		reader: &mut R,
+		endian: Endian,
+	) -> Result<Self, SpriteError> {
+		let mut set: Self = reader.read_type(endian)?;
+		if endian != Endian::Big {
+			return Ok(set);
+		}
+		let textures = set
+			.tex_sets
+			.iter_mut()
+			.flat_map(|tex_sets| tex_sets.textures.iter_mut());
+		for texture in textures {
+			texture.swap_packed16();
+		}
+		Ok(set)
+	}
+
+	pub(crate) fn textures(&self) -> &[FilePtr32<TexReader>] {
+		self.tex_sets
+			.as_ref()
+			.map_or(&[], |tex_sets| tex_sets.textures.as_slice())
+	}
+}
+
+#[derive(Debug, BinRead)]
+#[br(magic = b"TXP\x03")]
+pub(crate) struct TexSetReader {
+	#[br(parse_with = get_position)]
+	pub(crate) position: u32,
+	pub(crate) texture_count: u32,
+	pub(crate) padding: u32,
+	#[br(offset = (position - 4).into(), count = texture_count)]
+	pub(crate) textures: Vec<FilePtr32<TexReader>>,
+}
+
+impl TexReader {
+	/// Swaps 16 bit texel data read big endian back to the order the decoders expect.
+	pub(crate) fn swap_packed16(&mut self) {
+		let layers = match self {
+			TexReader::Tex2d(texture) => &mut texture.mip_map_array,
+			TexReader::TexCubeMap(cubemap) => &mut cubemap.mip_map_array,
+			TexReader::Unknown(_) => return,
+		};
+		for mip in layers
+			.iter_mut()
+			.flat_map(|layer| layer.mip_maps.iter_mut())
+		{
+			if is_packed16(mip.format) {
+				for pair in mip.data.chunks_exact_mut(2) {
+					pair.swap(0, 1);
+				}
+			}
+		}
+	}
+}
+
+#[derive(Debug, BinRead)]
+pub(crate) enum TexReader {
+	#[br(magic = b"TXP\x04")]
+	Tex2d(Tex2dReader),
+	#[br(magic = b"TXP\x05")]
+	TexCubeMap(TexCubeMapReader),
+	/// Any other TXP sub-version, holds the version byte
+	#[br(magic = b"TXP")]
+	Unknown(u8),
+}
+
+#[derive(Debug, BinRead)]
+pub(crate) struct Tex2dReader {
+	#[br(parse_with = get_position)]
+	pub(crate) position: u32,
+	pub(crate) mip_maps: u32,
+	pub(crate) mip_levels: u8,
+	pub(crate) array_size: u8,
+	pub(crate) depth: u8,
+	pub(crate) dimensions: u8,
+	#[br(args { inner: (mip_levels, position - 4) })]
+	#[br(count = array_size)]
+	pub(crate) mip_map_array: Vec<TexMipMapArrayReader>,
+}
+
+#[derive(Debug, BinRead)]
+pub(crate) struct TexCubeMapReader {
+	#[br(parse_with = get_position)]
+	pub(crate) position: u32,
+	pub(crate) mip_maps: u32,
+	pub(crate) mip_levels: u8,
+	pub(crate) array_size: u8,
+	pub(crate) depth: u8,
+	pub(crate) dimensions: u8,
+	#[br(calc = mip_levels / array_size)]
+	pub(crate) mip_levels_adjusted: u8,
+	#[br(args { inner: (mip_levels_adjusted, position - 4) })]
+	#[br(count = array_size)]
+	pub(crate) mip_map_array: Vec<TexMipMapArrayReader>,
+}
+
+#[derive(Debug, BinRead)]
+#[br(import(mip_levels: u8, position: u32))]
+pub(crate) struct TexMipMapArrayReader {
+	#[br(count = mip_levels)]
+	#[br(offset = position.into())]
+	pub(crate) mip_maps: Vec<FilePtr32<TexMipMapReader>>,
+}
+
+#[derive(Debug, BinRead)]
+#[br(magic = b"TXP\x02")]
+pub(crate) struct TexMipMapReader {
+	pub(crate) width: i32,
+	pub(crate) height: i32,
+	pub(crate) format: TextureFormat,
+	pub(crate) index: u8,
+	pub(crate) array_index: u8,
+	pub(crate) padding: u16,
+	pub(crate) data_size: u32,
+	#[br(count = data_size)]
+	pub(crate) data: Vec<u8>,
+}
+
+/// Defines a fieldless enum along with an `ALL` constant listing every variant in the order they
+/// are declared, so a new variant can't be left out of it.
+macro_rules! enum_with_all {
+	(
+		$(#[$meta:meta])*
+		$vis:vis enum $name:ident {
+			$($variant:ident = $value:expr,)*
+		}
+	) => {
+		$(#[$meta])*
+		$vis enum $name {
+			$($variant = $value,)*
+		}
+
+		impl $name {
+			pub const ALL: [$name; [$(stringify!($variant)),*].len()] = [$(Self::$variant),*];
+		}
+	};
+}
+
+enum_with_all! {
+	#[derive(Debug, BinRead, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+	#[br(repr = u32)]
+	pub enum TextureFormat {
+		Unknown = -1,
+		A8 = 0,
+		RGB8 = 1,
+		RGBA8 = 2,
+		RGB5 = 3,
+		RGB5A1 = 4,
+		RGBA4 = 5,
+		DXT1 = 6,
+		DXT1a = 7,
+		DXT3 = 8,
+		DXT5 = 9,
+		ATI1 = 10,
+		ATI2 = 11,
+		L8 = 12,
+		L8A8 = 13,
+		BC7 = 15,
+		BC6H = 127,
+	}
+}
+
+impl TextureFormat {
+	pub(crate) fn to_dxgi_format(&self) -> DxgiFormat {
+		match self {
+			Self::A8 => DxgiFormat::R8_UNorm,
+			Self::RGBA8 => DxgiFormat::R8G8B8A8_UNorm,
+			Self::DXT1 => DxgiFormat::BC1_UNorm,
+			Self::DXT1a => DxgiFormat::BC1_UNorm,
+			Self::DXT3 => DxgiFormat::BC2_UNorm_sRGB,
+			Self::DXT5 => DxgiFormat::BC3_UNorm,
+			Self::ATI1 => DxgiFormat::BC4_UNorm,
+			Self::ATI2 => DxgiFormat::BC5_UNorm,
+			Self::L8 => DxgiFormat::A8_UNorm,
+			Self::L8A8 => DxgiFormat::A8P8,
+			Self::BC7 => DxgiFormat::BC7_UNorm,
+			Self::BC6H => DxgiFormat::BC6H_UF16,
+			_ => DxgiFormat::Unknown,
+		}
+	}
+
+	pub fn is_block_compressed(&self) -> bool {
+		matches!(
+			self,
+			Self::DXT1
+				| Self::DXT1a
+				| Self::DXT3 | Self::DXT5
+				| Self::ATI1 | Self::ATI2
+				| Self::BC7 | Self::BC6H
+		)
+	}
+
+	pub(crate) fn from_dxgi_format(format: &DxgiFormat) -> Self {
+		match format {
+			DxgiFormat::R8_UNorm => Self::A8,
+			DxgiFormat::R8G8B8A8_UNorm => Self::RGBA8,
+			DxgiFormat::BC1_UNorm => Self::DXT1,
+			DxgiFormat::BC2_UNorm_sRGB => Self::DXT3,
+			DxgiFormat::BC3_UNorm => Self::DXT5,
+			DxgiFormat::BC4_UNorm => Self::ATI1,
+			DxgiFormat::BC5_UNorm => Self::ATI2,
+			DxgiFormat::A8_UNorm => Self::L8,
+			DxgiFormat::A8P8 => Self::L8A8,
+			DxgiFormat::BC7_UNorm => Self::BC7,
+			DxgiFormat::BC6H_UF16 => Self::BC6H,
+			_ => Self::Unknown,
+		}
+	}
+}
+
+#[derive(Debug, BinRead, BinWrite, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Vec4 {
+	pub x: f32,
+	pub y: f32,
+	pub z: f32,
+	pub w: f32,
+}
+
+#[derive(Debug, BinRead)]
+pub(crate) struct SpriteReader {
+	pub(crate) texture_index: i32,
+	pub(crate) rotate: i32,
+	pub(crate) texel_region: Vec4,
+	pub(crate) pixel_region: Vec4,
+}
+
+enum_with_all! {
+	#[pyo3::prelude::pyclass]
+	#[derive(Debug, BinRead, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+	#[br(repr = u32)]
+	pub enum ScreenMode {
+		QVGA = 0,
+		VGA = 1,
+		SVGA = 2,
+		XGA = 3,
+		SXGA = 4,
+		SXGAPLUS = 5,
+		UXGA = 6,
+		WVGA = 7,
+		WSVGA = 8,
+		WXGA = 9,
+		WXGA_ = 10,
+		WUXGA = 11,
+		WQXGA = 12,
+		HDTV720 = 13,
+		HDTV1080 = 14,
+		WQHD = 15,
+		HVGA = 16,
+		QHD = 17,
+		Custom = 18,
+	}
+}
+
+impl ScreenMode {
+	pub fn from_u32(value: u32) -> Option<Self> {
+		Self::ALL.get(value as usize).copied()
+	}
+
+	/// Width and height of the screen the mode stands for, `None` for `Custom`.
+	pub fn resolution(&self) -> Option<(u32, u32)> {
+		match self {
+			Self::QVGA => Some((320, 240)),
+			Self::VGA => Some((640, 480)),
+			Self::SVGA => Some((800, 600)),
+			Self::XGA => Some((1024, 768)),
+			Self::SXGA => Some((1280, 1024)),
+			Self::SXGAPLUS => Some((1400, 1050)),
+			Self::UXGA => Some((1600, 1200)),
+			Self::WVGA => Some((800, 480)),
+			Self::WSVGA => Some((1024, 600)),
+			Self::WXGA => Some((1280, 768)),
+			Self::WXGA_ => Some((1360, 768)),
+			Self::WUXGA => Some((1920, 1200)),
+			Self::WQXGA => Some((2560, 1600)),
+			Self::HDTV720 => Some((1280, 720)),
+			Self::HDTV1080 => Some((1920, 1080)),
+			Self::WQHD => Some((2560, 1440)),
+			Self::HVGA => Some((480, 272)),
+			Self::QHD => Some((960, 544)),
+			Self::Custom => None,
+		}
+	}
+}
+
+impl std::str::FromStr for ScreenMode {
+	type Err = SpriteError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		if let Ok(value) = s.parse::<u32>() {
+			return Self::from_u32(value)
+				.ok_or_else(|| SpriteError::Import(format!("Unknown screen mode {value}")));
+		}
+		Self::ALL
+			.iter()
+			.find(|mode| format!("{mode:?}").eq_ignore_ascii_case(s))
+			.copied()
+			.ok_or_else(|| SpriteError::Import(format!("Unknown screen mode {s}")))
+	}
+}
+
+fn get_position<R: io::Read + io::Seek>(reader: &mut R, _: &ReadOptions, _: ()) -> BinResult<u32> {
+	Ok(reader.stream_position()? as u32)
+}
+
+/// Format and encoder for one texture, whatever is unset falls back to the set-wide choice.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextureEncodeSettings {
+	pub format: Option<TextureFormat>,
+	pub encoder: Option<Encoder>,
+	pub importance: Option<Importance>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteOptions {
+	/// Byte order of every field, big endian for PS3 and Xbox 360 builds. Raw textures kept from
+	/// unknown TXP versions are written as they were read.
+	pub endianness: Endian,
+	pub texture_format: TextureFormat,
+	/// Per texture overrides for `texture_format`
+	pub texture_formats: HashMap<String, TextureFormat>,
+	/// Per texture format and encoder, taking precedence over `texture_formats` and `encoder`
+	pub texture_settings: HashMap<String, TextureEncodeSettings>,
+	pub alignment: u32,
+	pub write_names: bool,
+	pub allowed_formats: Option<Vec<TextureFormat>>,
+	pub require_block_aligned: bool,
+	pub record_layout: bool,
+	pub sprite_indexing: Option<SpriteIndexing>,
+	/// Channel remaps applied before encoding, keyed by the target format
+	pub channel_remaps: HashMap<TextureFormat, ChannelRemap>,
+	/// Used when encoding to formats with fewer than 8 bits per channel, see
+	/// [`TextureFormat::channel_bits`]
+	pub dithering: Dithering,
+	/// Per texture overrides for `dithering`
+	pub texture_dithering: HashMap<String, Dithering>,
+	/// Reduces textures to this many colours before encoding, which helps icons compress cleanly
+	pub quantize_colors: Option<usize>,
+	/// Per texture overrides for `quantize_colors`
+	pub texture_quantize_colors: HashMap<String, usize>,
+	/// Decodes every texture again after encoding and reports PSNR/SSIM against the source
+	pub measure_quality: bool,
+	/// Write placeholder textures out as is instead of refusing
+	pub allow_placeholders: bool,
+	pub flip: FlipConvention,
+	pub encoder: Encoder,
+	/// Which blocks get encoded again at the best setting
+	pub importance: Importance,
+	pub mip_mode: MipMode,
+	pub mip_filter: MipFilter,
+	/// Longest name in bytes the target build handles, longer ones break at runtime
+	pub max_name_length: Option<usize>,
+	/// Cut names down to `max_name_length` instead of refusing them, as long as they stay unique
+	pub truncate_names: bool,
+	/// Write names normalized on load as they were in the file, see
+	/// [`LoadOptions::normalize_names`]
+	pub restore_names: bool,
+	/// Write textures and sprites where they were in the file they were read from, new ones after
+	/// them, instead of by name. Keeps the indices spr_db refers to. An order set with
+	/// [`SprSet::set_texture_order`] or [`SprSet::set_sprite_order`] still wins.
+	pub original_order: bool,
+}
+
+impl Default for WriteOptions {
+	fn default() -> Self {
+		Self {
+			endianness: Endian::Little,
+			texture_format: TextureFormat::RGBA8,
+			texture_formats: HashMap::new(),
+			texture_settings: HashMap::new(),
+			alignment: 1,
+			write_names: true,
+			allowed_formats: None,
+			require_block_aligned: false,
+			record_layout: false,
+			sprite_indexing: None,
+			channel_remaps: HashMap::new(),
+			dithering: Dithering::None,
+			texture_dithering: HashMap::new(),
+			quantize_colors: None,
+			texture_quantize_colors: HashMap::new(),
+			measure_quality: false,
+			allow_placeholders: false,
+			flip: FlipConvention::Auto,
+			encoder: Encoder::Balanced,
+			importance: Importance::Uniform,
+			mip_mode: MipMode::TopOnly,
+			mip_filter: MipFilter::Box,
+			max_name_length: None,
+			truncate_names: false,
+			restore_names: false,
+			original_order: false,
+		}
+	}
+}
+
+impl WriteOptions {
+	pub fn format_for(&self, name: &str) -> TextureFormat {
+		self.texture_settings
+			.get(name)
+			.and_then(|settings| settings.format)
+			.or_else(|| self.texture_formats.get(name).copied())
+			.unwrap_or(self.texture_format)
+	}
+
+	pub fn encoder_for(&self, name: &str) -> Encoder {
+		self.texture_settings
+			.get(name)
+			.and_then(|settings| settings.encoder)
+			.unwrap_or(self.encoder)
+	}
+
+	pub fn importance_for(&self, name: &str) -> Importance {
+		self.texture_settings
+			.get(name)
+			.and_then(|settings| settings.importance)
+			.unwrap_or(self.importance)
+	}
+
+	/// Writes every texture of `set` back in the format it was read in, where the encoder can
+	/// produce it and no override was set already. Others keep `texture_format`.
+	pub fn preserve_formats(&mut self, set: &SprSet) {
+		for (name, texture) in &set.textures {
+			if texture.original_format.can_encode() && !self.texture_formats.contains_key(name) {
+				self.texture_formats
+					.insert(name.clone(), texture.original_format);
+			}
+		}
+	}
+
+	/// The image that actually gets encoded for `image`, a layer of texture `name`.
+	fn prepare_texture<'a>(
+		&self,
+		name: &str,
+		image: &'a DynamicImage,
+	) -> std::borrow::Cow<'a, DynamicImage> {
+		let format = self.format_for(name);
+		let remap = self
+			.channel_remaps
+			.get(&format)
+			.filter(|remap| !remap.is_identity());
+		let dithering = self
+			.texture_dithering
+			.get(name)
+			.copied()
+			.unwrap_or(self.dithering);
+		let bits = format
+			.channel_bits()
+			.filter(|_| dithering != Dithering::None);
+		let colors = self
+			.texture_quantize_colors
+			.get(name)
+			.copied()
+			.or(self.quantize_colors);
+		if remap.is_none() && bits.is_none() && colors.is_none() {
+			return std::borrow::Cow::Borrowed(image);
+		}
+
+		let mut image = image.to_rgba8();
+		if let Some(remap) = remap {
+			remap.apply(&mut image);
+		}
+		if let Some(colors) = colors {
+			quantize::quantize(&mut image, colors);
+		}
+		if let Some(bits) = bits {
+			dither::dither(&mut image, bits, dithering);
+		}
+		std::borrow::Cow::Owned(DynamicImage::ImageRgba8(image))
+	}
+
+	/// `name` as it is written, cut to `max_name_length` at a character boundary when truncating.
+	pub fn written_name<'a>(&self, name: &'a str) -> &'a str {
+		let Some(max) = self.max_name_length.filter(|_| self.truncate_names) else {
+			return name;
+		};
+		let mut end = max.min(name.len());
+		while !name.is_char_boundary(end) {
+			end -= 1;
+		}
+		&name[..end]
+	}
+
+	fn stored_texture_name<'a>(&self, set: &'a SprSet, name: &'a str) -> &'a str {
+		if self.restore_names {
+			set.original_texture_name(name)
+		} else {
+			name
+		}
+	}
+
+	fn stored_sprite_name<'a>(&self, set: &'a SprSet, name: &'a str) -> &'a str {
+		if self.restore_names {
+			set.original_sprite_name(name)
+		} else {
+			name
+		}
+	}
+
+	fn check_name_lengths<'a>(
+		&self,
+		names: impl Iterator<Item = &'a str>,
+		what: &str,
+	) -> Result<(), SpriteError> {
+		let Some(max) = self.max_name_length else {
+			return Ok(());
+		};
+		let mut names = names.collect::<Vec<_>>();
+		names.sort();
+		if !self.truncate_names {
+			return match names.iter().find(|name| name.len() > max) {
+				Some(name) => Err(SpriteError::Validation(
+					ErrorCode::NameTooLong,
+					format!(
+						"{what} name {name} is {} bytes, the limit is {max}",
+						name.len()
+					),
+				)),
+				None => Ok(()),
+			};
+		}
+		// Cut names may land on each other or on names that were short enough already
+		let mut written = HashMap::with_capacity(names.len());
+		for name in names {
+			if let Some(other) = written.insert(self.written_name(name), name) {
+				return Err(SpriteError::Validation(
+					ErrorCode::NameInUse,
+					format!(
+						"{what} names {other} and {name} are both {} when cut to {max} bytes",
+						self.written_name(name)
+					),
+				));
+			}
+		}
+		Ok(())
+	}
+
+	pub fn validate(&self, set: &SprSet) -> Result<(), SpriteError> {
+		self.check_name_lengths(
+			set.textures
+				.keys()
+				.map(|name| self.stored_texture_name(set, name)),
+			"Texture",
+		)?;
+		self.check_name_lengths(
+			set.sprites
+				.keys()
+				.map(|name| self.stored_sprite_name(set, name)),
+			"Sprite",
+		)?;
+		if !set.textures.is_empty() {
+			let mut external = set
+				.sprites
+				.iter()
+				.filter(|(_, sprite)| {
+					!set.textures.contains_key(&sprite.texture_name)
+						&& set.external_textures.contains_key(&sprite.texture_name)
+				})
+				.map(|(name, _)| name.as_str())
+				.collect::<Vec<_>>();
+			external.sort();
+			if let Some(name) = external.first() {
+				return Err(SpriteError::Validation(
+					ErrorCode::MissingTexture,
+					format!(
+						"Sprite {name} is on a texture from another set, only sets without textures can reference one"
+					),
+				));
+			}
+		}
+		if !self.allow_placeholders {
+			let mut placeholders = set
+				.textures
+				.iter()
+				.filter(|(_, texture)| texture.placeholder && texture.raw.is_none())
+				.map(|(name, _)| name.as_str())
+				.collect::<Vec<_>>();
+			placeholders.sort();
+			if !placeholders.is_empty() {
+				return Err(SpriteError::Validation(
+					ErrorCode::PlaceholderTexture,
+					format!(
+						"Textures {} are placeholders for data that failed to decode",
+						placeholders.join(", ")
+					),
+				));
+			}
+		}
+		let mut textures = set.textures.iter().collect::<Vec<_>>();
+		textures.sort_by(|(a, _), (b, _)| a.cmp(b));
+		for (name, texture) in &textures {
+			// Raw textures are written as they were read, their image is only a stand in
+			if texture.raw.is_some() {
+				continue;
+			}
+			let size = (texture.image.width(), texture.image.height());
+			if texture.cubemap && (texture.layers.len() != 5 || size.0 != size.1) {
+				return Err(SpriteError::Validation(
+					ErrorCode::TextureSizeMismatch,
+					format!(
+						"Cube map {name} has {} {}x{} faces, it needs 6 square ones",
+						texture.layers.len() + 1,
+						size.0,
+						size.1
+					),
+				));
+			}
+			if let Some(layer) = texture
+				.layers
+				.iter()
+				.find(|layer| (layer.width(), layer.height()) != size)
+			{
+				return Err(SpriteError::Validation(
+					ErrorCode::TextureSizeMismatch,
+					format!(
+						"Texture {name} is {}x{} but has a {}x{} layer, all layers must match",
+						size.0,
+						size.1,
+						layer.width(),
+						layer.height()
+					),
+				));
+			}
+		}
+		if let Some(allowed_formats) = &self.allowed_formats {
+			let formats = std::iter::once(self.texture_format)
+				.chain(textures.iter().map(|(name, _)| self.format_for(name)));
+			for format in formats {
+				if !allowed_formats.contains(&format) {
+					return Err(SpriteError::Validation(
+						ErrorCode::UnsupportedTextureFormat,
+						format!("Texture format {format:?} is not supported by this target"),
+					));
+				}
+			}
+		}
+		if self.require_block_aligned {
+			for (name, texture) in textures {
+				if !self.format_for(name).is_block_compressed() {
+					continue;
+				}
+				if texture.image.width() % 4 != 0 || texture.image.height() % 4 != 0 {
+					return Err(SpriteError::Validation(
+						ErrorCode::TextureSizeMismatch,
+						format!(
+						"Texture {name} is {}x{}, which is not a multiple of the 4x4 block size",
+						texture.image.width(),
+						texture.image.height()
+					),
+					));
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Write defaults matching what each game build expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteProfile {
+	Aft,
+	Ft,
+	MegaMixPlus,
+	MegaMixSwitch,
+	/// The PS3 build, see [`WriteProfile::variant`]
+	F2nd,
+}
+
+impl WriteProfile {
+	pub fn options(&self) -> WriteOptions {
+		match self {
+			Self::Aft | Self::Ft => WriteOptions {
+				texture_format: TextureFormat::DXT5,
+				alignment: 16,
+				..Default::default()
+			},
+			// Names are resolved through spr_db
+			Self::MegaMixPlus => WriteOptions {
+				texture_format: TextureFormat::DXT5,
+				alignment: 16,
+				write_names: false,
+				..Default::default()
+			},
+			// The Switch port tooling only swizzles block compressed textures
+			Self::MegaMixSwitch => WriteOptions {
+				texture_format: TextureFormat::DXT5,
+				alignment: 16,
+				write_names: false,
+				allowed_formats: Some(vec![
+					TextureFormat::DXT1,
+					TextureFormat::DXT5,
+					TextureFormat::ATI1,
+					TextureFormat::ATI2,
+					TextureFormat::BC7,
+				]),
+				require_block_aligned: true,
+				..Default::default()
+			},
+			// Big endian, and the RSX only samples DXT1/3/5 and uncompressed textures
+			Self::F2nd => WriteOptions {
+				endianness: Endian::Big,
+				texture_format: TextureFormat::DXT5,
+				alignment: 16,
+				allowed_formats: Some(vec![
+					TextureFormat::RGBA8,
+					TextureFormat::DXT1,
+					TextureFormat::DXT1a,
+					TextureFormat::DXT3,
+					TextureFormat::DXT5,
+				]),
+				require_block_aligned: true,
+				..Default::default()
+			},
+		}
+	}
+
+	/// Layout the build reads, for [`SprSet::convert`].
+	pub fn variant(&self) -> FormatVariant {
+		match self {
+			Self::F2nd => FormatVariant::Modern,
+			_ => FormatVariant::Classic,
+		}
+	}
+}
+
+/// Keeps sprites at known indices when writing. Pinned sprites keep their index, everything else is
+/// appended after the highest pinned index, and reserved ranges are never handed out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpriteIndexing {
+	pub pinned: HashMap<String, u32>,
+	pub reserved: Vec<Range<u32>>,
+}
+
+impl SpriteIndexing {
+	pub fn from_spr_db_set(spr_db_set: &diva_db::spr::SprDbSet) -> Self {
+		Self::from_spr_db_set_with_policy(spr_db_set, &NamePolicy::default())
+	}
+
+	pub fn from_spr_db_set_with_policy(
+		spr_db_set: &diva_db::spr::SprDbSet,
+		policy: &NamePolicy,
+	) -> Self {
+		Self {
+			pinned: spr_db_set
+				.sprites
+				.values()
+				.map(|entry| {
+					let name = policy.strip_sprite(&spr_db_set.name, &entry.name);
+					(name, u32::from(entry.index))
+				})
+				.collect(),
+			reserved: vec![],
+		}
+	}
+
+	pub(crate) fn is_reserved(&self, index: u32) -> bool {
+		self.reserved.iter().any(|range| range.contains(&index))
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SectionKind {
+	Header,
+	TextureSet,
+	Texture,
+	MipMap,
+	Sprites,
+	TextureNames,
+	SpriteNames,
+	SpriteExtras,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutSection {
+	pub kind: SectionKind,
+	pub name: Option<String>,
+	pub index: Option<u32>,
+	pub offset: u64,
+	pub size: u64,
+}
+
+/// Where every section of a written set ended up in the output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayoutMap {
+	pub sections: Vec<LayoutSection>,
+}
+
+impl LayoutMap {
+	fn push(
+		&mut self,
+		kind: SectionKind,
+		name: Option<&str>,
+		index: Option<u32>,
+		start: u64,
+		end: u64,
+	) {
+		self.sections.push(LayoutSection {
+			kind,
+			name: name.map(String::from),
+			index,
+			offset: start,
+			size: end - start,
+		});
+	}
+
+	pub fn find(&self, kind: SectionKind, name: Option<&str>) -> Option<&LayoutSection> {
+		self.sections
+			.iter()
+			.find(|section| section.kind == kind && section.name.as_deref() == name)
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WriteReport {
+	pub layout: Option<LayoutMap>,
+	/// Filled in when `WriteOptions::measure_quality` is set
+	pub quality: Vec<metrics::TextureQuality>,
+	pub timings: metrics::Timings,
+}
+
+impl From<WriteProfile> for WriteOptions {
+	fn from(value: WriteProfile) -> Self {
+		value.options()
+	}
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+	/// Substitute [`Texture::placeholder`] for textures that fail to decode instead of failing
+	/// the whole set
+	pub lenient: bool,
+	pub flip: FlipConvention,
+	/// Byte order of the file, detected from the header when unset
+	pub endianness: Option<Endian>,
+	/// How names from spr_db are turned into set names
+	pub name_policy: NamePolicy,
+	/// Keep every texture as its stored bytes instead of decoding it, and keep the file's order,
+	/// see [`SprSet::read_raw`]
+	pub keep_raw: bool,
+	/// Clean up applied to every texture and sprite name, the originals are kept in
+	/// [`SprSet::original_names`] for [`WriteOptions::restore_names`]
+	pub normalize_names: NameNormalization,
+}
+
+/// `name` normalized, recording the original when it changed. `used` starts out holding every name
+/// of the same kind as read, so a name that would land on another one is kept as it is wherever
+/// the other one sits in the file.
+fn normalize_name(
+	name: String,
+	normalization: &NameNormalization,
+	used: &mut std::collections::HashSet<String>,
+	originals: &mut HashMap<String, String>,
+	warnings: &mut Vec<Warning>,
+	kind: &str,
+) -> String {
+	let normalized = normalization.apply(&name);
+	if normalized == name {
+		return name;
+	}
+	if !used.insert(normalized.clone()) {
+		warnings.push(Warning {
+			code: ErrorCode::NameInUse,
+			message: format!(
+				"{kind} {name} normalizes to {normalized} which is already taken, kept as is"
+			),
+		});
+		return name;
+	}
+	originals.insert(normalized.clone(), name);
+	normalized
+}
+
+/// Guesses the byte order of the set at the reader's position from which reading of its header
+/// gives fields that fit in the file. Little endian when both or neither do. The reader is left
+/// where it started.
+pub fn detect_endianness<R: io::Read + io::Seek>(reader: &mut R) -> Result<Endian, SpriteError> {
+	let start = reader.stream_position()?;
+	let len = reader.seek(SeekFrom::End(0))?;
+	reader.seek(SeekFrom::Start(start))?;
+	let mut header = [0u8; 0x20];
+	let read = reader.read_exact(&mut header);
+	reader.seek(SeekFrom::Start(start))?;
+	// Too short for a header, leave the complaint to the structure check
+	if read.is_err() {
+		return Ok(Endian::Little);
+	}
+	let fits = |endian: Endian| {
+		header.chunks_exact(4).all(|field| {
+			let field = field.try_into().unwrap();
+			let value = match endian {
+				Endian::Big => u32::from_be_bytes(field),
+				_ => u32::from_le_bytes(field),
+			};
+			value as u64 <= len
+		})
+	};
+	if !fits(Endian::Little) && fits(Endian::Big) {
+		Ok(Endian::Big)
+	} else {
+		Ok(Endian::Little)
+	}
+}
+
+impl SprSet {
+	pub fn from_reader<R: io::Read + io::Seek>(
+		reader: &mut R,
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+	) -> Result<Self, SpriteError> {
+		Self::from_reader_with_hooks(reader, spr_db_set, &mut Hooks::new())
+	}
+
+	pub fn from_reader_with_hooks<R: io::Read + io::Seek>(
+		reader: &mut R,
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+		hooks: &mut Hooks,
+	) -> Result<Self, SpriteError> {
+		Self::from_reader_with_options(reader, spr_db_set, hooks, &LoadOptions::default())
+	}
+
+	pub fn from_reader_with_options<R: io::Read + io::Seek>(
+		reader: &mut R,
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+		hooks: &mut Hooks,
+		options: &LoadOptions,
+	) -> Result<Self, SpriteError> {
+		Self::from_reader_with_timings(reader, spr_db_set, hooks, options).map(|(set, _)| set)
+	}
+
+	/// [`SprSet::from_reader_with_options`], also reporting where the time went.
+	pub fn from_reader_with_timings<R: io::Read + io::Seek>(
+		reader: &mut R,
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+		hooks: &mut Hooks,
+		options: &LoadOptions,
+	) -> Result<(Self, metrics::Timings), SpriteError> {
+		let start = std::time::Instant::now();
+		let mut timings = metrics::Timings::default();
+		let (spr_set, parse) = metrics::time(|| -> Result<SprSetReader, SpriteError> {
+			let endian = match options.endianness {
+				Some(endian) => endian,
+				None => detect_endianness(reader)?,
+			};
+			structure::check_structure_with_endian(reader, endian)?;
+			SprSetReader::read(reader, endian)
+		});
+		let spr_set = spr_set?;
+		timings.parse = parse;
+		let mut out_sprites = IndexMap::with_capacity(spr_set.sprite_count as usize);
+		let mut out_textures = IndexMap::with_capacity(spr_set.tex_sets_count as usize);
+		let mut kept_textures = Vec::with_capacity(spr_set.tex_sets_count as usize);
+		let mut warnings = vec![];
+		let mut sprite_order = vec![];
+
+		let set_name = spr_db_set.map_or_else(String::new, |set| set.name.clone());
+		let policy = &options.name_policy;
+		let normalization = &options.normalize_names;
+		let mut original_names = OriginalNames::default();
+		let mut external_textures = HashMap::new();
+		let mut original_indices = OriginalIndices::default();
+
+		let mut texture_names = Vec::with_capacity(spr_set.tex_sets_count as usize);
+		for i in 0..spr_set.textures().len() {
+			let mut name = spr_set
+				.tex_names
+				.get(i as usize)
+				.ok_or_else(|| {
+					SpriteError::Malformed(format!("Texture {i} has no entry in the name table"))
+				})?
+				.to_string();
+			if name.is_empty() {
+				if let Some(spr_db_set) = spr_db_set {
+					name = match spr_db_set
+						.textures
+						.iter()
+						.find(|tex| tex.1.index as usize == i)
+					{
+						Some((_, entry)) => policy.strip_texture(&set_name, &entry.name),
+						None => missing_db_entry(&mut warnings, "texture", i),
+					};
+				}
+			}
+			texture_names.push(name);
+		}
+		let mut used_names = texture_names.iter().cloned().collect();
+		for (i, (tex, mut name)) in spr_set.textures().iter().zip(texture_names).enumerate() {
+			if !normalization.is_noop() {
+				name = normalize_name(
+					name,
+					normalization,
+					&mut used_names,
+					&mut original_names.textures,
+					&mut warnings,
+					"Texture",
+				);
+			}
+			let raw_version = match tex.deref() {
+				TexReader::Unknown(version) => Some(*version),
+				TexReader::Tex2d(_) if options.keep_raw => Some(4),
+				TexReader::TexCubeMap(_) if options.keep_raw => Some(5),
+				_ => None,
+			};
+			if let Some(version) = raw_version {
+				let pos = spr_set.tex_sets_ptr as u64 + tex.ptr as u64;
+				// Nothing is decoded, the time is spent copying the bytes
+				let (data, read_time) = metrics::time(|| {
+					let end = spr_set.structure_end_after(pos, reader)?;
+					reader.seek(SeekFrom::Start(pos))?;
+					let mut data = vec![0u8; (end - pos) as usize];
+					reader.read_exact(&mut data)?;
+					Ok::<_, SpriteError>(data)
+				});
+				timings.textures.push((name.to_string(), read_time));
+				let data = data?;
+				let (layers, mip_count, array_size) = match tex.deref() {
+					TexReader::Tex2d(texture) => (
+						&texture.mip_map_array[..],
+						texture.mip_levels as u32,
+						texture.array_size as u32,
+					),
+					TexReader::TexCubeMap(cubemap) => (
+						&cubemap.mip_map_array[..],
+						cubemap.mip_levels_adjusted as u32,
+						cubemap.array_size as u32,
+					),
+					TexReader::Unknown(_) => {
+						warnings.push(Warning {
+							code: ErrorCode::UnknownTextureVersion,
+							message: format!(
+								"Texture {name} uses unknown TXP version {version}, kept as raw bytes"
+							),
+						});
+						(&[][..], 1, 1)
+					}
+				};
+				let mut texture = Texture {
+					original_format: layers
+						.first()
+						.and_then(|layer| layer.mip_maps.first())
+						.map_or(TextureFormat::Unknown, |mip| mip.format),
+					mip_count,
+					array_size,
+					cubemap: version == 5,
+					raw: Some(RawTexture { version, data }),
+					..Texture::placeholder(0, 0)
+				};
+				match hooks.run_texture(name, &mut texture) {
+					Some(new_name) => {
+						kept_textures.push(Some(new_name.clone()));
+						original_indices.textures.insert(new_name.clone(), i as u32);
+						out_textures.insert(new_name, texture);
+					}
+					None => kept_textures.push(None),
+				}
+				continue;
+			}
+			let tex = tex.deref();
+			let texture_pos = spr_set.tex_sets_ptr as u64 + spr_set.textures()[i].ptr as u64;
+			let (texture, decode_time) = metrics::time(|| read_texture(tex, options));
+			timings.textures.push((name.to_string(), decode_time));
+			let mut texture =
+				texture.map_err(|err| err.in_texture(i, &name).at_offset(texture_pos))?;
+			match hooks.run_texture(name, &mut texture) {
+				Some(new_name) => {
+					kept_textures.push(Some(new_name.clone()));
+					original_indices.textures.insert(new_name.clone(), i as u32);
+					out_textures.insert(new_name, texture);
+				}
+				None => kept_textures.push(None),
+			}
+		}
+
+		let mut sprite_names = Vec::with_capacity(spr_set.sprite_count as usize);
+		for i in 0..spr_set.sprites.len() {
+			let mut name = spr_set
+				.sprite_names
+				.get(i as usize)
+				.ok_or_else(|| {
+					SpriteError::Malformed(format!("Sprite {i} has no entry in the name table"))
+				})?
+				.to_string();
+			if name.is_empty() {
+				if let Some(spr_db_set) = spr_db_set {
+					name = match spr_db_set
+						.sprites
+						.iter()
+						.find(|sprite| sprite.1.index as usize == i)
+					{
+						Some((_, entry)) => policy.strip_sprite(&set_name, &entry.name),
+						None => missing_db_entry(&mut warnings, "sprite", i),
+					};
+				}
+			}
+			sprite_names.push(name);
+		}
+		let mut used_names = sprite_names.iter().cloned().collect();
+		for (i, (spr, mut name)) in spr_set.sprites.iter().zip(sprite_names).enumerate() {
+			if !normalization.is_noop() {
+				name = normalize_name(
+					name,
+					normalization,
+					&mut used_names,
+					&mut original_names.sprites,
+					&mut warnings,
+					"Sprite",
+				);
+			}
+			let texture_name = match kept_textures.get(spr.texture_index as usize) {
+				Some(Some(texture_name)) => texture_name.clone(),
+				// Skipped textures take their sprites with them
+				Some(None) => continue,
+				// Reference-only sets index into another set's textures
+				None if kept_textures.is_empty() && spr.texture_index >= 0 => {
+					let index = spr.texture_index as u32;
+					let texture_name = reference::external_texture_name(index);
+					external_textures.insert(texture_name.clone(), index);
+					texture_name
+				}
+				None => {
+					return Err(SpriteError::Malformed(format!(
+						"Sprite uses texture {}, the set has {}",
+						spr.texture_index,
+						kept_textures.len()
+					))
+					.in_sprite(&name))
+				}
+			};
+			let mut sprite = Sprite {
+				screen_mode: spr_set
+					.sprite_extras
+					.get(i)
+					.ok_or_else(|| {
+						SpriteError::Malformed("Sprite has no screen mode entry".to_string())
+							.in_sprite(&name)
+					})?
+					.1,
+				pixel_region: spr.pixel_region,
+				texel_region: spr.texel_region,
+				rotate: spr.rotate,
+				texture_name,
+			};
+			if let Some(name) = hooks.run_sprite(name, &mut sprite) {
+				if out_sprites.insert(name.clone(), sprite).is_none() {
+					original_indices.sprites.insert(name.clone(), i as u32);
+					sprite_order.push(name);
+				}
+			}
+		}
+		let mut seen = std::collections::HashSet::new();
+		let texture_order = kept_textures
+			.iter()
+			.flatten()
+			.filter(|name| seen.insert(*name) && out_textures.contains_key(*name))
+			.cloned()
+			.collect::<Vec<_>>();
+
+		timings.total = start.elapsed();
+		Ok((
+			Self {
+				name: set_name,
+				flags: spr_set.flags,
+				textures: out_textures,
+				sprites: out_sprites,
+				warnings,
+				usage: Default::default(),
+				original_names,
+				external_textures,
+				original_indices,
+				texture_order: options.keep_raw.then_some(texture_order),
+				sprite_order: options.keep_raw.then_some(sprite_order),
+			},
+			timings,
+		))
+	}
+
+	pub fn to_writer<W: io::Write + io::Seek>(self, writer: &mut W) -> Result<(), SpriteError> {
+		self.to_writer_with_options(writer, &WriteOptions::default())?;
+		Ok(())
+	}
+
+	/// [`SprSet::to_writer`], keeping each texture's original format where it can be encoded
+	/// instead of writing everything as RGBA8.
+	pub fn to_writer_preserving_formats<W: io::Write + io::Seek>(
+		&self,
+		writer: &mut W,
+	) -> Result<WriteReport, SpriteError> {
+		let mut options = WriteOptions::default();
+		options.preserve_formats(self);
+		self.to_writer_with_options(writer, &options)
+	}
+
+	/// Writes to a sink that can't seek (pipes, sockets, archive builders) by laying the set out in
+	/// memory first and streaming the finished bytes.
+	pub fn to_plain_writer<W: io::Write>(
+		&self,
+		writer: &mut W,
+		options: &WriteOptions,
+	) -> Result<WriteReport, SpriteError> {
+		let mut buffer = Cursor::new(vec![]);
+		let report = self.to_writer_with_options(&mut buffer, options)?;
+		writer.write_all(buffer.get_ref())?;
+		writer.flush()?;
+		Ok(report)
+	}
+
+	pub fn to_writer_with_options<W: io::Write + io::Seek>(
+		&self,
+		writer: &mut W,
+		options: &WriteOptions,
+	) -> Result<WriteReport, SpriteError> {
+		options.validate(self)?;
+		let endian = options.endianness;
+		let textures = self
+			.written_texture_order(options)
+			.into_iter()
+			.map(|name| (name, &self.textures[name]))
+			.collect::<Vec<_>>();
+		let mut texture_ids = NameTable::with_capacity(textures.len());
+		for (name, _) in textures.iter() {
+			texture_ids.intern(name);
+		}
+		let filler_name = String::new();
+		let filler = Sprite {
+			screen_mode: ScreenMode::QVGA,
+			texel_region: Vec4::default(),
+			pixel_region: Vec4::default(),
+			texture_name: textures
+				.first()
+				.map(|(name, _)| name.to_string())
+				.unwrap_or_default(),
+			rotate: 0,
+		};
+		let sprites = match &options.sprite_indexing {
+			Some(indexing) => self
+				.assign_sprite_indices(indexing)?
+				.into_iter()
+				.map(|slot| match slot {
+					Some(name) => self
+						.sprites
+						.get_key_value(&name)
+						.ok_or_else(|| SpriteError::not_in_set("Sprite", &name)),
+					None => Ok((&filler_name, &filler)),
+				})
+				.collect::<Result<Vec<_>, _>>()?,
+			None => self
+				.written_sprite_order(options)
+				.into_iter()
+				.map(|name| (name, &self.sprites[name]))
+				.collect(),
+		};
+
+		let mut layout = LayoutMap::default();
+		let mut quality = vec![];
+		let start = std::time::Instant::now();
+		let mut timings = metrics::Timings::default();
+		let header_pos = writer.stream_position()?;
+		writer.write_type(&self.flags, endian)?;
+		let tex_ptr_pos = writer.stream_position()?;
+		writer.write_type(&0u32, endian)?;
+		writer.write_type(&(textures.len() as u32), endian)?;
+		writer.write_type(&(sprites.len() as u32), endian)?;
+		let spr_ptr_pos = writer.stream_position()?;
+		writer.write_type(&0u32, endian)?;
+		let tex_names_ptr_pos = writer.stream_position()?;
+		writer.write_type(&0u32, endian)?;
+		let spr_names_ptr_pos = writer.stream_position()?;
+		writer.write_type(&0u32, endian)?;
+		let spr_extra_ptr_pos = writer.stream_position()?;
+		writer.write_type(&0u32, endian)?;
+		layout.push(
+			SectionKind::Header,
+			None,
+			None,
+			header_pos,
+			writer.stream_position()?,
+		);
+
+		// Textures
+		align_writer(writer, options.alignment)?;
+		let tex_pos = writer.stream_position()?;
+		writer.seek(SeekFrom::Start(tex_ptr_pos))?;
+		writer.write_type(&(tex_pos as u32), endian)?;
+		writer.seek(SeekFrom::Start(tex_pos))?;
+		writer.write(b"TXP\x03")?;
+		writer.write_type(&(textures.len() as u32), endian)?;
+		writer.write_type(&0u32, endian)?; // Padding
+		let mut textures_pos = vec![];
+		for _ in textures.iter() {
+			textures_pos.push(writer.stream_position()?);
+			writer.write_type(&0u32, endian)?;
+		}
+		layout.push(
+			SectionKind::TextureSet,
+			None,
+			None,
+			tex_pos,
+			writer.stream_position()?,
+		);
+		for (i, (name, texture)) in textures.iter().enumerate() {
+			if let Some(raw) = &texture.raw {
+				// Offsets inside are relative to the texture, so the bytes can move as a block
+				align_writer(writer, options.alignment)?;
+				let pos = writer.stream_position()?;
+				writer.seek(SeekFrom::Start(textures_pos[i]))?;
+				writer.write_type(&((pos - tex_pos) as u32), endian)?;
+				writer.seek(SeekFrom::Start(pos))?;
+				let (written, write_time) = metrics::time(|| writer.write_all(&raw.data));
+				timings.textures.push((name.to_string(), write_time));
+				written?;
+				layout.push(
+					SectionKind::Texture,
+					Some(name.as_str()),
+					None,
+					pos,
+					writer.stream_position()?,
+				);
+				continue;
+			}
+			let source = texture;
+			let mip_levels = options.mip_mode.levels(source);
+			let (layers, encode_time) = metrics::time(|| {
+				std::iter::once(&texture.image)
+					.chain(&texture.layers)
+					.map(|image| {
+						let image = options.prepare_texture(name, image);
+						mipmap::generate_mips(&image, mip_levels, options.mip_filter)
+							.iter()
+							.map(|mip| {
+								let format = options.format_for(name);
+								let encoder = options.encoder_for(name);
+								let mut dds = encode_texture(mip, format, options.flip, encoder)?;
+								if options.importance_for(name) == Importance::SpriteEdges
+									&& encoder != Encoder::Best
+								{
+									let blocks = self.sprite_edge_blocks(
+										name,
+										mip.width(),
+										mip.height(),
+										options.flip,
+									);
+									refine_blocks(&mut dds, mip, format, options.flip, &blocks);
+								}
+								Ok::<_, SpriteError>(dds)
+							})
+							.collect::<Result<Vec<_>, _>>()
+					})
+					.collect::<Result<Vec<_>, _>>()
+			});
+			timings.textures.push((name.to_string(), encode_time));
+			let layers = layers?;
+			let texture = &layers[0][0];
+			if options.measure_quality {
+				let (width, height) = (texture.get_width(), texture.get_height());
+				let decoded = decode_region(
+					texture.get_data(0)?,
+					options.format_for(name),
+					width,
+					height,
+					(0, 0, width, height),
+					options.flip,
+				)
+				.ok_or_else(|| {
+					SpriteError::Dds(format!(
+						"Texture {name} could not be decoded again to measure its quality"
+					))
+				})?;
+				let source = source.image.to_rgba8();
+				quality.push(metrics::TextureQuality {
+					name: name.to_string(),
+					format: options.format_for(name),
+					psnr: metrics::psnr(&source, &decoded),
+					ssim: metrics::ssim(&source, &decoded),
+				});
+			}
+			align_writer(writer, options.alignment)?;
+			let pos = writer.stream_position()?;
+			writer.seek(SeekFrom::Start(textures_pos[i]))?;
+			writer.write_type(&((pos - tex_pos) as u32), endian)?;
+			writer.seek(SeekFrom::Start(pos))?;
+			let array_size = layers.len() as u32;
+			if source.cubemap {
+				// Cube maps count the mips of every face in the levels byte too
+				writer.write(b"TXP\x05")?;
+				writer.write_type(&(mip_levels * array_size), endian)?;
+				writer.write_type(&((mip_levels * array_size) as u8), endian)?;
+			} else {
+				writer.write(b"TXP\x04")?;
+				writer.write_type(&(mip_levels * array_size), endian)?;
+				writer.write_type(&(mip_levels as u8), endian)?;
+			}
+			writer.write_type(&(array_size as u8), endian)?;
+			writer.write_type(&(texture.header.depth.unwrap_or(8) as u8), endian)?;
+			writer.write_type(&0u8, endian)?; // dimensions
+
+			let mut mip_pos = vec![];
+			for _ in 0..mip_levels * array_size {
+				mip_pos.push(writer.stream_position()?);
+				writer.write_type(&0u32, endian)?;
+			}
+			let mips = layers.iter().enumerate().flat_map(|(layer, mips)| {
+				mips.iter()
+					.enumerate()
+					.map(move |(level, mip)| (layer as u32, level as u32, mip))
+			});
+			for (i, (layer, level, mip)) in (0..).zip(mips) {
+				align_writer(writer, options.alignment)?;
+				let data_pos = writer.stream_position()?;
+				writer.seek(SeekFrom::Start(mip_pos[i as usize]))?;
+				writer.write_type(&((data_pos - pos) as u32), endian)?;
+				writer.seek(SeekFrom::Start(data_pos))?;
+				writer.write(b"TXP\x02")?;
+				writer.write_type(&mip.get_width(), endian)?;
+				writer.write_type(&mip.get_height(), endian)?;
+				let format = mip.get_dxgi_format().ok_or_else(|| {
+					SpriteError::Dds(format!("Texture {name} was encoded without a DXGI format"))
+				})?;
+				writer.write_type(&(TextureFormat::from_dxgi_format(&format) as u32), endian)?;
+				// Indices read with the texture still apply while its chain has the same length
+				let source_mips = source.mip_count.max(1);
+				let kept = if mip_levels == source_mips {
+					source.mip_indices.get(i as usize)
+				} else if level == 0 {
+					source.mip_indices.get((layer * source_mips) as usize)
+				} else {
+					None
+				};
+				let indices = kept.copied().unwrap_or(MipIndex {
+					index: level as u8,
+					array_index: layer as u8,
+				});
+				writer.write_type(&indices.index, endian)?;
+				writer.write_type(&indices.array_index, endian)?;
+				writer.write_type(&0u16, endian)?;
+				let data = swap_texture_data(
+					TextureFormat::from_dxgi_format(&format),
+					mip.get_data(0)?,
+					endian,
+				);
+				writer.write_type(&(data.len() as u32), endian)?;
+				writer.write(&data)?;
+				layout.push(
+					SectionKind::MipMap,
+					Some(name.as_str()),
+					Some(i),
+					data_pos,
+					writer.stream_position()?,
+				);
+			}
+			layout.push(
+				SectionKind::Texture,
+				Some(name.as_str()),
+				None,
+				pos,
+				writer.stream_position()?,
+			);
+		}
+
+		// Sprites
+		align_writer(writer, options.alignment)?;
+		let pos = writer.stream_position()?;
+		writer.seek(SeekFrom::Start(spr_ptr_pos))?;
+		writer.write_type(&(pos as u32), endian)?;
+		writer.seek(SeekFrom::Start(pos))?;
+		for (name, sprite) in sprites.iter() {
+			let index = match texture_ids.get(&sprite.texture_name) {
+				Some(index) => index.index() as i32,
+				None => *self
+					.external_textures
+					.get(&sprite.texture_name)
+					.ok_or_else(|| SpriteError::missing_texture(name, &sprite.texture_name))?
+					as i32,
+			};
+			writer.write_type(&index, endian)?;
+			writer.write_type(&sprite.rotate, endian)?;
+			writer.write_type(&sprite.texel_region, endian)?;
+			writer.write_type(&sprite.pixel_region, endian)?;
+		}
+		layout.push(
+			SectionKind::Sprites,
+			None,
+			None,
+			pos,
+			writer.stream_position()?,
+		);
+
+		// Texture names
+		let pos = writer.stream_position()?;
+		writer.seek(SeekFrom::Start(tex_names_ptr_pos))?;
+		writer.write_type(&(pos as u32), endian)?;
+		writer.seek(SeekFrom::Start(pos))?;
+		let mut texture_names_locs = vec![];
+		for _ in textures.iter() {
+			texture_names_locs.push(writer.stream_position()?);
+			writer.write_type(&0u32, endian)?;
+		}
+		for (i, (name, _)) in textures.iter().enumerate() {
+			let pos = writer.stream_position()?;
+			writer.seek(SeekFrom::Start(texture_names_locs[i]))?;
+			writer.write_type(&(pos as u32), endian)?;
+			writer.seek(SeekFrom::Start(pos))?;
+			let name = if options.write_names {
+				options.written_name(options.stored_texture_name(self, name))
+			} else {
+				""
+			};
+			writer.write(std::ffi::CString::new(name)?.as_bytes_with_nul())?;
+		}
+		layout.push(
+			SectionKind::TextureNames,
+			None,
+			None,
+			pos,
+			writer.stream_position()?,
+		);
+
+		// Sprite names
+		let pos = writer.stream_position()?;
+		writer.seek(SeekFrom::Start(spr_names_ptr_pos))?;
+		writer.write_type(&(pos as u32), endian)?;
+		writer.seek(SeekFrom::Start(pos))?;
+		let mut spr_names_locs = vec![];
+		for _ in sprites.iter() {
+			spr_names_locs.push(writer.stream_position()?);
+			writer.write_type(&0u32, endian)?;
+		}
+		for (i, (name, _)) in sprites.iter().enumerate() {
+			let pos = writer.stream_position()?;
+			writer.seek(SeekFrom::Start(spr_names_locs[i]))?;
+			writer.write_type(&(pos as u32), endian)?;
+			writer.seek(SeekFrom::Start(pos))?;
+			let name = if options.write_names {
+				options.written_name(options.stored_sprite_name(self, name))
+			} else {
+				""
+			};
+			writer.write(std::ffi::CString::new(name)?.as_bytes_with_nul())?;
+		}
+		layout.push(
+			SectionKind::SpriteNames,
+			None,
+			None,
+			pos,
+			writer.stream_position()?,
+		);
+
+		// Sprite extras
+		align_writer(writer, options.alignment)?;
+		let pos = writer.stream_position()?;
+		writer.seek(SeekFrom::Start(spr_extra_ptr_pos))?;
+		writer.write_type(&(pos as u32), endian)?;
+		writer.seek(SeekFrom::Start(pos))?;
+		for (_, sprite) in sprites.iter() {
+			writer.write_type(&0u32, endian)?;
+			writer.write_type(&(sprite.screen_mode as u32), endian)?;
+		}
+		layout.push(
+			SectionKind::SpriteExtras,
+			None,
+			None,
+			pos,
+			writer.stream_position()?,
+		);
+
+		timings.total = start.elapsed();
+		Ok(WriteReport {
+			layout: options.record_layout.then_some(layout),
+			quality,
+			timings,
+		})
+	}
+}
+
+impl SprSet {
+	/// Crops a single sprite straight out of a set's raw data, decoding only the blocks it covers.
+	/// The data is classic layout, so `FlipConvention::Auto` reads it bottom-up.
+	pub fn read_sprite_image<R: io::Read + io::Seek>(
+		reader: &mut R,
+		sprite_name: &str,
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+		flip: FlipConvention,
+	) -> Result<DynamicImage, SpriteError> {
+		structure::check_structure(reader)?;
+		let endian = detect_endianness(reader)?;
+		let spr_set = SprSetReader::read(reader, endian)?;
+		let index = spr_set
+			.sprite_names
+			.iter()
+			.enumerate()
+			.position(|(i, name)| {
+				let name = name.to_string();
+				if !name.is_empty() {
+					return name == sprite_name;
+				}
+				let Some(spr_db_set) = spr_db_set else {
+					return false;
+				};
+				let policy = NamePolicy::default();
+				spr_db_set.sprites.values().any(|entry| {
+					entry.index as usize == i
+						&& policy.strip_sprite(&spr_db_set.name, &entry.name) == sprite_name
+				})
+			})
+			.ok_or_else(|| SpriteError::not_in_set("Sprite", sprite_name))?;
+		let sprite = spr_set.sprites.get(index).ok_or_else(|| {
+			SpriteError::Malformed(format!("Sprite {sprite_name} has a name but no record"))
+		})?;
+		let texture = spr_set
+			.textures()
+			.get(sprite.texture_index as usize)
+			.ok_or_else(|| {
+				SpriteError::Validation(
+					ErrorCode::MissingTexture,
+					format!(
+						"Sprite {sprite_name} uses texture {}, the set has {}",
+						sprite.texture_index,
+						spr_set.textures().len()
+					),
+				)
+			})?;
+		let mip = match texture.deref() {
+			TexReader::Tex2d(texture) => &texture.mip_map_array,
+			TexReader::TexCubeMap(cubemap) => &cubemap.mip_map_array,
+			TexReader::Unknown(version) => {
+				return Err(SpriteError::Malformed(format!(
+					"Texture uses unknown TXP version {version}"
+				)))
+			}
+		}
+		.first()
+		.and_then(|layer| layer.mip_maps.first())
+		.ok_or_else(|| {
+			SpriteError::Malformed(format!("The texture of sprite {sprite_name} has no mips"))
+		})?;
+
+		let region = SubPixel::Floor.bounds(&sprite.pixel_region);
+		let image = decode_region(
+			&mip.data,
+			mip.format,
+			mip.width as u32,
+			mip.height as u32,
+			region,
+			flip.resolve(FormatVariant::Classic),
+		)
+		.ok_or_else(|| {
+			SpriteError::Dds(format!(
+				"Sprite {sprite_name} lies outside its texture or its {:?} data can't be decoded",
+				mip.format
+			))
+		})?;
+		Ok(DynamicImage::ImageRgba8(image))
+	}
+}
+
+/// 16 bit packed formats are stored as native 16 bit values, so they swap along with everything
+/// else. Byte sized and block compressed data stays as is.
+fn is_packed16(format: TextureFormat) -> bool {
+	matches!(
+		format,
+		TextureFormat::RGB5 | TextureFormat::RGB5A1 | TextureFormat::RGBA4 | TextureFormat::L8A8
+	)
+}
+
+pub(crate) fn swap_texture_data(
+	format: TextureFormat,
+	data: &[u8],
+	endian: Endian,
+) -> std::borrow::Cow<'_, [u8]> {
+	if !is_packed16(format) || endian != Endian::Big {
+		return std::borrow::Cow::Borrowed(data);
+	}
+	let mut swapped = data.to_vec();
+	for pair in swapped.chunks_exact_mut(2) {
+		pair.swap(0, 1);
+	}
+	std::borrow::Cow::Owned(swapped)
+}
+
+/// Decodes a texture as [`SprSet::from_reader_with_options`] does. Unknown TXP versions fail, the
+/// caller keeps those raw.
+pub(crate) fn read_texture(tex: &TexReader, options: &LoadOptions) -> Result<Texture, SpriteError> {
+	let image = match decode_tex(tex, options.flip) {
+		Ok(image) => Some(image),
+		Err(_) if options.lenient => None,
+		Err(err) => return Err(err),
+	};
+	let (original_format, mip_count, array_size) = match &tex {
+		TexReader::Tex2d(texture) => (
+			&texture.mip_map_array,
+			texture.mip_levels as u32,
+			texture.array_size as u32,
+		),
+		TexReader::TexCubeMap(cubemap) => (
+			&cubemap.mip_map_array,
+			cubemap.mip_levels_adjusted as u32,
+			cubemap.array_size as u32,
+		),
+		TexReader::Unknown(version) => {
+			return Err(SpriteError::Malformed(format!(
+				"Texture uses unknown TXP version {version}"
+			)))
+		}
+	};
+	let mip_indices = original_format
+		.iter()
+		.flat_map(|layer| &layer.mip_maps)
+		.map(|mip| MipIndex {
+			index: mip.index,
+			array_index: mip.array_index,
+		})
+		.collect();
+	let first_mip = original_format
+		.first()
+		.and_then(|layer| layer.mip_maps.first());
+	// Layers after the first, only their top mip is kept like for the first
+	let layers = match original_format
+		.iter()
+		.skip(1)
+		.map(|layer| {
+			let mip = layer.mip_maps.first()?;
+			let (width, height) = (mip.width as u32, mip.height as u32);
+			decode_region(
+				&mip.data,
+				mip.format,
+				width,
+				height,
+				(0, 0, width, height),
+				options.flip,
+			)
+			.map(DynamicImage::ImageRgba8)
+		})
+		.collect::<Option<Vec<_>>>()
+	{
+		Some(layers) => layers,
+		None if options.lenient => vec![],
+		None => {
+			return Err(SpriteError::Dds(
+				"A layer after the first could not be decoded".to_string(),
+			))
+		}
+	};
+	let texture = match (image, first_mip) {
+		(Some(image), Some(mip)) => Texture {
+			image,
+			original_format: mip.format,
+			mip_count,
+			array_size,
+			placeholder: false,
+			raw: None,
+			mip_indices,
+			layers,
+			cubemap: matches!(tex, TexReader::TexCubeMap(_)),
+		},
+		(Some(_), None) => return Err(SpriteError::Malformed("Texture has no mips".to_string())),
+		(None, mip) => Texture {
+			mip_indices,
+			original_format: mip.map_or(TextureFormat::Unknown, |mip| mip.format),
+			mip_count,
+			array_size,
+			..Texture::placeholder(
+				mip.map_or(0, |mip| mip.width as u32),
+				mip.map_or(0, |mip| mip.height as u32),
+			)
+		},
+	};
+	Ok(texture)
+}
+
+/// Name for an entry spr_db doesn't list, so a partially updated db still loads.
+pub(crate) fn missing_db_entry(warnings: &mut Vec<Warning>, kind: &str, index: usize) -> String {
+	let name = format!("{kind}_{index}");
+	warnings.push(Warning {
+		code: ErrorCode::MissingDbEntry,
+		message: format!("spr_db has no {kind} at index {index}, named it {name}"),
+	});
+	name
+}
+
+fn align_writer<W: io::Write + io::Seek>(writer: &mut W, alignment: u32) -> io::Result<()> {
+	let pos = writer.stream_position()?;
+	let aligned = pos.next_multiple_of(alignment.max(1) as u64);
+	writer.write_all(&vec![0u8; (aligned - pos) as usize])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn read_upper_cased(textures: &[&str], sprites: &[&str]) -> SprSet {
+		let region = Vec4 {
+			x: 0.0,
+			y: 0.0,
+			z: 4.0,
+			w: 4.0,
+		};
+		let set = SprSet::from_parts(
+			"test",
+			textures.iter().map(|name| {
+				let image = DynamicImage::new_rgba8(4, 4);
+				(name.to_string(), Texture::new(image))
+			}),
+			sprites.iter().map(|name| {
+				let sprite = Sprite::new(textures[0], region, ScreenMode::HDTV720);
+				(name.to_string(), sprite)
+			}),
+			0,
+		)
+		.unwrap();
+		let mut out = Cursor::new(vec![]);
+		set.to_writer_with_options(&mut out, &WriteOptions::default())
+			.unwrap();
+		out.set_position(0);
+		let options = LoadOptions {
+			normalize_names: NameNormalization {
+				upper_case: true,
+				..Default::default()
+			},
+			..Default::default()
+		};
+		SprSet::from_reader_with_options(&mut out, None, &mut Hooks::new(), &options).unwrap()
+	}
+
+	#[test]
+	fn default_options_round_trip() {
+		let image = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(8, 4, |x, y| {
+			image::Rgba([x as u8 * 32, y as u8 * 64, 7, 255 - x as u8])
+		}));
+		let region = Vec4 {
+			x: 0.0,
+			y: 0.0,
+			z: 8.0,
+			w: 4.0,
+		};
+		let set = SprSet::from_parts(
+			"test",
+			[("TEX".to_string(), Texture::new(image.clone()))],
+			[(
+				"SPRITE".to_string(),
+				Sprite::new("TEX", region, ScreenMode::HDTV720),
+			)],
+			0,
+		)
+		.unwrap();
+		let mut out = Cursor::new(vec![]);
+		set.to_writer_with_options(&mut out, &WriteOptions::default())
+			.unwrap();
+		out.set_position(0);
+		let read = SprSet::from_reader(&mut out, None).unwrap();
+		let texture = &read.textures["TEX"];
+		assert_eq!(texture.original_format, TextureFormat::RGBA8);
+		assert_eq!(texture.image.to_rgba8(), image.to_rgba8());
+		assert_eq!(read.sprites["SPRITE"].pixel_region, region);
+	}
+
+	#[test]
+	fn raw_textures_are_timed() {
+		let set = SprSet::from_parts(
+			"test",
+			["A", "B"].map(|name| {
+				let image = DynamicImage::new_rgba8(4, 4);
+				(name.to_string(), Texture::new(image))
+			}),
+			[],
+			0,
+		)
+		.unwrap();
+		let mut out = Cursor::new(vec![]);
+		set.to_writer_with_options(&mut out, &WriteOptions::default())
+			.unwrap();
+		out.set_position(0);
+		let options = LoadOptions {
+			keep_raw: true,
+			..Default::default()
+		};
+		let (read, timings) =
+			SprSet::from_reader_with_timings(&mut out, None, &mut Hooks::new(), &options).unwrap();
+		let names = |timings: &metrics::Timings| {
+			timings
+				.textures
+				.iter()
+				.map(|(name, _)| name.clone())
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(names(&timings), ["A", "B"]);
+		let report = read
+			.to_writer_with_options(&mut Cursor::new(vec![]), &WriteOptions::default())
+			.unwrap();
+		assert_eq!(names(&report.timings), ["A", "B"]);
+	}
+
+	#[test]
+	fn renames_survive_restore_names() {
+		let mut set = read_upper_cased(&["tex"], &["icon"]);
+		assert_eq!(set.original_sprite_name("ICON"), "icon");
+		Command::RenameSprite {
+			old: "ICON".to_string(),
+			new: "LOGO".to_string(),
+		}
+		.apply(&mut set)
+		.unwrap();
+		Command::RenameTexture {
+			old: "TEX".to_string(),
+			new: "PAGE".to_string(),
+		}
+		.apply(&mut set)
+		.unwrap();
+
+		let options = WriteOptions {
+			restore_names: true,
+			..Default::default()
+		};
+		let mut out = Cursor::new(vec![]);
+		set.to_writer_with_options(&mut out, &options).unwrap();
+		out.set_position(0);
+		let read = SprSet::from_reader(&mut out, None).unwrap();
+		assert!(read.sprites.contains_key("LOGO"));
+		assert!(read.textures.contains_key("PAGE"));
+		assert_eq!(read.sprites["LOGO"].texture_name, "PAGE");
+	}
+
+	#[test]
+	fn normalized_names_are_unique_per_kind() {
+		let set = read_upper_cased(&["icon"], &["icon"]);
+		assert!(set.textures.contains_key("ICON"));
+		assert!(set.sprites.contains_key("ICON"));
+		assert!(set.warnings.is_empty());
+	}
+
+	#[test]
+	fn normalized_names_never_replace_later_ones() {
+		let set = read_upper_cased(&["tex"], &["a", "A"]);
+		assert_eq!(set.sprites.len(), 2);
+		assert!(set.sprites.contains_key("a"));
+		assert!(set.sprites.contains_key("A"));
+		assert!(set.original_names.sprites.is_empty());
+		assert_eq!(set.warnings.len(), 1);
+	}
+}
@@ -0,0 +1,165 @@
+//! Sprites arranged by their name prefixes, so `SEL_PV_LOGO` sits under `SEL` then `PV`. Editors
+//! can show thousands of sprites as a tree and work on a whole branch at once.
+use crate::*;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct GroupNode {
+	/// Prefix of everything under this node, without a trailing separator. Empty for the root.
+	pub path: String,
+	/// Sprites whose name ends at this level
+	pub sprites: Vec<String>,
+	pub children: BTreeMap<String, GroupNode>,
+}
+
+impl GroupNode {
+	/// Every sprite in this node and below, sorted.
+	pub fn all_sprites(&self) -> Vec<String> {
+		let mut sprites = self.sprites.clone();
+		for child in self.children.values() {
+			sprites.extend(child.all_sprites());
+		}
+		sprites.sort();
+		sprites
+	}
+
+	/// The node at `path`, e.g. `SEL_PV` with separator `_`.
+	pub fn find(&self, path: &str, separator: char) -> Option<&GroupNode> {
+		if path.is_empty() {
+			return Some(self);
+		}
+		path.split(separator)
+			.try_fold(self, |node, part| node.children.get(part))
+	}
+}
+
+/// Groups sprite names on `separator`. The last part of a name is the sprite itself, every part
+/// before it a group.
+pub fn group_names<'a>(names: impl IntoIterator<Item = &'a str>, separator: char) -> GroupNode {
+	let mut root = GroupNode::default();
+	for name in names {
+		let mut node = &mut root;
+		let mut parts = name.split(separator).collect::<Vec<_>>();
+		parts.pop();
+		for part in parts {
+			let path = if node.path.is_empty() {
+				part.to_string()
+			} else {
+				format!("{}{separator}{part}", node.path)
+			};
+			node = node
+				.children
+				.entry(part.to_string())
+				.or_insert_with(|| GroupNode {
+					path,
+					..Default::default()
+				});
+		}
+		node.sprites.push(name.to_string());
+	}
+	sort_sprites(&mut root);
+	root
+}
+
+fn sort_sprites(node: &mut GroupNode) {
+	node.sprites.sort();
+	for child in node.children.values_mut() {
+		sort_sprites(child);
+	}
+}
+
+fn in_group(name: &str, group: &str, separator: char) -> bool {
+	name.strip_prefix(group)
+		.is_some_and(|rest| rest.starts_with(separator))
+}
+
+impl SprSet {
+	pub fn groups(&self, separator: char) -> GroupNode {
+		group_names(self.sprites.keys().map(|name| name.as_str()), separator)
+	}
+
+	/// Sprites under `group`, sorted.
+	pub fn group_sprites(&self, group: &str, separator: char) -> Vec<String> {
+		let mut sprites = self
+			.sprites
+			.keys()
+			.filter(|name| in_group(name, group, separator))
+			.cloned()
+			.collect::<Vec<_>>();
+		sprites.sort();
+		sprites
+	}
+
+	/// Sets `mode` on every sprite under `group`, returning how many changed.
+	pub fn set_group_screen_mode(
+		&mut self,
+		group: &str,
+		separator: char,
+		mode: ScreenMode,
+	) -> usize {
+		self.set_screen_mode_where(|name, _| in_group(name, group, separator), mode)
+	}
+
+	/// Removes every sprite under `group`, returning them by name.
+	pub fn remove_group(&mut self, group: &str, separator: char) -> Vec<(String, Sprite)> {
+		self.group_sprites(group, separator)
+			.into_iter()
+			.filter_map(|name| {
+				let sprite = self.sprites.shift_remove(&name)?;
+				Some((name, sprite))
+			})
+			.collect()
+	}
+
+	/// Moves every sprite under `group` to `new_group`, e.g. `SEL_PV_LOGO` to `SEL_PV2_LOGO`.
+	/// Fails without changing anything if a new name is taken.
+	pub fn rename_group(
+		&mut self,
+		group: &str,
+		new_group: &str,
+		separator: char,
+	) -> Result<usize, SpriteError> {
+		let renames = self
+			.group_sprites(group, separator)
+			.into_iter()
+			.map(|name| {
+				let new_name = format!("{new_group}{}", &name[group.len()..]);
+				(name, new_name)
+			})
+			.collect::<Vec<_>>();
+		let moving = renames
+			.iter()
+			.map(|(name, _)| name.as_str())
+			.collect::<Vec<_>>();
+		if let Some((_, taken)) = renames.iter().find(|(_, new_name)| {
+			self.sprites.contains_key(new_name) && !moving.contains(&new_name.as_str())
+		}) {
+			return Err(SpriteError::Validation(
+				ErrorCode::NameInUse,
+				format!("Sprite name {taken} is already used"),
+			));
+		}
+		// All at once and in place, a new name may be the old name of another sprite that moves
+		let renames = renames.into_iter().collect::<HashMap<_, _>>();
+		let rename = |name: String| renames.get(&name).cloned().unwrap_or(name);
+		self.sprites = std::mem::take(&mut self.sprites)
+			.into_iter()
+			.map(|(name, sprite)| (rename(name), sprite))
+			.collect();
+		self.original_indices.sprites = std::mem::take(&mut self.original_indices.sprites)
+			.into_iter()
+			.map(|(name, index)| (rename(name), index))
+			.collect();
+		self.original_names.sprites = std::mem::take(&mut self.original_names.sprites)
+			.into_iter()
+			.map(|(name, original)| (rename(name), original))
+			.collect();
+		for name in self.sprite_order.iter_mut().flatten() {
+			*name = rename(std::mem::take(name));
+		}
+		for (name, new_name) in &renames {
+			self.usage.rename_sprite(name, new_name);
+		}
+		Ok(renames.len())
+	}
+}
@@ -0,0 +1,99 @@
+//! Which parts of a texture sprites use, and how often. Unused areas are what a repack saves,
+//! areas several sprites share are what it would duplicate.
+use crate::*;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heatmap {
+	pub width: u32,
+	pub height: u32,
+	/// Number of sprites covering each pixel, row by row from the top
+	pub counts: Vec<u32>,
+}
+
+impl Heatmap {
+	pub fn count(&self, x: u32, y: u32) -> u32 {
+		self.counts[(y * self.width + x) as usize]
+	}
+
+	/// Fraction of the texture no sprite covers.
+	pub fn unused_fraction(&self) -> f64 {
+		self.fraction(|count| count == 0)
+	}
+
+	/// Fraction of the texture covered by more than one sprite.
+	pub fn shared_fraction(&self) -> f64 {
+		self.fraction(|count| count > 1)
+	}
+
+	fn fraction(&self, f: impl Fn(u32) -> bool) -> f64 {
+		if self.counts.is_empty() {
+			return 0.0;
+		}
+		self.counts.iter().filter(|count| f(**count)).count() as f64 / self.counts.len() as f64
+	}
+
+	/// Unused pixels in red, pixels one sprite uses in green and shared ones from yellow to white
+	/// the more sprites share them. With `base` the texture shows through dimmed underneath.
+	pub fn render(&self, base: Option<&DynamicImage>) -> image::RgbaImage {
+		let base = base.map(|base| base.to_luma_alpha8());
+		image::RgbaImage::from_fn(self.width, self.height, |x, y| {
+			let heat = match self.count(x, y) {
+				0 => [200.0, 40.0, 40.0],
+				1 => [40.0, 180.0, 60.0],
+				count => {
+					let t = ((count - 2) as f32 / 4.0).min(1.0);
+					[255.0, 220.0 + 35.0 * t, 40.0 + 215.0 * t]
+				}
+			};
+			let shade = match &base {
+				Some(base) if x < base.width() && y < base.height() => {
+					let [luma, alpha] = base.get_pixel(x, y).0;
+					0.4 + 0.6 * (luma as f32 / 255.0) * (alpha as f32 / 255.0)
+				}
+				_ => 1.0,
+			};
+			let [r, g, b] = heat.map(|value| (value * shade) as u8);
+			image::Rgba([r, g, b, 255])
+		})
+	}
+}
+
+impl SprSet {
+	pub fn heatmap(&self, texture: &str) -> Option<Heatmap> {
+		let image = &self.textures.get(texture)?.image;
+		let (width, height) = (image.width(), image.height());
+		let mut counts = vec![0u32; (width * height) as usize];
+		for sprite in self
+			.sprites
+			.values()
+			.filter(|sprite| sprite.texture_name == texture)
+		{
+			let region = sprite.pixel_region;
+			let x0 = (region.x.max(0.0) as u32).min(width);
+			let y0 = region.y.max(0.0) as u32;
+			let x1 = ((region.x + region.z).max(0.0).ceil() as u32).min(width);
+			let y1 = ((region.y + region.w).max(0.0).ceil() as u32).min(height);
+			for y in y0..y1 {
+				for count in
+					&mut counts[(y * width + x0) as usize..(y * width + x1.max(x0)) as usize]
+				{
+					*count += 1;
+				}
+			}
+		}
+		Some(Heatmap {
+			width,
+			height,
+			counts,
+		})
+	}
+
+	/// [`SprSet::heatmap`] of every texture.
+	pub fn heatmaps(&self) -> BTreeMap<String, Heatmap> {
+		self.textures
+			.keys()
+			.filter_map(|name| Some((name.clone(), self.heatmap(name)?)))
+			.collect()
+	}
+}
@@ -0,0 +1,115 @@
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookAction {
+	Keep,
+	Skip,
+	Rename(String),
+}
+
+type SpriteHook<'a> = Box<dyn FnMut(&str, &mut Sprite) -> HookAction + 'a>;
+type TextureHook<'a> = Box<dyn FnMut(&str, &mut Texture) -> HookAction + 'a>;
+
+/// Callbacks run on every sprite and texture as a set is read, or just before it is written.
+/// Hooks run in the order they were added and each sees the name left by the previous one.
+#[derive(Default)]
+pub struct Hooks<'a> {
+	sprite: Vec<SpriteHook<'a>>,
+	texture: Vec<TextureHook<'a>>,
+}
+
+impl std::fmt::Debug for Hooks<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Hooks")
+			.field("sprite", &self.sprite.len())
+			.field("texture", &self.texture.len())
+			.finish()
+	}
+}
+
+impl<'a> Hooks<'a> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn on_sprite(mut self, hook: impl FnMut(&str, &mut Sprite) -> HookAction + 'a) -> Self {
+		self.sprite.push(Box::new(hook));
+		self
+	}
+
+	pub fn on_texture(mut self, hook: impl FnMut(&str, &mut Texture) -> HookAction + 'a) -> Self {
+		self.texture.push(Box::new(hook));
+		self
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.sprite.is_empty() && self.texture.is_empty()
+	}
+
+	/// Returns the name the sprite should be stored under, or `None` if a hook skipped it.
+	pub(crate) fn run_sprite(&mut self, mut name: String, sprite: &mut Sprite) -> Option<String> {
+		for hook in self.sprite.iter_mut() {
+			match hook(&name, sprite) {
+				HookAction::Keep => {}
+				HookAction::Skip => return None,
+				HookAction::Rename(new_name) => name = new_name,
+			}
+		}
+		Some(name)
+	}
+
+	pub(crate) fn run_texture(
+		&mut self,
+		mut name: String,
+		texture: &mut Texture,
+	) -> Option<String> {
+		for hook in self.texture.iter_mut() {
+			match hook(&name, texture) {
+				HookAction::Keep => {}
+				HookAction::Skip => return None,
+				HookAction::Rename(new_name) => name = new_name,
+			}
+		}
+		Some(name)
+	}
+}
+
+impl SprSet {
+	/// Runs `hooks` over the set in place. Sprites on a skipped texture are dropped with it, and
+	/// sprites follow their texture when it is renamed.
+	pub fn apply_hooks(&mut self, hooks: &mut Hooks) {
+		let mut renamed = HashMap::new();
+		let textures = std::mem::take(&mut self.textures);
+		for (name, mut texture) in textures {
+			if let Some(new_name) = hooks.run_texture(name.clone(), &mut texture) {
+				renamed.insert(name, new_name.clone());
+				self.textures.insert(new_name, texture);
+			}
+		}
+
+		let sprites = std::mem::take(&mut self.sprites);
+		for (name, mut sprite) in sprites {
+			match renamed.get(&sprite.texture_name) {
+				Some(texture_name) => sprite.texture_name = texture_name.clone(),
+				None => continue,
+			}
+			if let Some(new_name) = hooks.run_sprite(name, &mut sprite) {
+				self.sprites.insert(new_name, sprite);
+			}
+		}
+	}
+
+	pub fn to_writer_with_hooks<W: io::Write + io::Seek>(
+		&self,
+		writer: &mut W,
+		options: &WriteOptions,
+		hooks: &mut Hooks,
+	) -> Result<WriteReport, SpriteError> {
+		if hooks.is_empty() {
+			return self.to_writer_with_options(writer, options);
+		}
+		let mut set = self.clone();
+		set.apply_hooks(hooks);
+		set.to_writer_with_options(writer, options)
+	}
+}
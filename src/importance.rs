@@ -0,0 +1,67 @@
+//! Where block compression quality matters most. Sprite edges are what players notice, so the
+//! blocks they run through can be encoded again at the best setting while the rest stays fast.
+use crate::*;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Importance {
+	/// Every block gets the same encoder
+	#[default]
+	Uniform,
+	/// Blocks along sprite outlines are encoded again with [`Encoder::Best`]
+	SpriteEdges,
+}
+
+impl SprSet {
+	/// Blocks (column and row, in the order `flip` stores them) of a `width`x`height` mip of
+	/// `texture` that a sprite outline runs through. Regions are scaled down to the mip.
+	pub(crate) fn sprite_edge_blocks(
+		&self,
+		texture: &str,
+		width: u32,
+		height: u32,
+		flip: FlipConvention,
+	) -> Vec<(u32, u32)> {
+		let Some(source) = self.textures.get(texture) else {
+			return vec![];
+		};
+		if width == 0 || height == 0 {
+			return vec![];
+		}
+		let scale_x = width as f32 / source.image.width().max(1) as f32;
+		let scale_y = height as f32 / source.image.height().max(1) as f32;
+		let last_column = (width - 1) / 4;
+		let last_row = (height - 1) / 4;
+		let mut blocks = BTreeSet::new();
+		for sprite in self.sprites.values() {
+			let region = &sprite.pixel_region;
+			if sprite.texture_name != texture || region.z <= 0.0 || region.w <= 0.0 {
+				continue;
+			}
+			let left = (region.x * scale_x).max(0.0).floor() as u32;
+			let right = ((region.x + region.z) * scale_x).ceil().max(1.0) as u32 - 1;
+			let top = (region.y * scale_y).max(0.0).floor() as u32;
+			let bottom = ((region.y + region.w) * scale_y).ceil().max(1.0) as u32 - 1;
+			if left >= width || top >= height {
+				continue;
+			}
+			let (right, bottom) = (right.min(width - 1), bottom.min(height - 1));
+			let (top, bottom) = if flip.is_bottom_up() {
+				(height - 1 - bottom, height - 1 - top)
+			} else {
+				(top, bottom)
+			};
+			let (x0, x1) = ((left / 4).min(last_column), (right / 4).min(last_column));
+			let (y0, y1) = ((top / 4).min(last_row), (bottom / 4).min(last_row));
+			for column in x0..=x1 {
+				blocks.insert((column, y0));
+				blocks.insert((column, y1));
+			}
+			for row in y0..=y1 {
+				blocks.insert((x0, row));
+				blocks.insert((x1, row));
+			}
+		}
+		blocks.into_iter().collect()
+	}
+}
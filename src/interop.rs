@@ -0,0 +1,298 @@
+use crate::*;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DumpRecord {
+	pub name: String,
+	pub texture: String,
+	pub x: f32,
+	pub y: f32,
+	pub width: f32,
+	pub height: f32,
+	pub screen_mode: ScreenMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpLayout {
+	Csv,
+	Ini,
+}
+
+/// Imports a folder written by community sprite tools: a coordinate file (CSV or INI) next to
+/// either one PNG per texture or one folder per texture holding a PNG per sprite.
+pub fn import_tool_dump(dir: &Path) -> Result<SprSet, SpriteError> {
+	import_tool_dump_with_rounding(dir, TexelRounding::default())
+}
+
+/// [`import_tool_dump`] working texel regions out with `rounding`.
+pub fn import_tool_dump_with_rounding(
+	dir: &Path,
+	rounding: TexelRounding,
+) -> Result<SprSet, SpriteError> {
+	let (path, layout) = find_coordinate_file(dir)?;
+	let text = std::fs::read_to_string(&path)?;
+	let records = match layout {
+		DumpLayout::Csv => parse_csv(&text)?,
+		DumpLayout::Ini => parse_ini(&text)?,
+	};
+	let name = dir
+		.file_name()
+		.map(|name| name.to_string_lossy().to_string())
+		.unwrap_or_default();
+	records_to_set(dir, name, &records, rounding)
+}
+
+fn find_coordinate_file(dir: &Path) -> Result<(std::path::PathBuf, DumpLayout), SpriteError> {
+	let mut entries = std::fs::read_dir(dir)?
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.path())
+		.filter(|path| path.is_file())
+		.collect::<Vec<_>>();
+	entries.sort();
+	for path in entries {
+		let extension = path
+			.extension()
+			.map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+		match extension.as_deref() {
+			Some("csv") => return Ok((path, DumpLayout::Csv)),
+			Some("ini") => return Ok((path, DumpLayout::Ini)),
+			_ => {}
+		}
+	}
+	Err(SpriteError::Import(format!(
+		"No coordinate file found in {}",
+		dir.to_string_lossy()
+	)))
+}
+
+fn parse_number(value: &str, field: &str, name: &str) -> Result<f32, SpriteError> {
+	value
+		.trim()
+		.parse()
+		.map_err(|_| SpriteError::Import(format!("Invalid {field} '{value}' for sprite {name}")))
+}
+
+/// CSV columns in the order they are read without a header, with the other names tools use for
+/// them.
+const CSV_COLUMNS: [&[&str]; 7] = [
+	&["name", "sprite"],
+	&["texture"],
+	&["x"],
+	&["y"],
+	&["width", "w"],
+	&["height", "h"],
+	&["screen_mode", "mode"],
+];
+
+/// Where each of [`CSV_COLUMNS`] is in a header row, `None` if `fields` isn't one. A header has to
+/// name at least the six columns every row needs.
+fn csv_header(fields: &[&str]) -> Option<[Option<usize>; 7]> {
+	let mut columns = [None; 7];
+	for (column, names) in columns.iter_mut().zip(CSV_COLUMNS) {
+		*column = fields
+			.iter()
+			.position(|field| names.iter().any(|name| field.eq_ignore_ascii_case(name)));
+	}
+	columns[..6].iter().all(Option::is_some).then_some(columns)
+}
+
+pub fn parse_csv(text: &str) -> Result<Vec<DumpRecord>, SpriteError> {
+	let mut records = vec![];
+	let mut columns = None;
+	let mut first = true;
+	for (i, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		let separator = if line.contains(';') { ';' } else { ',' };
+		let fields = line
+			.split(separator)
+			.map(|field| field.trim().trim_matches('"'))
+			.collect::<Vec<_>>();
+		if std::mem::take(&mut first) {
+			columns = csv_header(&fields);
+			if columns.is_some() {
+				continue;
+			}
+		}
+		let columns = columns.unwrap_or([0, 1, 2, 3, 4, 5, 6].map(Some));
+		let expected = columns[..6]
+			.iter()
+			.flatten()
+			.max()
+			.map_or(0, |last| last + 1);
+		if fields.len() < expected {
+			return Err(SpriteError::Import(format!(
+				"Line {} has {} columns, expected at least {expected}",
+				i + 1,
+				fields.len()
+			)));
+		}
+		let field = |column: usize| columns[column].and_then(|index| fields.get(index).copied());
+		let required = |column: usize| field(column).unwrap_or_default();
+		let name = required(0);
+		records.push(DumpRecord {
+			name: name.to_string(),
+			texture: required(1).to_string(),
+			x: parse_number(required(2), "x", name)?,
+			y: parse_number(required(3), "y", name)?,
+			width: parse_number(required(4), "width", name)?,
+			height: parse_number(required(5), "height", name)?,
+			screen_mode: match field(6) {
+				Some(mode) if !mode.is_empty() => mode.parse()?,
+				_ => ScreenMode::HDTV720,
+			},
+		});
+	}
+	Ok(records)
+}
+
+pub fn parse_ini(text: &str) -> Result<Vec<DumpRecord>, SpriteError> {
+	let mut sections: Vec<(String, HashMap<String, String>)> = vec![];
+	for line in text.lines() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+			continue;
+		}
+		if let Some(section) = line
+			.strip_prefix('[')
+			.and_then(|line| line.strip_suffix(']'))
+		{
+			sections.push((section.trim().to_string(), HashMap::new()));
+			continue;
+		}
+		let (key, value) = line
+			.split_once('=')
+			.ok_or_else(|| SpriteError::Import(format!("Invalid line '{line}'")))?;
+		let (_, values) = sections
+			.last_mut()
+			.ok_or_else(|| SpriteError::Import(format!("Key '{key}' outside of a section")))?;
+		values.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+	}
+
+	sections
+		.iter()
+		.map(|(name, values)| {
+			let get = |key: &str| {
+				values.get(key).ok_or_else(|| {
+					SpriteError::Import(format!("Sprite {name} is missing key '{key}'"))
+				})
+			};
+			Ok(DumpRecord {
+				name: name.clone(),
+				texture: get("texture")?.clone(),
+				x: parse_number(get("x")?, "x", name)?,
+				y: parse_number(get("y")?, "y", name)?,
+				width: parse_number(get("width")?, "width", name)?,
+				height: parse_number(get("height")?, "height", name)?,
+				screen_mode: match values.get("screen_mode") {
+					Some(mode) => mode.parse()?,
+					None => ScreenMode::HDTV720,
+				},
+			})
+		})
+		.collect()
+}
+
+pub fn records_to_set(
+	dir: &Path,
+	name: String,
+	records: &[DumpRecord],
+	rounding: TexelRounding,
+) -> Result<SprSet, SpriteError> {
+	let mut textures = IndexMap::new();
+	for record in records {
+		if textures.contains_key(&record.texture) {
+			continue;
+		}
+		let texture = load_dump_texture(dir, &record.texture, records)?;
+		if texture.width() == 0 || texture.height() == 0 {
+			return Err(SpriteError::Import(format!(
+				"Texture {} is {}x{}, sprites on it can't be placed",
+				record.texture,
+				texture.width(),
+				texture.height()
+			)));
+		}
+		textures.insert(record.texture.clone(), Texture::new(texture));
+	}
+
+	let sprites = records
+		.iter()
+		.map(|record| {
+			let texture = &textures[&record.texture];
+			let pixel_region = Vec4 {
+				x: record.x,
+				y: record.y,
+				z: record.width,
+				w: record.height,
+			};
+			let sprite = Sprite {
+				screen_mode: record.screen_mode,
+				texel_region: rounding.texel_region(
+					&pixel_region,
+					texture.image.width(),
+					texture.image.height(),
+				),
+				pixel_region,
+				texture_name: record.texture.clone(),
+				rotate: 0,
+			};
+			(record.name.clone(), sprite)
+		})
+		.collect();
+
+	Ok(SprSet {
+		name,
+		flags: 0,
+		textures,
+		sprites,
+		warnings: vec![],
+		usage: Default::default(),
+		original_names: Default::default(),
+		external_textures: HashMap::new(),
+		original_indices: Default::default(),
+		texture_order: None,
+		sprite_order: None,
+	})
+}
+
+fn load_dump_texture(
+	dir: &Path,
+	texture: &str,
+	records: &[DumpRecord],
+) -> Result<DynamicImage, SpriteError> {
+	let path = dir.join(format!("{texture}.png"));
+	if path.is_file() {
+		return Ok(image::open(path)?);
+	}
+
+	let sprite_dir = dir.join(texture);
+	if !sprite_dir.is_dir() {
+		return Err(SpriteError::Import(format!(
+			"Texture {texture} has neither an image nor a sprite folder"
+		)));
+	}
+	let records = records
+		.iter()
+		.filter(|record| record.texture == texture)
+		.collect::<Vec<_>>();
+	let width = records
+		.iter()
+		.map(|record| (record.x + record.width).ceil() as u32)
+		.max()
+		.unwrap_or(0);
+	let height = records
+		.iter()
+		.map(|record| (record.y + record.height).ceil() as u32)
+		.max()
+		.unwrap_or(0);
+	let mut canvas = image::RgbaImage::new(width, height);
+	for record in records {
+		let path = sprite_dir.join(format!("{}.png", record.name));
+		let sprite = image::open(&path)?.to_rgba8();
+		image::imageops::replace(&mut canvas, &sprite, record.x as i64, record.y as i64);
+	}
+	Ok(DynamicImage::ImageRgba8(canvas))
+}
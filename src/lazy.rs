@@ -0,0 +1,219 @@
+//! Sets opened with only their header, names and sprites read. Textures are read and decoded the
+//! first time they are asked for, so tools that only need sprite rectangles don't pay for
+//! decoding hundreds of megabytes of texture data.
+use crate::*;
+use std::io::Seek;
+use std::path::Path;
+
+#[derive(Debug)]
+pub struct LazySprSet {
+	pub name: String,
+	pub flags: u32,
+	/// Every sprite, with its texture named like in a fully read set
+	pub sprites: IndexMap<String, Sprite>,
+	pub warnings: Vec<Warning>,
+	reader: std::io::BufReader<std::fs::File>,
+	endian: Endian,
+	options: LoadOptions,
+	/// Names and file offsets in file order
+	textures: Vec<(String, u64)>,
+	external_textures: HashMap<String, u32>,
+	original_indices: OriginalIndices,
+	decoded: HashMap<String, Texture>,
+}
+
+impl SprSet {
+	pub fn open_lazy(path: impl AsRef<Path>) -> Result<LazySprSet, SpriteError> {
+		Self::open_lazy_with_options(path, None, &LoadOptions::default())
+	}
+
+	/// [`SprSet::open_lazy`] naming things like [`SprSet::from_reader_with_options`] does.
+	/// `keep_raw` and `normalize_names` are not supported and ignored.
+	pub fn open_lazy_with_options(
+		path: impl AsRef<Path>,
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+		options: &LoadOptions,
+	) -> Result<LazySprSet, SpriteError> {
+		let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+		let endian = match options.endianness {
+			Some(endian) => endian,
+			None => detect_endianness(&mut reader)?,
+		};
+		structure::check_structure_with_endian(&mut reader, endian)?;
+		reader.seek(SeekFrom::Start(0))?;
+		let header: SetHeader = reader.read_type(endian)?;
+		let offsets = header.texture_offsets(&mut reader, endian)?;
+
+		let set_name = spr_db_set.map_or_else(String::new, |set| set.name.clone());
+		let policy = &options.name_policy;
+		let mut warnings = vec![];
+		let mut textures = Vec::with_capacity(offsets.len());
+		for (i, offset) in offsets.into_iter().enumerate() {
+			let mut name = header
+				.tex_names
+				.get(i)
+				.ok_or_else(|| {
+					SpriteError::Malformed(format!("Texture {i} has no entry in the name table"))
+				})?
+				.to_string();
+			if name.is_empty() {
+				if let Some(spr_db_set) = spr_db_set {
+					name = match spr_db_set
+						.textures
+						.iter()
+						.find(|tex| tex.1.index as usize == i)
+					{
+						Some((_, entry)) => policy.strip_texture(&set_name, &entry.name),
+						None => missing_db_entry(&mut warnings, "texture", i),
+					};
+				}
+			}
+			textures.push((name, offset));
+		}
+
+		let mut sprites = IndexMap::with_capacity(header.sprites.len());
+		let mut original_indices = OriginalIndices::default();
+		let mut external_textures = HashMap::new();
+		for (i, spr) in header.sprites.iter().enumerate() {
+			let mut name = header
+				.sprite_names
+				.get(i)
+				.ok_or_else(|| {
+					SpriteError::Malformed(format!("Sprite {i} has no entry in the name table"))
+				})?
+				.to_string();
+			if name.is_empty() {
+				if let Some(spr_db_set) = spr_db_set {
+					name = match spr_db_set
+						.sprites
+						.iter()
+						.find(|sprite| sprite.1.index as usize == i)
+					{
+						Some((_, entry)) => policy.strip_sprite(&set_name, &entry.name),
+						None => missing_db_entry(&mut warnings, "sprite", i),
+					};
+				}
+			}
+			let texture_name = match textures.get(spr.texture_index as usize) {
+				Some((texture_name, _)) => texture_name.clone(),
+				None if textures.is_empty() && spr.texture_index >= 0 => {
+					let index = spr.texture_index as u32;
+					let texture_name = reference::external_texture_name(index);
+					external_textures.insert(texture_name.clone(), index);
+					texture_name
+				}
+				None => {
+					return Err(SpriteError::Malformed(format!(
+						"Sprite uses texture {}, the set has {}",
+						spr.texture_index,
+						textures.len()
+					))
+					.in_sprite(&name))
+				}
+			};
+			let screen_mode = header
+				.sprite_extras
+				.get(i)
+				.ok_or_else(|| {
+					SpriteError::Malformed("Sprite has no screen mode entry".to_string())
+						.in_sprite(&name)
+				})?
+				.1;
+			let sprite = Sprite {
+				screen_mode,
+				pixel_region: spr.pixel_region,
+				texel_region: spr.texel_region,
+				rotate: spr.rotate,
+				texture_name,
+			};
+			if sprites.insert(name.clone(), sprite).is_none() {
+				original_indices.sprites.insert(name, i as u32);
+			}
+		}
+
+		Ok(LazySprSet {
+			name: set_name,
+			flags: header.flags,
+			sprites,
+			warnings,
+			reader,
+			endian,
+			options: options.clone(),
+			textures,
+			external_textures,
+			original_indices,
+			decoded: HashMap::new(),
+		})
+	}
+}
+
+impl LazySprSet {
+	/// Texture names in file order.
+	pub fn texture_names(&self) -> impl Iterator<Item = &String> {
+		self.textures.iter().map(|(name, _)| name)
+	}
+
+	pub fn is_decoded(&self, name: &str) -> bool {
+		self.decoded.contains_key(name)
+	}
+
+	/// Reads and decodes `name` the first time, later calls return the same texture.
+	pub fn get_texture(&mut self, name: &str) -> Result<&Texture, SpriteError> {
+		if !self.decoded.contains_key(name) {
+			let (index, offset) = self
+				.textures
+				.iter()
+				.enumerate()
+				.find(|(_, (texture, _))| texture == name)
+				.map(|(index, (_, offset))| (index, *offset))
+				.ok_or_else(|| {
+					SpriteError::Validation(
+						ErrorCode::MissingTexture,
+						format!("Texture {name} is not in the set"),
+					)
+				})?;
+			let texture = self
+				.read_texture(offset)
+				.map_err(|err| err.in_texture(index, name).at_offset(offset))?;
+			self.decoded.insert(name.to_string(), texture);
+		}
+		Ok(&self.decoded[name])
+	}
+
+	fn read_texture(&mut self, offset: u64) -> Result<Texture, SpriteError> {
+		self.reader.seek(SeekFrom::Start(offset))?;
+		let mut tex: TexReader = self.reader.read_type(self.endian)?;
+		if self.endian == Endian::Big {
+			tex.swap_packed16();
+		}
+		read_texture(&tex, &self.options)
+	}
+
+	/// Decodes whatever wasn't yet and hands back a regular set.
+	pub fn into_set(mut self) -> Result<SprSet, SpriteError> {
+		let names = self.texture_names().cloned().collect::<Vec<_>>();
+		for name in &names {
+			self.get_texture(name)?;
+		}
+		let mut textures = IndexMap::with_capacity(names.len());
+		for (index, name) in names.iter().enumerate() {
+			if let Some(texture) = self.decoded.remove(name) {
+				textures.insert(name.clone(), texture);
+				self.original_indices
+					.textures
+					.insert(name.clone(), index as u32);
+			}
+		}
+		let mut set = SprSet {
+			flags: self.flags,
+			textures,
+			sprites: self.sprites,
+			warnings: self.warnings,
+			external_textures: self.external_textures,
+			original_indices: self.original_indices,
+			..SprSet::new(&self.name)
+		};
+		set.set_texture_order(names)?;
+		Ok(set)
+	}
+}
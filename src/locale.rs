@@ -0,0 +1,90 @@
+//! Sprites that come in per-language variants, like `LOGO_EN` and `LOGO_JP` sharing one texture.
+use crate::*;
+use std::collections::BTreeMap;
+
+/// The suffixes the games use.
+pub const LOCALES: [&str; 8] = ["JP", "EN", "CN", "TW", "KR", "FR", "DE", "ES"];
+
+/// Splits `LOGO_EN` into `("LOGO", "EN")` when the suffix is one of `locales`.
+pub fn split_locale<'a>(name: &'a str, locales: &[&str]) -> Option<(&'a str, &'a str)> {
+	let (base, locale) = name.rsplit_once('_')?;
+	if base.is_empty() || !locales.contains(&locale) {
+		return None;
+	}
+	Some((base, locale))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleGroup {
+	pub base: String,
+	/// Locale to sprite name
+	pub variants: BTreeMap<String, String>,
+	/// Whether a sprite named just `base` exists too
+	pub has_base: bool,
+}
+
+impl SprSet {
+	/// Every base name with at least one locale variant, sorted by base name.
+	pub fn locale_groups(&self, locales: &[&str]) -> Vec<LocaleGroup> {
+		let mut groups: BTreeMap<&str, BTreeMap<String, String>> = BTreeMap::new();
+		for name in self.sprites.keys() {
+			if let Some((base, locale)) = split_locale(name, locales) {
+				groups
+					.entry(base)
+					.or_default()
+					.insert(locale.to_string(), name.clone());
+			}
+		}
+		groups
+			.into_iter()
+			.map(|(base, variants)| LocaleGroup {
+				base: base.to_string(),
+				variants,
+				has_base: self.sprites.contains_key(base),
+			})
+			.collect()
+	}
+
+	/// Base names missing some of `locales`, with the locales they lack.
+	pub fn missing_locales(&self, locales: &[&str]) -> Vec<(String, Vec<String>)> {
+		self.locale_groups(locales)
+			.into_iter()
+			.filter_map(|group| {
+				let missing = locales
+					.iter()
+					.filter(|locale| !group.variants.contains_key(**locale))
+					.map(|locale| locale.to_string())
+					.collect::<Vec<_>>();
+				(!missing.is_empty()).then_some((group.base, missing))
+			})
+			.collect()
+	}
+
+	/// Fills every missing variant with a copy of the unsuffixed base sprite, or of the
+	/// `fallback` locale's variant when there is no base sprite. The copies share the original's
+	/// texture and region, ready for the translated art to be dropped in. Returns the sprites
+	/// created.
+	pub fn fill_missing_locales(&mut self, locales: &[&str], fallback: &str) -> Vec<String> {
+		let mut created = vec![];
+		for group in self.locale_groups(locales) {
+			let source = if group.has_base {
+				Some(group.base.clone())
+			} else {
+				group.variants.get(fallback).cloned()
+			};
+			let source = match source.and_then(|source| self.sprites.get(&source)) {
+				Some(source) => source.clone(),
+				None => continue,
+			};
+			for locale in locales {
+				if group.variants.contains_key(*locale) {
+					continue;
+				}
+				let name = format!("{}_{locale}", group.base);
+				self.sprites.insert(name.clone(), source.clone());
+				created.push(name);
+			}
+		}
+		created
+	}
+}
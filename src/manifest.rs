@@ -0,0 +1,158 @@
+//! SHA-256 hashes of a set's file, textures and sprites, for mod hosts to publish next to a
+//! download and check it against later. Textures kept as stored bytes are hashed by those bytes,
+//! decoded ones by their pixels. Manifests of files always hash the stored bytes, so they only
+//! match other files: encoding is lossy, a set in memory doesn't hash like the file it becomes.
+use crate::*;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+	/// Hash of the whole file, when the manifest was made from one
+	pub file: Option<String>,
+	pub textures: BTreeMap<String, String>,
+	pub sprites: BTreeMap<String, String>,
+}
+
+/// What [`Manifest::verify`] found. Names are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestReport {
+	pub file_changed: bool,
+	pub changed: Vec<String>,
+	/// In the manifest but not the set
+	pub missing: Vec<String>,
+	/// In the set but not the manifest
+	pub added: Vec<String>,
+}
+
+impl ManifestReport {
+	pub fn is_ok(&self) -> bool {
+		!self.file_changed
+			&& self.changed.is_empty()
+			&& self.missing.is_empty()
+			&& self.added.is_empty()
+	}
+}
+
+fn texture_hash(texture: &Texture) -> String {
+	let mut hasher = Sha256::new();
+	match &texture.raw {
+		Some(raw) => {
+			hasher.update([raw.version]);
+			hasher.update(&raw.data);
+		}
+		None => {
+			for image in std::iter::once(&texture.image).chain(&texture.layers) {
+				hasher.update(image.width().to_le_bytes());
+				hasher.update(image.height().to_le_bytes());
+				hasher.update(image.to_rgba8().as_raw());
+			}
+		}
+	}
+	format!("{:x}", hasher.finalize())
+}
+
+fn sprite_hash(sprite: &Sprite) -> String {
+	let mut hasher = Sha256::new();
+	hasher.update(sprite.texture_name.as_bytes());
+	hasher.update([0]);
+	for region in [&sprite.pixel_region, &sprite.texel_region] {
+		for value in [region.x, region.y, region.z, region.w] {
+			hasher.update(value.to_le_bytes());
+		}
+	}
+	hasher.update(sprite.rotate.to_le_bytes());
+	hasher.update((sprite.screen_mode as u32).to_le_bytes());
+	format!("{:x}", hasher.finalize())
+}
+
+fn compare(
+	expected: &BTreeMap<String, String>,
+	actual: &BTreeMap<String, String>,
+	kind: &str,
+	report: &mut ManifestReport,
+) {
+	for (name, hash) in expected {
+		match actual.get(name) {
+			Some(actual) if actual != hash => report.changed.push(format!("{kind} {name}")),
+			Some(_) => {}
+			None => report.missing.push(format!("{kind} {name}")),
+		}
+	}
+	for name in actual.keys().filter(|name| !expected.contains_key(*name)) {
+		report.added.push(format!("{kind} {name}"));
+	}
+}
+
+/// Reads `data` keeping every texture as stored, which is how files are hashed.
+fn read_stored(data: &[u8]) -> Result<SprSet, SpriteError> {
+	let options = LoadOptions {
+		keep_raw: true,
+		..Default::default()
+	};
+	SprSet::from_reader_with_options(&mut Cursor::new(data), None, &mut Hooks::new(), &options)
+}
+
+impl SprSet {
+	/// Hashes of the set as it is. Textures read with [`LoadOptions::keep_raw`] are hashed by
+	/// their stored bytes like [`Manifest::from_file`] does, others by their pixels.
+	pub fn manifest(&self) -> Manifest {
+		Manifest {
+			file: None,
+			textures: self
+				.textures
+				.iter()
+				.map(|(name, texture)| (name.clone(), texture_hash(texture)))
+				.collect(),
+			sprites: self
+				.sprites
+				.iter()
+				.map(|(name, sprite)| (name.clone(), sprite_hash(sprite)))
+				.collect(),
+		}
+	}
+}
+
+impl Manifest {
+	/// Manifest of a set file, including the hash of its bytes.
+	pub fn from_file(data: &[u8]) -> Result<Self, SpriteError> {
+		let set = read_stored(data)?;
+		Ok(Self {
+			file: Some(format!("{:x}", Sha256::digest(data))),
+			..set.manifest()
+		})
+	}
+
+	pub fn to_json(&self) -> Result<String, SpriteError> {
+		serde_json::to_string_pretty(self).map_err(|err| SpriteError::Import(err.to_string()))
+	}
+
+	pub fn from_json(json: &str) -> Result<Self, SpriteError> {
+		serde_json::from_str(json).map_err(|err| SpriteError::Import(err.to_string()))
+	}
+
+	/// Compares every texture and sprite of `set` against the manifest. Against a manifest of a
+	/// file, `set` has to be read with [`LoadOptions::keep_raw`], see [`SprSet::manifest`].
+	pub fn verify(&self, set: &SprSet) -> ManifestReport {
+		let actual = set.manifest();
+		let mut report = ManifestReport::default();
+		compare(&self.textures, &actual.textures, "Texture", &mut report);
+		compare(&self.sprites, &actual.sprites, "Sprite", &mut report);
+		report.changed.sort();
+		report.missing.sort();
+		report.added.sort();
+		report
+	}
+
+	/// [`Manifest::verify`] for a file, also checking its bytes when the manifest has their hash.
+	/// A file that doesn't parse is an error rather than a report.
+	pub fn verify_file(&self, data: &[u8]) -> Result<ManifestReport, SpriteError> {
+		let set = read_stored(data)?;
+		let mut report = self.verify(&set);
+		report.file_changed = self
+			.file
+			.as_ref()
+			.is_some_and(|hash| *hash != format!("{:x}", Sha256::digest(data)));
+		Ok(report)
+	}
+}
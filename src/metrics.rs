@@ -0,0 +1,235 @@
+use crate::*;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Where the time of a read or write went.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+	/// Reading and checking the structure, zero for writes
+	pub parse: Duration,
+	/// Decode time of each texture for reads, encode time for writes, in file order
+	pub textures: Vec<(String, Duration)>,
+	pub total: Duration,
+}
+
+impl Timings {
+	pub fn texture_total(&self) -> Duration {
+		self.textures.iter().map(|(_, time)| *time).sum()
+	}
+
+	/// The `count` textures that took longest.
+	pub fn slowest_textures(&self, count: usize) -> Vec<(String, Duration)> {
+		let mut textures = self.textures.clone();
+		textures.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+		textures.truncate(count);
+		textures
+	}
+}
+
+pub(crate) fn time<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+	let start = Instant::now();
+	let out = f();
+	(out, start.elapsed())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureQuality {
+	pub name: String,
+	pub format: TextureFormat,
+	/// In dB over all four channels, infinite when nothing changed
+	pub psnr: f64,
+	/// Mean SSIM of the luma over 8x8 windows, 1.0 is identical
+	pub ssim: f64,
+}
+
+pub fn psnr(a: &image::RgbaImage, b: &image::RgbaImage) -> f64 {
+	if a.dimensions() != b.dimensions() || a.as_raw().is_empty() {
+		return 0.0;
+	}
+	let squared_error = a
+		.as_raw()
+		.iter()
+		.zip(b.as_raw())
+		.map(|(a, b)| (*a as f64 - *b as f64).powi(2))
+		.sum::<f64>();
+	let mse = squared_error / a.as_raw().len() as f64;
+	if mse == 0.0 {
+		return f64::INFINITY;
+	}
+	10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+fn luma(image: &image::RgbaImage) -> Vec<f64> {
+	image
+		.pixels()
+		.map(|pixel| {
+			let [r, g, b, a] = pixel.0.map(|value| value as f64);
+			// Premultiply so differences hidden under full transparency don't count
+			(0.299 * r + 0.587 * g + 0.114 * b) * a / 255.0
+		})
+		.collect()
+}
+
+pub fn ssim(a: &image::RgbaImage, b: &image::RgbaImage) -> f64 {
+	const WINDOW: u32 = 8;
+	const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+	const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+	if a.dimensions() != b.dimensions() {
+		return 0.0;
+	}
+	let (width, height) = a.dimensions();
+	let (luma_a, luma_b) = (luma(a), luma(b));
+
+	let mut total = 0.0;
+	let mut windows = 0;
+	for window_y in (0..height).step_by(WINDOW as usize) {
+		for window_x in (0..width).step_by(WINDOW as usize) {
+			let mut samples = vec![];
+			for y in window_y..(window_y + WINDOW).min(height) {
+				for x in window_x..(window_x + WINDOW).min(width) {
+					let i = (y * width + x) as usize;
+					samples.push((luma_a[i], luma_b[i]));
+				}
+			}
+			let n = samples.len() as f64;
+			let mean_a = samples.iter().map(|(a, _)| a).sum::<f64>() / n;
+			let mean_b = samples.iter().map(|(_, b)| b).sum::<f64>() / n;
+			let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+			for (a, b) in &samples {
+				var_a += (a - mean_a).powi(2);
+				var_b += (b - mean_b).powi(2);
+				covariance += (a - mean_a) * (b - mean_b);
+			}
+			let (var_a, var_b, covariance) = (var_a / n, var_b / n, covariance / n);
+			total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+				/ ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+			windows += 1;
+		}
+	}
+	if windows == 0 {
+		return 1.0;
+	}
+	total / windows as f64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpriteComparison {
+	OnlyInA,
+	OnlyInB,
+	/// The sprite exists on both sides but its texture is missing on at least one
+	MissingTexture,
+	SizeMismatch {
+		a: (u32, u32),
+		b: (u32, u32),
+	},
+	Compared {
+		/// Per channel absolute difference, opaque so it can be viewed directly
+		difference: image::RgbaImage,
+		/// Largest difference of any channel of any pixel
+		max_difference: u8,
+		/// Pixels with a channel differing by more than the threshold
+		differing_pixels: usize,
+		psnr: f64,
+	},
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VisualDiff {
+	pub threshold: u8,
+	pub sprites: BTreeMap<String, SpriteComparison>,
+}
+
+impl VisualDiff {
+	pub fn passed(&self, name: &str) -> bool {
+		matches!(
+			self.sprites.get(name),
+			Some(SpriteComparison::Compared {
+				differing_pixels: 0,
+				..
+			})
+		)
+	}
+
+	/// Sprites that are missing on one side, changed size or differ beyond the threshold.
+	pub fn failures(&self) -> Vec<&str> {
+		self.sprites
+			.keys()
+			.filter(|name| !self.passed(name))
+			.map(|name| name.as_str())
+			.collect()
+	}
+
+	pub fn is_pass(&self) -> bool {
+		self.failures().is_empty()
+	}
+}
+
+/// Crops every sprite out of both sets and compares them pixel by pixel. A sprite passes when no
+/// channel of any pixel differs by more than `threshold`.
+pub fn visual_diff(a: &SprSet, b: &SprSet, threshold: u8) -> VisualDiff {
+	let crop = |set: &SprSet, sprite: &Sprite| {
+		set.textures
+			.get(&sprite.texture_name)
+			.map(|texture| extract::crop_sprite(&texture.image, sprite).to_rgba8())
+	};
+	let mut sprites = BTreeMap::new();
+	for name in a.sprites.keys().chain(b.sprites.keys()) {
+		if sprites.contains_key(name) {
+			continue;
+		}
+		let comparison = match (a.sprites.get(name), b.sprites.get(name)) {
+			(Some(_), None) => SpriteComparison::OnlyInA,
+			(None, _) => SpriteComparison::OnlyInB,
+			(Some(sprite_a), Some(sprite_b)) => {
+				compare(crop(a, sprite_a), crop(b, sprite_b), threshold)
+			}
+		};
+		sprites.insert(name.clone(), comparison);
+	}
+	VisualDiff { threshold, sprites }
+}
+
+fn compare(
+	crop_a: Option<image::RgbaImage>,
+	crop_b: Option<image::RgbaImage>,
+	threshold: u8,
+) -> SpriteComparison {
+	match (crop_a, crop_b) {
+		(None, _) | (_, None) => SpriteComparison::MissingTexture,
+		(Some(crop_a), Some(crop_b)) if crop_a.dimensions() != crop_b.dimensions() => {
+			SpriteComparison::SizeMismatch {
+				a: crop_a.dimensions(),
+				b: crop_b.dimensions(),
+			}
+		}
+		(Some(crop_a), Some(crop_b)) => {
+			let mut difference = image::RgbaImage::new(crop_a.width(), crop_a.height());
+			let mut max_difference = 0;
+			let mut differing_pixels = 0;
+			for ((pixel_a, pixel_b), out) in crop_a
+				.pixels()
+				.zip(crop_b.pixels())
+				.zip(difference.pixels_mut())
+			{
+				let delta = [0, 1, 2, 3].map(|c| pixel_a.0[c].abs_diff(pixel_b.0[c]));
+				let max = delta.into_iter().max().unwrap_or(0);
+				max_difference = max_difference.max(max);
+				if max > threshold {
+					differing_pixels += 1;
+				}
+				out.0 = [
+					delta[0].max(delta[3]),
+					delta[1].max(delta[3]),
+					delta[2].max(delta[3]),
+					255,
+				];
+			}
+			SpriteComparison::Compared {
+				psnr: psnr(&crop_a, &crop_b),
+				difference,
+				max_difference,
+				differing_pixels,
+			}
+		}
+	}
+}
@@ -0,0 +1,288 @@
+//! Moving every sprite of one screen mode to another across a whole data folder, the bulk of the
+//! work in mods that take the game wide screen or to a higher resolution.
+use crate::scanner::{ScanFailure, SpriteIndex};
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+pub const MIGRATION_REPORT_FILE: &str = "migration_report.json";
+
+pub struct MigrateOptions<'a> {
+	/// Names sets are read with and whose indices are brought in line with the rewritten files
+	pub spr_db: Option<&'a mut diva_db::spr::SprDb>,
+	/// Scale textures by the ratio of the two resolutions, otherwise only the screen mode changes.
+	/// Textures shared with sprites of other modes are left alone.
+	pub rescale: bool,
+	pub resample: ResamplePreset,
+	/// Work everything out and write the report, but leave sets and spr_db untouched
+	pub dry_run: bool,
+	/// [`MIGRATION_REPORT_FILE`] under the root when not set
+	pub report_path: Option<PathBuf>,
+	/// Sets are scanned on its threads and read with its load options. Its write options apply
+	/// to every rewritten set on top of the formats its textures were read in, and keep the file
+	/// order by default so ids other files point at stay valid.
+	pub config: SprConfig,
+}
+
+impl Default for MigrateOptions<'_> {
+	fn default() -> Self {
+		Self {
+			spr_db: None,
+			rescale: false,
+			resample: ResamplePreset::default(),
+			dry_run: false,
+			report_path: None,
+			config: SprConfig {
+				write: WriteOptions {
+					original_order: true,
+					..Default::default()
+				},
+				..Default::default()
+			},
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MigratedSet {
+	pub set: String,
+	pub file: PathBuf,
+	pub farc_entry: Option<String>,
+	/// Sprites moved to the new mode, sorted
+	pub sprites: Vec<String>,
+	pub scaled_textures: Vec<String>,
+	/// Textures that weren't scaled because other sprites share them or they are kept raw
+	pub skipped_textures: Vec<String>,
+	/// spr_db entries whose index changed with the rewrite
+	pub reindexed_entries: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MigrationReport {
+	pub from: ScreenMode,
+	pub to: ScreenMode,
+	pub dry_run: bool,
+	pub sets: Vec<MigratedSet>,
+	/// Files the scan couldn't read and sets that failed to convert
+	pub failures: Vec<ScanFailure>,
+}
+
+/// Finds every set under `root` with sprites in `from`, moves those sprites to `to`, writes the
+/// sets back where they were found and updates `spr_db`. A set that fails is recorded in the
+/// report and the rest carry on. The report is also written as JSON.
+pub fn migrate_screen_mode(
+	root: &Path,
+	from: ScreenMode,
+	to: ScreenMode,
+	mut options: MigrateOptions,
+) -> Result<MigrationReport, SpriteError> {
+	let index = SpriteIndex::scan_with_config(root, &options.config)?;
+	let mut affected: BTreeMap<(PathBuf, Option<String>), BTreeSet<String>> = BTreeMap::new();
+	for location in index
+		.sprites
+		.iter()
+		.filter(|location| location.screen_mode == from)
+	{
+		affected
+			.entry((location.file.clone(), location.farc_entry.clone()))
+			.or_default()
+			.insert(location.sprite.clone());
+	}
+
+	let mut report = MigrationReport {
+		from,
+		to,
+		dry_run: options.dry_run,
+		sets: vec![],
+		failures: index.failures,
+	};
+	for ((file, farc_entry), sprites) in affected {
+		match migrate_file(
+			&file,
+			farc_entry.as_deref(),
+			&sprites,
+			from,
+			to,
+			&mut options,
+		) {
+			Ok(migrated) => report.sets.push(migrated),
+			Err(err) => report.failures.push(ScanFailure {
+				file,
+				farc_entry,
+				error: err.to_string(),
+			}),
+		}
+	}
+
+	let report_path = options
+		.report_path
+		.clone()
+		.unwrap_or_else(|| root.join(MIGRATION_REPORT_FILE));
+	let json = serde_json::to_string_pretty(&report)
+		.map_err(|err| SpriteError::Import(format!("Report could not be written: {err}")))?;
+	std::fs::write(report_path, json)?;
+	Ok(report)
+}
+
+fn migrate_file(
+	file: &Path,
+	farc_entry: Option<&str>,
+	sprites: &BTreeSet<String>,
+	from: ScreenMode,
+	to: ScreenMode,
+	options: &mut MigrateOptions,
+) -> Result<MigratedSet, SpriteError> {
+	let mut farc = farc_entry.map(|_| farc::Farc::read(file)).transpose()?;
+	let file_name = match farc_entry {
+		Some(entry) => entry.to_string(),
+		None => file
+			.file_name()
+			.map(|name| name.to_string_lossy().to_string())
+			.unwrap_or_default(),
+	};
+	let data = match (&farc, farc_entry) {
+		(Some(farc), Some(entry)) => farc
+			.get(entry)
+			.ok_or_else(|| {
+				SpriteError::Farc(format!(
+					"{} does not contain {entry}",
+					file.to_string_lossy()
+				))
+			})?
+			.data
+			.clone(),
+		_ => std::fs::read(file)?,
+	};
+	let spr_db_set = options
+		.spr_db
+		.as_deref()
+		.and_then(|spr_db| db::get_spr_db_set(&file_name, spr_db));
+	let mut set = options.config.read(&mut Cursor::new(data), spr_db_set)?;
+	let db_name = spr_db_set
+		.map(|spr_db_set| spr_db_set.name.clone())
+		.unwrap_or_else(|| project::set_name_from_filename(&file_name));
+
+	let mut migrated = MigratedSet {
+		set: db_name.clone(),
+		file: file.to_path_buf(),
+		farc_entry: farc_entry.map(str::to_string),
+		sprites: vec![],
+		scaled_textures: vec![],
+		skipped_textures: vec![],
+		reindexed_entries: 0,
+	};
+	let mut moved_textures = BTreeSet::new();
+	for (name, sprite) in set.sprites.iter_mut() {
+		if sprite.screen_mode == from && sprites.contains(name) {
+			sprite.screen_mode = to;
+			migrated.sprites.push(name.clone());
+			moved_textures.insert(sprite.texture_name.clone());
+		}
+	}
+	migrated.sprites.sort();
+
+	let scale = match (from.resolution(), to.resolution()) {
+		(Some(from), Some(to)) if options.rescale => {
+			Some((to.0 as f32 / from.0 as f32, to.1 as f32 / from.1 as f32))
+		}
+		_ => None,
+	};
+	if let Some((scale_x, scale_y)) = scale {
+		for name in moved_textures {
+			let shared = set
+				.sprites
+				.values()
+				.any(|sprite| sprite.texture_name == name && sprite.screen_mode != to);
+			let Some(texture) = set.textures.get(&name).filter(|_| !shared) else {
+				migrated.skipped_textures.push(name);
+				continue;
+			};
+			let width = ((texture.image.width() as f32 * scale_x).round() as u32).max(1);
+			let height = ((texture.image.height() as f32 * scale_y).round() as u32).max(1);
+			match set.resize_texture(&name, width, height, options.resample) {
+				Ok(_) => migrated.scaled_textures.push(name),
+				Err(_) => migrated.skipped_textures.push(name),
+			}
+		}
+	}
+	if options.dry_run {
+		return Ok(migrated);
+	}
+
+	let mut write_options = options.config.write.clone();
+	write_options.preserve_formats(&set);
+	let mut data = vec![];
+	set.to_writer_with_options(&mut Cursor::new(&mut data), &write_options)?;
+	match (&mut farc, farc_entry) {
+		(Some(farc), Some(entry)) => {
+			farc.insert(entry, data);
+			farc.write(file)?;
+		}
+		_ => std::fs::write(file, data)?,
+	}
+	if let Some(spr_db) = options.spr_db.as_deref_mut() {
+		migrated.reindexed_entries =
+			reindex_db(spr_db, &file_name, &db_name, &set, &write_options)?;
+	}
+	Ok(migrated)
+}
+
+/// Points the set's spr_db entries at the indices the writer gave them, returning how many moved.
+fn reindex_db(
+	spr_db: &mut diva_db::spr::SprDb,
+	file_name: &str,
+	db_name: &str,
+	set: &SprSet,
+	write_options: &WriteOptions,
+) -> Result<usize, SpriteError> {
+	let policy = db::NamePolicy::default();
+	let sprites = match &write_options.sprite_indexing {
+		Some(indexing) => set.assign_sprite_indices(indexing)?,
+		None => set
+			.written_sprite_order(write_options)
+			.into_iter()
+			.cloned()
+			.map(Some)
+			.collect(),
+	};
+	let sprites = sprites
+		.iter()
+		.enumerate()
+		.filter_map(|(index, name)| {
+			Some((
+				format!("{}{}", policy.sprite_prefix(db_name), name.as_ref()?),
+				index,
+			))
+		})
+		.collect::<HashMap<_, _>>();
+	let textures = set
+		.written_texture_order(write_options)
+		.into_iter()
+		.enumerate()
+		.map(|(index, name)| (format!("{}{name}", policy.texture_prefix(db_name)), index))
+		.collect::<HashMap<_, _>>();
+
+	let mut changed = 0;
+	for (_, db_set) in spr_db.sets.iter_mut() {
+		if db_set.filename != file_name {
+			continue;
+		}
+		for (_, entry) in db_set.sprites.iter_mut() {
+			if let Some(index) = sprites.get(&entry.name) {
+				if u32::from(entry.index) as usize != *index {
+					entry.index = *index as _;
+					changed += 1;
+				}
+			}
+		}
+		for (_, entry) in db_set.textures.iter_mut() {
+			if let Some(index) = textures.get(&entry.name) {
+				if u32::from(entry.index) as usize != *index {
+					entry.index = *index as _;
+					changed += 1;
+				}
+			}
+		}
+	}
+	Ok(changed)
+}
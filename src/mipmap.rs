@@ -0,0 +1,90 @@
+//! Mip chains for written textures. The games sample some sprites scaled down, where a texture
+//! with only its top level shimmers, so the writer can emit smaller levels below it.
+use crate::*;
+use image::imageops::FilterType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MipMode {
+	/// Only the top level of each layer, as the writer always did
+	#[default]
+	TopOnly,
+	/// As many levels as the texture was read with, rebuilt from the top level since only that is
+	/// kept in memory
+	Preserve,
+	/// Every level down to 1x1
+	Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MipFilter {
+	/// Averages each 2x2 block of the level above, weighted by alpha
+	#[default]
+	Box,
+	/// Lanczos from the top level, sharper but can ring around hard edges
+	Lanczos,
+}
+
+/// Number of levels in a full chain for a `width`x`height` texture.
+pub fn full_mip_count(width: u32, height: u32) -> u32 {
+	32 - width.max(height).max(1).leading_zeros()
+}
+
+impl MipMode {
+	/// Levels to write for `texture`.
+	pub fn levels(&self, texture: &Texture) -> u32 {
+		let full = full_mip_count(texture.image.width(), texture.image.height());
+		match self {
+			Self::TopOnly => 1,
+			Self::Preserve => texture.mip_count.clamp(1, full),
+			Self::Full => full,
+		}
+	}
+}
+
+fn box_halve(image: &image::RgbaImage) -> image::RgbaImage {
+	let (width, height) = image.dimensions();
+	let (out_width, out_height) = ((width / 2).max(1), (height / 2).max(1));
+	image::RgbaImage::from_fn(out_width, out_height, |x, y| {
+		let mut sum = [0u32; 4];
+		for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+			let pixel = image
+				.get_pixel((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1))
+				.0;
+			let alpha = pixel[3] as u32;
+			for channel in 0..3 {
+				sum[channel] += pixel[channel] as u32 * alpha;
+			}
+			sum[3] += alpha;
+		}
+		if sum[3] == 0 {
+			return image::Rgba([0, 0, 0, 0]);
+		}
+		image::Rgba([
+			((sum[0] + sum[3] / 2) / sum[3]) as u8,
+			((sum[1] + sum[3] / 2) / sum[3]) as u8,
+			((sum[2] + sum[3] / 2) / sum[3]) as u8,
+			((sum[3] + 2) / 4) as u8,
+		])
+	})
+}
+
+/// `levels` images starting with `image` itself, each half the size of the one before.
+pub fn generate_mips(image: &DynamicImage, levels: u32, filter: MipFilter) -> Vec<DynamicImage> {
+	let mut mips = vec![image.clone()];
+	let mut previous = image.to_rgba8();
+	for level in 1..levels {
+		let (width, height) = (
+			(image.width() >> level).max(1),
+			(image.height() >> level).max(1),
+		);
+		let mip = match filter {
+			MipFilter::Box => box_halve(&previous),
+			MipFilter::Lanczos => image
+				.resize_exact(width, height, FilterType::Lanczos3)
+				.to_rgba8(),
+		};
+		mips.push(DynamicImage::ImageRgba8(mip.clone()));
+		previous = mip;
+	}
+	mips
+}
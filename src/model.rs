@@ -0,0 +1,179 @@
+//! A tool-neutral description of a set as atlas pages and named regions, for exporters to
+//! TexturePacker, Unity, Godot, Aseprite and the like. Each exporter only needs to turn an
+//! [`AtlasModel`] into its own format, and importers build one to get back to a [`SprSet`].
+//! Anything only sets have lives in the string metadata maps.
+use crate::*;
+use std::collections::BTreeMap;
+
+/// Set metadata key for the set's flags
+pub const META_FLAGS: &str = "flags";
+/// Page metadata key for the texture format the page was stored in
+pub const META_FORMAT: &str = "format";
+/// Region metadata key for the sprite's screen mode
+pub const META_SCREEN_MODE: &str = "screen_mode";
+/// Region metadata key for the sprite's rotate value
+pub const META_ROTATE: &str = "rotate";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasPage {
+	pub name: String,
+	pub image: DynamicImage,
+	pub metadata: BTreeMap<String, String>,
+}
+
+/// A named rectangle on a page, in top-down pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtlasRegion {
+	pub name: String,
+	pub page: String,
+	pub x: f32,
+	pub y: f32,
+	pub width: f32,
+	pub height: f32,
+	pub rotated: bool,
+	pub metadata: BTreeMap<String, String>,
+}
+
+/// Pages and regions in the order they are written.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AtlasModel {
+	pub name: String,
+	pub pages: Vec<AtlasPage>,
+	pub regions: Vec<AtlasRegion>,
+	pub metadata: BTreeMap<String, String>,
+}
+
+impl From<SprSet> for AtlasModel {
+	fn from(set: SprSet) -> Self {
+		let pages = set
+			.texture_order()
+			.into_iter()
+			.map(|name| {
+				let texture = &set.textures[name];
+				AtlasPage {
+					name: name.clone(),
+					image: texture.image.clone(),
+					metadata: BTreeMap::from([(
+						META_FORMAT.to_string(),
+						format!("{:?}", texture.original_format),
+					)]),
+				}
+			})
+			.collect();
+		let regions = set
+			.sprite_order()
+			.into_iter()
+			.map(|name| {
+				let sprite = &set.sprites[name];
+				AtlasRegion {
+					name: name.clone(),
+					page: sprite.texture_name.clone(),
+					x: sprite.pixel_region.x,
+					y: sprite.pixel_region.y,
+					width: sprite.pixel_region.z,
+					height: sprite.pixel_region.w,
+					rotated: sprite.rotate != 0,
+					metadata: BTreeMap::from([
+						(
+							META_SCREEN_MODE.to_string(),
+							format!("{:?}", sprite.screen_mode),
+						),
+						(META_ROTATE.to_string(), sprite.rotate.to_string()),
+					]),
+				}
+			})
+			.collect();
+		Self {
+			name: set.name.clone(),
+			pages,
+			regions,
+			metadata: BTreeMap::from([(META_FLAGS.to_string(), set.flags.to_string())]),
+		}
+	}
+}
+
+fn parse_meta<T: std::str::FromStr>(
+	metadata: &BTreeMap<String, String>,
+	key: &str,
+	owner: &str,
+) -> Result<Option<T>, SpriteError> {
+	let Some(value) = metadata.get(key) else {
+		return Ok(None);
+	};
+	value.parse().map(Some).map_err(|_| {
+		SpriteError::Import(format!("{owner} has {key} {value:?}, which is not valid"))
+	})
+}
+
+impl TryFrom<AtlasModel> for SprSet {
+	type Error = SpriteError;
+
+	/// Checks the same things [`SprSet::from_parts`] does. Missing metadata falls back to
+	/// RGBA8, HDTV720 and no flags.
+	fn try_from(model: AtlasModel) -> Result<Self, Self::Error> {
+		let mut textures = IndexMap::with_capacity(model.pages.len());
+		for page in &model.pages {
+			let owner = format!("Page {}", page.name);
+			let original_format = match page.metadata.get(META_FORMAT) {
+				Some(value) => *TextureFormat::ALL
+					.iter()
+					.find(|format| format!("{format:?}") == *value)
+					.ok_or_else(|| {
+						SpriteError::Import(format!(
+							"{owner} has {META_FORMAT} {value:?}, which is not valid"
+						))
+					})?,
+				None => TextureFormat::RGBA8,
+			};
+			let texture = Texture {
+				original_format,
+				..Texture::new(page.image.clone())
+			};
+			if textures.insert(page.name.clone(), texture).is_some() {
+				return Err(SpriteError::Validation(
+					ErrorCode::NameInUse,
+					format!("{owner} is listed twice"),
+				));
+			}
+		}
+
+		let mut sprites = IndexMap::with_capacity(model.regions.len());
+		for region in &model.regions {
+			let owner = format!("Region {}", region.name);
+			let screen_mode = parse_meta(&region.metadata, META_SCREEN_MODE, &owner)?;
+			let rotate = parse_meta(&region.metadata, META_ROTATE, &owner)?;
+			let pixel_region = Vec4 {
+				x: region.x,
+				y: region.y,
+				z: region.width,
+				w: region.height,
+			};
+			let sprite = Sprite {
+				rotate: rotate.unwrap_or(region.rotated as i32),
+				..Sprite::new(
+					&region.page,
+					pixel_region,
+					screen_mode.unwrap_or(ScreenMode::HDTV720),
+				)
+			};
+			if sprites.insert(region.name.clone(), sprite).is_some() {
+				return Err(SpriteError::Validation(
+					ErrorCode::NameInUse,
+					format!("{owner} is listed twice"),
+				));
+			}
+		}
+
+		let flags = parse_meta(&model.metadata, META_FLAGS, "Atlas")?.unwrap_or(0);
+		let mut set = Self::from_parts(&model.name, textures, sprites, flags)?;
+		set.set_texture_order(model.pages.into_iter().map(|page| page.name).collect())?;
+		set.set_sprite_order(
+			model
+				.regions
+				.into_iter()
+				.map(|region| region.name)
+				.collect(),
+		)?;
+		Ok(set)
+	}
+}
@@ -0,0 +1,69 @@
+//! Several sets stored back to back in one stream, as some containers do. Pointers in each set
+//! are relative to where that set starts.
+use crate::*;
+
+/// Offsets of the sets are kept 16 byte aligned when writing.
+const SET_ALIGNMENT: u64 = 16;
+
+/// Parses every set stored back to back in `reader`, along with the offset each starts at.
+/// Zero padding between sets is skipped.
+pub fn read_all<R: io::Read + io::Seek>(reader: &mut R) -> Result<Vec<(u64, SprSet)>, SpriteError> {
+	let mut data = vec![];
+	reader.read_to_end(&mut data)?;
+	let mut sets = vec![];
+	let mut pos = 0;
+	while pos < data.len() {
+		let (set, len) = read_one(&data[pos..])?;
+		sets.push((pos as u64, set));
+		pos += len;
+		// Step over padding, stopping at the first non zero word
+		while pos < data.len() && data[pos] == 0 && !is_set_start(&data[pos..]) {
+			pos += 1;
+		}
+	}
+	Ok(sets)
+}
+
+/// A header pointing at a sprite or texture table, rather than more padding.
+fn is_set_start(data: &[u8]) -> bool {
+	data.len() >= 0x20
+		&& data[4..0x20].iter().any(|byte| *byte != 0)
+		&& structure::check_structure(&mut Cursor::new(data)).is_ok()
+}
+
+fn read_one(data: &[u8]) -> Result<(SprSet, usize), SpriteError> {
+	let mut reader = Cursor::new(data);
+	let end = structure::structure_end(&mut reader)?;
+	let endian = detect_endianness(&mut reader)?;
+	let header = SprSetReader::read(&mut reader, endian)?;
+	let names_end = header
+		.tex_names
+		.iter()
+		.chain(header.sprite_names.iter())
+		.map(|name| name.ptr as u64 + name.len() as u64 + 1)
+		.max()
+		.unwrap_or(0);
+	reader.set_position(0);
+	let set = SprSet::from_reader(&mut reader, None)?;
+	Ok((set, end.max(names_end) as usize))
+}
+
+/// Writes `sets` back to back, each 16 byte aligned, returning the offset each starts at.
+pub fn write_all<W: io::Write + io::Seek>(
+	sets: &[SprSet],
+	writer: &mut W,
+	options: &WriteOptions,
+) -> Result<Vec<u64>, SpriteError> {
+	let mut offsets = Vec::with_capacity(sets.len());
+	let mut pos = 0u64;
+	for set in sets {
+		let padding = pos.next_multiple_of(SET_ALIGNMENT) - pos;
+		writer.write_all(&vec![0u8; padding as usize])?;
+		pos += padding;
+		offsets.push(pos);
+		let data = set.convert(FormatVariant::Classic, options)?;
+		writer.write_all(&data)?;
+		pos += data.len() as u64;
+	}
+	Ok(offsets)
+}
@@ -0,0 +1,124 @@
+use crate::*;
+
+/// Cheap handle for a sprite or texture name inside a [`NameTable`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Name(u32);
+
+impl Name {
+	pub fn index(self) -> usize {
+		self.0 as usize
+	}
+}
+
+/// Interns names so each one is stored once and compared by id. Ids are handed out in insertion
+/// order starting at 0.
+#[derive(Debug, Clone, Default)]
+pub struct NameTable {
+	names: Vec<String>,
+	lookup: HashMap<String, Name>,
+}
+
+impl NameTable {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn with_capacity(capacity: usize) -> Self {
+		Self {
+			names: Vec::with_capacity(capacity),
+			lookup: HashMap::with_capacity(capacity),
+		}
+	}
+
+	pub fn intern(&mut self, name: &str) -> Name {
+		if let Some(id) = self.lookup.get(name) {
+			return *id;
+		}
+		let id = Name(self.names.len() as u32);
+		self.names.push(name.to_string());
+		self.lookup.insert(name.to_string(), id);
+		id
+	}
+
+	pub fn get(&self, name: &str) -> Option<Name> {
+		self.lookup.get(name).copied()
+	}
+
+	pub fn resolve(&self, name: Name) -> &str {
+		&self.names[name.index()]
+	}
+
+	pub fn len(&self) -> usize {
+		self.names.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.names.is_empty()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (Name, &str)> {
+		self.names
+			.iter()
+			.enumerate()
+			.map(|(i, name)| (Name(i as u32), name.as_str()))
+	}
+}
+
+/// Clean up applied to names while reading, see [`LoadOptions::normalize_names`]. Names are
+/// trimmed, then the first matching prefix is stripped as long as something is left, then they
+/// are upper-cased.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NameNormalization {
+	pub upper_case: bool,
+	pub trim: bool,
+	pub strip_prefixes: Vec<String>,
+}
+
+impl NameNormalization {
+	pub fn is_noop(&self) -> bool {
+		!self.upper_case && !self.trim && self.strip_prefixes.is_empty()
+	}
+
+	pub fn apply(&self, name: &str) -> String {
+		let mut name = if self.trim { name.trim() } else { name };
+		if let Some(rest) = self
+			.strip_prefixes
+			.iter()
+			.find_map(|prefix| name.strip_prefix(prefix.as_str()))
+			.filter(|rest| !rest.is_empty())
+		{
+			name = rest;
+		}
+		if self.upper_case {
+			name.to_uppercase()
+		} else {
+			name.to_string()
+		}
+	}
+}
+
+/// Names as they were in the file, keyed by what normalization turned them into. Only names that
+/// changed are listed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OriginalNames {
+	pub textures: HashMap<String, String>,
+	pub sprites: HashMap<String, String>,
+}
+
+impl SprSet {
+	/// `name` as it was in the file before normalization.
+	pub fn original_texture_name<'a>(&'a self, name: &'a str) -> &'a str {
+		self.original_names
+			.textures
+			.get(name)
+			.map_or(name, String::as_str)
+	}
+
+	/// `name` as it was in the file before normalization.
+	pub fn original_sprite_name<'a>(&'a self, name: &'a str) -> &'a str {
+		self.original_names
+			.sprites
+			.get(name)
+			.map_or(name, String::as_str)
+	}
+}
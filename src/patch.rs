@@ -0,0 +1,132 @@
+//! Editing sprite coordinates straight in a file's bytes, leaving everything else byte for byte
+//! as it was. Small fixes don't need a full read and rewrite that re-encodes every texture.
+use crate::farc::Farc;
+use crate::*;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteEdit {
+	pub sprite: String,
+	/// New top-down pixel region, the texel region is worked out from it, see
+	/// [`patch_sprites_with_rounding`]
+	pub pixel_region: Option<Vec4>,
+	pub screen_mode: Option<ScreenMode>,
+}
+
+fn write_bytes(data: &mut [u8], pos: usize, bytes: &[u8]) -> Result<(), SpriteError> {
+	data.get_mut(pos..pos + bytes.len())
+		.ok_or_else(|| {
+			SpriteError::Malformed(format!(
+				"Sprite table at {pos:#x} runs past the end of the file"
+			))
+		})?
+		.copy_from_slice(bytes);
+	Ok(())
+}
+
+fn read_u32(data: &[u8], pos: usize, endian: Endian) -> Result<u32, SpriteError> {
+	let mut reader = Cursor::new(data);
+	reader.set_position(pos as u64);
+	Ok(reader.read_type(endian)?)
+}
+
+fn write_u32(data: &mut [u8], pos: usize, value: u32, endian: Endian) -> Result<(), SpriteError> {
+	match endian {
+		Endian::Big => write_bytes(data, pos, &value.to_be_bytes()),
+		_ => write_bytes(data, pos, &value.to_le_bytes()),
+	}
+}
+
+fn write_vec4(
+	data: &mut [u8],
+	pos: usize,
+	value: &Vec4,
+	endian: Endian,
+) -> Result<(), SpriteError> {
+	for (i, value) in [value.x, value.y, value.z, value.w].iter().enumerate() {
+		write_u32(data, pos + i * 4, value.to_bits(), endian)?;
+	}
+	Ok(())
+}
+
+/// Applies `edits` to the sprite table of the set in `data`. Returns how many sprites changed.
+pub fn patch_sprites(data: &mut [u8], edits: &[SpriteEdit]) -> Result<usize, SpriteError> {
+	patch_sprites_with_rounding(data, edits, TexelRounding::default())
+}
+
+/// [`patch_sprites`] working texel regions out with `rounding`.
+pub fn patch_sprites_with_rounding(
+	data: &mut [u8],
+	edits: &[SpriteEdit],
+	rounding: TexelRounding,
+) -> Result<usize, SpriteError> {
+	let (targets, endian) = {
+		let set = SprSetRef::parse(data)?;
+		let mut targets = Vec::with_capacity(edits.len());
+		for edit in edits {
+			let index = set
+				.sprites
+				.iter()
+				.position(|sprite| sprite.name == edit.sprite)
+				.ok_or_else(|| {
+					SpriteError::Import(format!("Sprite {} is not in the set", edit.sprite))
+				})?;
+			// Only the texel region needs the texture, so screen modes can be patched in sets that
+			// reference another set's textures
+			let size = match edit.pixel_region {
+				Some(_) => Some(
+					set.textures
+						.get(set.sprites[index].texture_index as usize)
+						.and_then(|texture| texture.mip(0, 0))
+						.map(|mip| (mip.width, mip.height))
+						.ok_or_else(|| {
+							SpriteError::Validation(
+								ErrorCode::MissingTexture,
+								format!(
+									"Sprite {} is on a texture the set has no mips for",
+									edit.sprite
+								),
+							)
+						})?,
+				),
+				None => None,
+			};
+			targets.push((index, size, edit));
+		}
+		(targets, set.endian)
+	};
+
+	let sprites_pos = read_u32(data, 16, endian)? as usize;
+	let sprite_extras_pos = read_u32(data, 28, endian)? as usize;
+	for (index, size, edit) in &targets {
+		if let (Some(pixel_region), Some((width, height))) = (&edit.pixel_region, size) {
+			let texel_region = rounding.texel_region(pixel_region, *width, *height);
+			let pos = sprites_pos + index * 40 + 8;
+			write_vec4(data, pos, &texel_region, endian)?;
+			write_vec4(data, pos + 16, pixel_region, endian)?;
+		}
+		if let Some(screen_mode) = edit.screen_mode {
+			let pos = sprite_extras_pos + index * 8 + 4;
+			write_u32(data, pos, screen_mode as u32, endian)?;
+		}
+	}
+	Ok(targets.len())
+}
+
+/// Patches the sprite table of the set stored as `entry` in the FARC at `farc_path`. Only that
+/// entry is recompressed, the others are written back as they were stored.
+pub fn patch_in_farc(
+	farc_path: impl AsRef<Path>,
+	entry: &str,
+	edits: &[SpriteEdit],
+) -> Result<usize, SpriteError> {
+	let farc_path = farc_path.as_ref();
+	let mut farc = Farc::read(farc_path)?;
+	let data = &mut farc
+		.get_mut(entry)
+		.ok_or_else(|| SpriteError::Farc(format!("FARC has no entry {entry}")))?
+		.data;
+	let changed = patch_sprites(data, edits)?;
+	farc.write(farc_path)?;
+	Ok(changed)
+}
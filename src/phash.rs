@@ -0,0 +1,154 @@
+//! Perceptual hashes of sprites, for finding where a screenshot crop came from.
+use crate::scanner::{SpriteIndex, SpriteLocation};
+use crate::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 64 bit difference hash: the image shrunk to 9x8 grey pixels, one bit per horizontal pair
+/// telling whether brightness goes up. Survives scaling, recompression and small colour shifts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ImageHash(pub u64);
+
+impl ImageHash {
+	pub fn of(image: &DynamicImage) -> Self {
+		if image.width() == 0 || image.height() == 0 {
+			return Self(0);
+		}
+		// Transparent pixels count as black so the sprite's outline is part of the hash
+		let rgba = image.to_rgba8();
+		let grey = image::GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+			let [r, g, b, a] = rgba.get_pixel(x, y).0.map(|value| value as u32);
+			image::Luma([((r * 299 + g * 587 + b * 114) / 1000 * a / 255) as u8])
+		});
+		let small = image::imageops::resize(&grey, 9, 8, image::imageops::FilterType::Triangle);
+		let mut hash = 0;
+		for y in 0..8 {
+			for x in 0..8 {
+				hash <<= 1;
+				if small.get_pixel(x + 1, y).0[0] > small.get_pixel(x, y).0[0] {
+					hash |= 1;
+				}
+			}
+		}
+		Self(hash)
+	}
+
+	/// Number of differing bits, 0 for near identical images and around 32 for unrelated ones.
+	pub fn distance(&self, other: &Self) -> u32 {
+		(self.0 ^ other.0).count_ones()
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageMatch {
+	pub sprite: String,
+	pub distance: u32,
+}
+
+fn matches_sorted(mut matches: Vec<ImageMatch>) -> Vec<ImageMatch> {
+	matches.sort_by(|a, b| a.distance.cmp(&b.distance).then(a.sprite.cmp(&b.sprite)));
+	matches
+}
+
+impl SprSet {
+	pub fn sprite_hashes(&self) -> HashMap<String, ImageHash> {
+		self.sprites
+			.iter()
+			.filter_map(|(name, sprite)| {
+				let texture = self.textures.get(&sprite.texture_name)?;
+				let crop = extract::crop_sprite(&texture.image, sprite);
+				Some((name.clone(), ImageHash::of(&crop)))
+			})
+			.collect()
+	}
+
+	/// Sprites that look like `image` within `tolerance` differing hash bits, closest first.
+	/// Around 5 catches rescaled or recompressed crops without many false hits.
+	pub fn find_sprite_by_image(&self, image: &DynamicImage, tolerance: u32) -> Vec<ImageMatch> {
+		let hash = ImageHash::of(image);
+		matches_sorted(
+			self.sprite_hashes()
+				.into_iter()
+				.map(|(sprite, other)| ImageMatch {
+					sprite,
+					distance: hash.distance(&other),
+				})
+				.filter(|found| found.distance <= tolerance)
+				.collect(),
+		)
+	}
+}
+
+impl SpriteIndex {
+	/// [`SprSet::find_sprite_by_image`] over every indexed set. Each file is read again and only
+	/// the sprite regions are decoded; files that no longer read are skipped.
+	pub fn find_by_image(
+		&self,
+		image: &DynamicImage,
+		tolerance: u32,
+	) -> Vec<(&SpriteLocation, u32)> {
+		let hash = ImageHash::of(image);
+		let mut by_file: BTreeMap<(&PathBuf, &Option<String>), Vec<&SpriteLocation>> =
+			BTreeMap::new();
+		for location in &self.sprites {
+			by_file
+				.entry((&location.file, &location.farc_entry))
+				.or_default()
+				.push(location);
+		}
+
+		let mut found = vec![];
+		for ((file, farc_entry), locations) in by_file {
+			let data = match read_file(file, farc_entry.as_deref()) {
+				Some(data) => data,
+				None => continue,
+			};
+			let set = match SprSetRef::parse(&data) {
+				Ok(set) => set,
+				Err(_) => continue,
+			};
+			for location in locations {
+				let mip = set
+					.textures
+					.iter()
+					.find(|texture| texture.name == location.texture)
+					.and_then(|texture| texture.mip(0, 0));
+				let crop = mip.and_then(|mip| {
+					decode_region(
+						mip.data,
+						mip.format,
+						mip.width,
+						mip.height,
+						(
+							location.x.max(0.0) as u32,
+							location.y.max(0.0) as u32,
+							location.width.max(0.0) as u32,
+							location.height.max(0.0) as u32,
+						),
+						FlipConvention::Auto,
+					)
+				});
+				if let Some(crop) = crop {
+					let distance = hash.distance(&ImageHash::of(&DynamicImage::ImageRgba8(crop)));
+					if distance <= tolerance {
+						found.push((location, distance));
+					}
+				}
+			}
+		}
+		found.sort_by_key(|(_, distance)| *distance);
+		found
+	}
+}
+
+fn read_file(file: &Path, farc_entry: Option<&str>) -> Option<Vec<u8>> {
+	match farc_entry {
+		Some(entry) => farc::Farc::read(file)
+			.ok()?
+			.entries
+			.into_iter()
+			.find(|other| other.name == entry)
+			.map(|entry| entry.data),
+		None => std::fs::read(file).ok(),
+	}
+}
@@ -0,0 +1,88 @@
+//! Dry runs of bulk operations, so automation can show what would change and get it approved
+//! before anything is touched.
+use crate::diff::Change;
+use crate::*;
+use std::fmt;
+
+/// What an operation did, or would do on a dry run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan<T> {
+	/// What the operation returned, e.g. the [`AtlasLayout`] of a repack
+	pub result: T,
+	pub dry_run: bool,
+	pub diff: diff::SprSetDiff,
+	/// Stored size of all textures before and after with the given write options
+	pub bytes_before: u64,
+	pub bytes_after: u64,
+}
+
+impl<T> fmt::Display for Plan<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let changes = self
+			.diff
+			.textures
+			.iter()
+			.map(|(name, change)| ("Texture", name, change_kind(change)))
+			.chain(
+				self.diff
+					.sprites
+					.iter()
+					.map(|(name, change)| ("Sprite", name, change_kind(change))),
+			);
+		for (what, name, kind) in changes {
+			writeln!(f, "{what} {name} {kind}")?;
+		}
+		write!(
+			f,
+			"Textures {} -> {} bytes{}",
+			self.bytes_before,
+			self.bytes_after,
+			if self.dry_run { " (dry run)" } else { "" }
+		)
+	}
+}
+
+fn change_kind<T>(change: &Change<T>) -> &'static str {
+	match change {
+		Change::Added(_) => "added",
+		Change::Removed => "removed",
+		Change::Modified(_) => "modified",
+	}
+}
+
+fn plan<T>(
+	result: T,
+	dry_run: bool,
+	before: &SprSet,
+	after: &SprSet,
+	options: &WriteOptions,
+) -> Plan<T> {
+	Plan {
+		result,
+		dry_run,
+		diff: diff::SprSetDiff::new("plan", before, after),
+		bytes_before: before.texture_bytes(options),
+		bytes_after: after.texture_bytes(options),
+	}
+}
+
+impl SprSet {
+	/// Runs a bulk operation like [`SprSet::repack`], [`SprSet::resize_texture`] or
+	/// [`SprSet::recolor`] and reports what it changed. With `dry_run` it runs on a copy and the
+	/// set is left as it was.
+	pub fn run<T>(
+		&mut self,
+		dry_run: bool,
+		options: &WriteOptions,
+		operation: impl FnOnce(&mut SprSet) -> T,
+	) -> Plan<T> {
+		let mut copy = self.clone();
+		if dry_run {
+			let result = operation(&mut copy);
+			plan(result, dry_run, self, &copy, options)
+		} else {
+			let result = operation(self);
+			plan(result, dry_run, &copy, self, options)
+		}
+	}
+}
@@ -0,0 +1,572 @@
+use crate::farc::Farc;
+use crate::*;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbEntry {
+	pub name: String,
+	pub index: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProjectDb {
+	pub set_id: u32,
+	pub name: String,
+	pub filename: String,
+	pub sprites: BTreeMap<u32, DbEntry>,
+	pub textures: BTreeMap<u32, DbEntry>,
+	pub name_policy: NamePolicy,
+}
+
+impl ProjectDb {
+	pub fn from_spr_db_set(set_id: u32, spr_db_set: &diva_db::spr::SprDbSet) -> Self {
+		Self {
+			set_id,
+			name: spr_db_set.name.clone(),
+			filename: spr_db_set.filename.clone(),
+			sprites: spr_db_set
+				.sprites
+				.iter()
+				.map(|(id, entry)| {
+					(
+						u32::from(*id),
+						DbEntry {
+							name: entry.name.clone(),
+							index: u32::from(entry.index),
+						},
+					)
+				})
+				.collect(),
+			textures: spr_db_set
+				.textures
+				.iter()
+				.map(|(id, entry)| {
+					(
+						u32::from(*id),
+						DbEntry {
+							name: entry.name.clone(),
+							index: u32::from(entry.index),
+						},
+					)
+				})
+				.collect(),
+			name_policy: NamePolicy::default(),
+		}
+	}
+
+	pub fn sprite_prefix(&self) -> String {
+		self.name_policy.sprite_prefix(&self.name)
+	}
+
+	pub fn texture_prefix(&self) -> String {
+		self.name_policy.texture_prefix(&self.name)
+	}
+
+	/// Brings the db entries in line with the set: entries for removed sprites/textures are
+	/// dropped, new ones get fresh ids, and every index matches the order the writer uses.
+	pub fn sync(&mut self, set: &SprSet) {
+		self.sync_with(set, |db| Some(db.next_id()));
+	}
+
+	fn sync_with(
+		&mut self,
+		set: &SprSet,
+		mut next_id: impl FnMut(&Self) -> Option<u32>,
+	) -> Option<()> {
+		let mut sprites = set.sprites.keys().collect::<Vec<_>>();
+		sprites.sort();
+		let mut textures = set.textures.keys().collect::<Vec<_>>();
+		textures.sort();
+
+		let sprite_prefix = self.sprite_prefix();
+		let texture_prefix = self.texture_prefix();
+		let sprites = sprites
+			.iter()
+			.map(|name| format!("{sprite_prefix}{name}"))
+			.collect::<Vec<_>>();
+		let textures = textures
+			.iter()
+			.map(|name| format!("{texture_prefix}{name}"))
+			.collect::<Vec<_>>();
+
+		self.sprites
+			.retain(|_, entry| sprites.contains(&entry.name));
+		self.textures
+			.retain(|_, entry| textures.contains(&entry.name));
+		for (index, name) in sprites.iter().enumerate() {
+			match self.sprites.values_mut().find(|entry| &entry.name == name) {
+				Some(entry) => entry.index = index as u32,
+				None => {
+					let id = next_id(self)?;
+					self.sprites.insert(
+						id,
+						DbEntry {
+							name: name.clone(),
+							index: index as u32,
+						},
+					);
+				}
+			}
+		}
+		for (index, name) in textures.iter().enumerate() {
+			match self.textures.values_mut().find(|entry| &entry.name == name) {
+				Some(entry) => entry.index = index as u32,
+				None => {
+					let id = next_id(self)?;
+					self.textures.insert(
+						id,
+						DbEntry {
+							name: name.clone(),
+							index: index as u32,
+						},
+					);
+				}
+			}
+		}
+		Some(())
+	}
+
+	fn next_id(&self) -> u32 {
+		self.sprites
+			.keys()
+			.chain(self.textures.keys())
+			.max()
+			.map_or(0, |id| id + 1)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CollisionKind {
+	SpriteName,
+	TextureName,
+	Id,
+}
+
+/// A name or id this set shares with another set in the spr_db.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbCollision {
+	pub kind: CollisionKind,
+	pub name: String,
+	pub id: Option<u32>,
+	pub other_set: String,
+}
+
+impl std::fmt::Display for DbCollision {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.id {
+			Some(id) => write!(
+				f,
+				"{:?} {} ({id}) collides with set {}",
+				self.kind, self.name, self.other_set
+			),
+			None => write!(
+				f,
+				"{:?} {} collides with set {}",
+				self.kind, self.name, self.other_set
+			),
+		}
+	}
+}
+
+fn collisions_with(
+	spr_db: &diva_db::spr::SprDb,
+	own_set: &str,
+	own_id: Option<u32>,
+	sprites: &[(Option<u32>, String)],
+	textures: &[(Option<u32>, String)],
+) -> Vec<DbCollision> {
+	let mut collisions = vec![];
+	for (id, set) in spr_db.sets.iter() {
+		let id = u32::from(*id);
+		if set.name == own_set {
+			continue;
+		}
+		let mut collide = |kind, name: &str, id| {
+			collisions.push(DbCollision {
+				kind,
+				name: name.to_string(),
+				id,
+				other_set: set.name.clone(),
+			})
+		};
+		if own_id == Some(id) {
+			collide(CollisionKind::Id, own_set, Some(id));
+		}
+		let other_ids = set
+			.sprites
+			.keys()
+			.chain(set.textures.keys())
+			.map(|id| u32::from(*id))
+			.collect::<HashSet<_>>();
+		for (kind, own, other) in [
+			(CollisionKind::SpriteName, sprites, &set.sprites),
+			(CollisionKind::TextureName, textures, &set.textures),
+		] {
+			for (own_id, name) in own {
+				if other.values().any(|entry| &entry.name == name) {
+					collide(kind, name, None);
+				}
+				if let Some(own_id) = own_id {
+					if other_ids.contains(own_id) || *own_id == id {
+						collide(CollisionKind::Id, name, Some(*own_id));
+					}
+				}
+			}
+		}
+	}
+	collisions
+}
+
+impl ProjectDb {
+	/// Names and ids in this db that other sets in `spr_db` already use. Shipping with any of
+	/// these shadows unrelated sprites in game.
+	pub fn collisions(&self, spr_db: &diva_db::spr::SprDb) -> Vec<DbCollision> {
+		let entries = |map: &BTreeMap<u32, DbEntry>| {
+			map.iter()
+				.map(|(id, entry)| (Some(*id), entry.name.clone()))
+				.collect::<Vec<_>>()
+		};
+		collisions_with(
+			spr_db,
+			&self.name,
+			Some(self.set_id),
+			&entries(&self.sprites),
+			&entries(&self.textures),
+		)
+	}
+}
+
+impl SprSet {
+	/// Sprite and texture names this set would register that another set in `spr_db` already
+	/// has. Ids are only known once the set has db entries, see [`ProjectDb::collisions`].
+	pub fn db_collisions(&self, spr_db: &diva_db::spr::SprDb) -> Vec<DbCollision> {
+		let db = ProjectDb {
+			name: self.name.clone(),
+			..Default::default()
+		};
+		let sprite_prefix = db.sprite_prefix();
+		let texture_prefix = db.texture_prefix();
+		let sprites = self
+			.sprites
+			.keys()
+			.map(|name| (None, format!("{sprite_prefix}{name}")))
+			.collect::<Vec<_>>();
+		let textures = self
+			.textures
+			.keys()
+			.map(|name| (None, format!("{texture_prefix}{name}")))
+			.collect::<Vec<_>>();
+		let mut collisions = collisions_with(spr_db, &self.name, None, &sprites, &textures);
+		collisions.sort_by(|a, b| a.name.cmp(&b.name));
+		collisions
+	}
+
+	/// Writes the set only if none of its names collide with other sets in `spr_db`.
+	pub fn to_writer_checked<W: io::Write + io::Seek>(
+		&self,
+		writer: &mut W,
+		options: &WriteOptions,
+		spr_db: &diva_db::spr::SprDb,
+	) -> Result<WriteReport, SpriteError> {
+		check_collisions(self.db_collisions(spr_db))?;
+		self.to_writer_with_options(writer, options)
+	}
+}
+
+fn check_collisions(collisions: Vec<DbCollision>) -> Result<(), SpriteError> {
+	if collisions.is_empty() {
+		return Ok(());
+	}
+	Err(SpriteError::Validation(
+		ErrorCode::DbCollision,
+		collisions
+			.iter()
+			.map(|collision| collision.to_string())
+			.collect::<Vec<_>>()
+			.join("\n"),
+	))
+}
+
+pub fn set_name_from_filename(filename: &str) -> String {
+	let stem = filename.strip_suffix(".bin").unwrap_or(filename);
+	stem.to_ascii_uppercase()
+}
+
+pub fn filename_from_set_name(set_name: &str) -> String {
+	format!("{}.bin", set_name.to_ascii_lowercase())
+}
+
+fn used_ids(spr_db: &diva_db::spr::SprDb) -> HashSet<u32> {
+	let mut ids = HashSet::new();
+	for (id, set) in spr_db.sets.iter() {
+		ids.insert(u32::from(*id));
+		ids.extend(set.sprites.keys().map(|id| u32::from(*id)));
+		ids.extend(set.textures.keys().map(|id| u32::from(*id)));
+	}
+	ids
+}
+
+/// An spr set together with its spr_db entries and the FARC it is packaged in. Edits made through
+/// the project are applied to all three so they can't drift apart.
+#[derive(Debug, Clone)]
+pub struct SprProject {
+	pub path: PathBuf,
+	pub farc: Farc,
+	pub set: SprSet,
+	pub db: ProjectDb,
+	/// Used by [`SprProject::save`]
+	pub write_options: WriteOptions,
+}
+
+impl SprProject {
+	pub fn load(
+		path: impl AsRef<Path>,
+		spr_db: Option<&diva_db::spr::SprDb>,
+	) -> Result<Self, SpriteError> {
+		Self::load_with_config(path, spr_db, &SprConfig::default())
+	}
+
+	/// [`SprProject::load`] reading with `config`'s load options and saving with its write options.
+	pub fn load_with_config(
+		path: impl AsRef<Path>,
+		spr_db: Option<&diva_db::spr::SprDb>,
+		config: &SprConfig,
+	) -> Result<Self, SpriteError> {
+		let path = path.as_ref();
+		let farc = Farc::read(path)?;
+		let entry = farc
+			.entries
+			.iter()
+			.find(|entry| entry.name.ends_with(".bin"))
+			.ok_or_else(|| SpriteError::Farc("FARC contains no spr set".into()))?;
+		let spr_db_set = spr_db.and_then(|spr_db| {
+			spr_db
+				.sets
+				.iter()
+				.find(|(_, set)| set.filename == entry.name)
+		});
+
+		let mut reader = Cursor::new(&entry.data);
+		let mut set = config.read(&mut reader, spr_db_set.map(|(_, set)| set))?;
+		let db = match spr_db_set {
+			Some((id, spr_db_set)) => ProjectDb::from_spr_db_set(u32::from(*id), spr_db_set),
+			None => {
+				let mut db = ProjectDb {
+					name: set_name_from_filename(&entry.name),
+					filename: entry.name.clone(),
+					..Default::default()
+				};
+				db.sync(&set);
+				db
+			}
+		};
+		if set.name.is_empty() {
+			set.name = db.name.clone();
+		}
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			farc,
+			set,
+			db,
+			write_options: config.write.clone(),
+		})
+	}
+
+	pub fn save(&mut self) -> Result<(), SpriteError> {
+		self.db.sync(&self.set);
+		let mut data = vec![];
+		self.set
+			.to_writer_with_options(&mut Cursor::new(&mut data), &self.write_options)?;
+		self.farc.insert(&self.db.filename, data);
+		self.farc.write(&self.path)
+	}
+
+	/// Like [`SprProject::save`], but refuses to write when the project's names or ids collide
+	/// with other sets in `spr_db`.
+	pub fn save_checked(&mut self, spr_db: &diva_db::spr::SprDb) -> Result<(), SpriteError> {
+		self.db.sync(&self.set);
+		check_collisions(self.db.collisions(spr_db))?;
+		self.save()
+	}
+
+	pub fn add_sprite(&mut self, name: &str, sprite: Sprite) -> Result<(), SpriteError> {
+		if !self.set.textures.contains_key(&sprite.texture_name) {
+			return Err(SpriteError::missing_texture(name, &sprite.texture_name));
+		}
+		self.set.sprites.insert(name.to_string(), sprite);
+		self.db.sync(&self.set);
+		Ok(())
+	}
+
+	pub fn remove_sprite(&mut self, name: &str) -> Option<Sprite> {
+		let sprite = self.set.sprites.shift_remove(name)?;
+		self.db.sync(&self.set);
+		Some(sprite)
+	}
+
+	pub fn rename_sprite(&mut self, old: &str, new: &str) -> Result<(), SpriteError> {
+		if !self.set.rename_sprite_key(old, new) {
+			return Err(SpriteError::not_in_set("Sprite", old));
+		}
+		let prefix = self.db.sprite_prefix();
+		let old_name = format!("{prefix}{old}");
+		if let Some(entry) = self
+			.db
+			.sprites
+			.values_mut()
+			.find(|entry| entry.name == old_name)
+		{
+			entry.name = format!("{prefix}{new}");
+		}
+		self.db.sync(&self.set);
+		Ok(())
+	}
+
+	pub fn add_texture(&mut self, name: &str, texture: Texture) {
+		self.set.textures.insert(name.to_string(), texture);
+		self.db.sync(&self.set);
+	}
+
+	pub fn remove_texture(&mut self, name: &str) -> Result<Texture, SpriteError> {
+		if let Some((sprite, _)) = self
+			.set
+			.sprites
+			.iter()
+			.find(|(_, sprite)| sprite.texture_name == name)
+		{
+			return Err(SpriteError::Import(format!(
+				"Texture {name} is still used by sprite {sprite}"
+			)));
+		}
+		let texture = self
+			.set
+			.textures
+			.shift_remove(name)
+			.ok_or_else(|| SpriteError::not_in_set("Texture", name))?;
+		self.db.sync(&self.set);
+		Ok(texture)
+	}
+
+	pub fn rename_texture(&mut self, old: &str, new: &str) -> Result<(), SpriteError> {
+		if !self.set.rename_texture_key(old, new) {
+			return Err(SpriteError::not_in_set("Texture", old));
+		}
+		for sprite in self.set.sprites.values_mut() {
+			if sprite.texture_name == old {
+				sprite.texture_name = new.to_string();
+			}
+		}
+		let prefix = self.db.texture_prefix();
+		let old_name = format!("{prefix}{old}");
+		if let Some(entry) = self
+			.db
+			.textures
+			.values_mut()
+			.find(|entry| entry.name == old_name)
+		{
+			entry.name = format!("{prefix}{new}");
+		}
+		self.db.sync(&self.set);
+		Ok(())
+	}
+
+	/// Clones the project into a new set named `new_set_name`, giving the set and all of its
+	/// entries fresh ids from `new_id_range`. Ids and names already present in `spr_db` are skipped
+	/// or rejected so the copy doesn't shadow existing content.
+	pub fn rebase(
+		&self,
+		new_set_name: &str,
+		new_id_range: Range<u32>,
+		spr_db: Option<&diva_db::spr::SprDb>,
+	) -> Result<Self, SpriteError> {
+		let mut name = new_set_name.to_ascii_uppercase();
+		if !name.starts_with("SPR_") {
+			name.insert_str(0, "SPR_");
+		}
+		let filename = filename_from_set_name(&name);
+
+		let used = match spr_db {
+			Some(spr_db) => {
+				if let Some((_, set)) = spr_db
+					.sets
+					.iter()
+					.find(|(_, set)| set.name == name || set.filename == filename)
+				{
+					return Err(SpriteError::Import(format!(
+						"Set {} already exists in spr_db",
+						set.name
+					)));
+				}
+				used_ids(spr_db)
+			}
+			None => HashSet::new(),
+		};
+		let mut ids = new_id_range.filter(|id| !used.contains(id));
+		let mut next_id = || {
+			ids.next()
+				.ok_or_else(|| SpriteError::Import(format!("Id range exhausted rebasing {name}")))
+		};
+
+		let mut db = ProjectDb {
+			set_id: next_id()?,
+			name: name.clone(),
+			filename: filename.clone(),
+			name_policy: self.db.name_policy.clone(),
+			..Default::default()
+		};
+		let texture_prefix = db.texture_prefix();
+		let sprite_prefix = db.sprite_prefix();
+		let old_texture_prefix = self.db.texture_prefix();
+		let old_sprite_prefix = self.db.sprite_prefix();
+		for entry in self.db.textures.values() {
+			let short = entry
+				.name
+				.strip_prefix(&old_texture_prefix)
+				.unwrap_or(&entry.name);
+			db.textures.insert(
+				next_id()?,
+				DbEntry {
+					name: format!("{texture_prefix}{short}"),
+					index: entry.index,
+				},
+			);
+		}
+		for entry in self.db.sprites.values() {
+			let short = entry
+				.name
+				.strip_prefix(&old_sprite_prefix)
+				.unwrap_or(&entry.name);
+			db.sprites.insert(
+				next_id()?,
+				DbEntry {
+					name: format!("{sprite_prefix}{short}"),
+					index: entry.index,
+				},
+			);
+		}
+		let mut set = self.set.clone();
+		set.name = name.clone();
+		// Anything the old db didn't cover still needs an entry
+		db.sync_with(&set, |_| ids.next()).ok_or_else(|| {
+			SpriteError::Import(format!("Id range exhausted rebasing {}", set.name))
+		})?;
+
+		let mut farc = self.farc.clone();
+		farc.remove(&self.db.filename);
+		farc.insert(&filename, vec![]);
+		let path = self
+			.path
+			.with_file_name(format!("{}.farc", filename.trim_end_matches(".bin")));
+
+		Ok(Self {
+			path,
+			farc,
+			set,
+			db,
+			write_options: self.write_options.clone(),
+		})
+	}
+}
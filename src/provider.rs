@@ -0,0 +1,216 @@
+//! Where textures come from when a set doesn't carry them, see [`SprSet::external_textures`].
+//! Cropping, extraction and validation take a provider and only ask it for textures the set
+//! doesn't have itself.
+use crate::*;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+pub trait TextureProvider {
+	/// The texture called `name`, or at `index` in the provider's order when the sprite's set
+	/// knows it. Fails with [`ErrorCode::MissingTexture`] when there is no such texture.
+	fn texture(&self, name: &str, index: Option<u32>)
+		-> Result<Cow<'_, DynamicImage>, SpriteError>;
+
+	/// Width and height of the texture, for checks that don't need the pixels.
+	fn texture_size(&self, name: &str, index: Option<u32>) -> Result<(u32, u32), SpriteError> {
+		let texture = self.texture(name, index)?;
+		Ok((texture.width(), texture.height()))
+	}
+}
+
+fn missing_texture(name: &str, index: Option<u32>) -> SpriteError {
+	let message = match index {
+		Some(index) => format!("Texture {name} (index {index}) is not provided"),
+		None => format!("Texture {name} is not provided"),
+	};
+	SpriteError::Validation(ErrorCode::MissingTexture, message)
+}
+
+/// A set's own textures, indexed by where they were in the file the set was read from. Textures
+/// added since count from where [`WriteOptions::original_order`] would write them.
+impl TextureProvider for SprSet {
+	fn texture(
+		&self,
+		name: &str,
+		index: Option<u32>,
+	) -> Result<Cow<'_, DynamicImage>, SpriteError> {
+		let texture = match index {
+			Some(index) => {
+				let options = WriteOptions {
+					original_order: true,
+					..Default::default()
+				};
+				self.original_indices
+					.textures
+					.iter()
+					.find(|(name, original)| {
+						**original == index && self.textures.contains_key(*name)
+					})
+					.map(|(name, _)| name)
+					.or_else(|| {
+						self.written_texture_order(&options)
+							.get(index as usize)
+							.copied()
+					})
+					.and_then(|name| self.textures.get(name))
+			}
+			None => self.textures.get(name),
+		};
+		texture
+			.map(|texture| Cow::Borrowed(&texture.image))
+			.ok_or_else(|| missing_texture(name, index))
+	}
+}
+
+/// Textures read from a folder, either one written by [`SprSet::export_dir`], whose manifest
+/// gives names and indices, or loose `<name>.png` or `<name>.dds` files found by name.
+#[derive(Debug, Clone)]
+pub struct DirTextureProvider {
+	pub path: PathBuf,
+	manifest: Option<dir::DirManifest>,
+}
+
+impl DirTextureProvider {
+	pub fn new(path: &Path) -> Result<Self, SpriteError> {
+		let manifest_path = path.join(dir::MANIFEST_FILE);
+		let manifest = if manifest_path.is_file() {
+			let json = std::fs::read_to_string(&manifest_path)?;
+			Some(serde_json::from_str(&json).map_err(|err| {
+				SpriteError::Import(format!(
+					"{} is not a valid manifest: {err}",
+					manifest_path.to_string_lossy()
+				))
+			})?)
+		} else {
+			None
+		};
+		Ok(Self {
+			path: path.to_path_buf(),
+			manifest,
+		})
+	}
+
+	fn file(&self, name: &str, index: Option<u32>) -> Option<PathBuf> {
+		if let Some(manifest) = &self.manifest {
+			let entry = match index {
+				Some(index) => manifest.textures.get(index as usize),
+				None => manifest.textures.iter().find(|entry| entry.name == name),
+			};
+			return entry.map(|entry| self.path.join(&entry.file));
+		}
+		["png", "dds"]
+			.iter()
+			.map(|extension| self.path.join(format!("{name}.{extension}")))
+			.find(|path| path.is_file())
+	}
+}
+
+impl TextureProvider for DirTextureProvider {
+	fn texture(
+		&self,
+		name: &str,
+		index: Option<u32>,
+	) -> Result<Cow<'_, DynamicImage>, SpriteError> {
+		let path = self
+			.file(name, index)
+			.ok_or_else(|| missing_texture(name, index))?;
+		Ok(Cow::Owned(dir::read_image(&path)?))
+	}
+
+	fn texture_size(&self, name: &str, index: Option<u32>) -> Result<(u32, u32), SpriteError> {
+		if let Some(manifest) = &self.manifest {
+			let entry = match index {
+				Some(index) => manifest.textures.get(index as usize),
+				None => manifest.textures.iter().find(|entry| entry.name == name),
+			};
+			return entry
+				.map(|entry| (entry.width, entry.height))
+				.ok_or_else(|| missing_texture(name, index));
+		}
+		let texture = self.texture(name, index)?;
+		Ok((texture.width(), texture.height()))
+	}
+}
+
+/// Textures of one set in a [`Workspace`], usually the one a reference-only set points at.
+#[derive(Clone, Copy)]
+pub struct WorkspaceTextureProvider<'a> {
+	pub workspace: &'a Workspace,
+	pub set_id: u32,
+}
+
+impl<'a> WorkspaceTextureProvider<'a> {
+	pub fn new(workspace: &'a Workspace, set_id: u32) -> Self {
+		Self { workspace, set_id }
+	}
+}
+
+impl TextureProvider for WorkspaceTextureProvider<'_> {
+	fn texture(
+		&self,
+		name: &str,
+		index: Option<u32>,
+	) -> Result<Cow<'_, DynamicImage>, SpriteError> {
+		let set = self.workspace.sets.get(&self.set_id).ok_or_else(|| {
+			SpriteError::Validation(
+				ErrorCode::MissingTexture,
+				format!("Set {} is not loaded in the workspace", self.set_id),
+			)
+		})?;
+		set.texture(name, index)
+	}
+}
+
+impl SprSet {
+	/// The image `sprite` sits on, from the set itself or else from `provider`.
+	pub(crate) fn sprite_texture<'a>(
+		&'a self,
+		sprite: &Sprite,
+		provider: Option<&'a dyn TextureProvider>,
+	) -> Result<Cow<'a, DynamicImage>, SpriteError> {
+		if let Some(texture) = self.textures.get(&sprite.texture_name) {
+			return Ok(Cow::Borrowed(&texture.image));
+		}
+		let index = self.external_textures.get(&sprite.texture_name).copied();
+		match provider {
+			Some(provider) => provider.texture(&sprite.texture_name, index),
+			None => Err(missing_texture(&sprite.texture_name, index)),
+		}
+	}
+
+	/// Checks that every sprite on a texture the set doesn't have finds it in `provider` and lies
+	/// inside it, what [`SprSet::from_parts`] checks for the set's own textures.
+	pub fn validate_external(&self, provider: &dyn TextureProvider) -> Result<(), SpriteError> {
+		let mut sprites = self
+			.sprites
+			.iter()
+			.filter(|(_, sprite)| !self.textures.contains_key(&sprite.texture_name))
+			.collect::<Vec<_>>();
+		sprites.sort_by(|(a, _), (b, _)| a.cmp(b));
+		let mut sizes = HashMap::new();
+		for (name, sprite) in sprites {
+			let (width, height) = match sizes.get(&sprite.texture_name) {
+				Some(size) => *size,
+				None => {
+					let index = self.external_textures.get(&sprite.texture_name).copied();
+					let size = provider
+						.texture_size(&sprite.texture_name, index)
+						.map_err(|err| err.in_sprite(name))?;
+					sizes.insert(sprite.texture_name.clone(), size);
+					size
+				}
+			};
+			if !extract::region_inside(&sprite.pixel_region, width, height) {
+				let region = sprite.pixel_region;
+				return Err(SpriteError::Validation(
+					ErrorCode::RegionOutOfBounds,
+					format!(
+						"Sprite {name} covers {}x{} at {}, {}, outside its {width}x{height} texture {}",
+						region.z, region.w, region.x, region.y, sprite.texture_name
+					),
+				));
+			}
+		}
+		Ok(())
+	}
+}
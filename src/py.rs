@@ -8,8 +8,24 @@ impl From<SpriteError> for PyErr {
 			SpriteError::Io(io_err) => PyErr::new::<PyIOError, _>(io_err.to_string()),
 			SpriteError::BinRead(bin_err) => PyErr::new::<PyException, _>(format!("{}", bin_err)),
 			SpriteError::NulError(_) => PyErr::new::<PyException, _>("Null in middle of name"),
-			SpriteError::MissingData => PyErr::new::<PyException, _>("Failed to parse file"),
-			SpriteError::Dds(_) => PyErr::new::<PyException, _>("Failed to parse texture"),
+			SpriteError::Dds(msg) => PyErr::new::<PyException, _>(msg),
+			SpriteError::Image(img_err) => PyErr::new::<PyException, _>(img_err.to_string()),
+			SpriteError::Import(msg) => PyErr::new::<PyException, _>(msg),
+			SpriteError::Farc(msg) => PyErr::new::<PyException, _>(msg),
+			SpriteError::Validation(code, msg) => {
+				PyErr::new::<PyValueError, _>(format!("{code}: {msg}"))
+			}
+			SpriteError::Malformed(msg) => PyErr::new::<PyValueError, _>(msg),
+			SpriteError::Context(context, err) => {
+				let msg = format!("{context}: {err}");
+				match err.root() {
+					SpriteError::Io(_) => PyErr::new::<PyIOError, _>(msg),
+					SpriteError::Validation(..) | SpriteError::Malformed(_) => {
+						PyErr::new::<PyValueError, _>(msg)
+					}
+					_ => PyErr::new::<PyException, _>(msg),
+				}
+			}
 		}
 	}
 }
@@ -20,6 +36,11 @@ pub struct PyImage {
 	pub width: u32,
 	pub height: u32,
 	pub data: Vec<u8>,
+	/// What the texture was stored as when read, RGBA8 for new ones. Only stats use these, the
+	/// pixels in `data` are always RGBA.
+	pub original_format: TextureFormat,
+	pub mip_count: u32,
+	pub array_size: u32,
 }
 
 #[pyclass]
@@ -37,6 +58,8 @@ pub struct PySprite {
 	pub height: f32,
 	#[pyo3(get, set)]
 	pub screen_mode: ScreenMode,
+	#[pyo3(get, set)]
+	pub rotate: i32,
 }
 
 #[pyclass]
@@ -123,6 +146,65 @@ impl PySprSet {
 		Ok(())
 	}
 
+	/// `SprSet::set_screen_mode_where`, calling `predicate` with each sprite's name and sprite.
+	/// Nothing changes when it raises.
+	pub fn set_screen_mode_where(
+		&mut self,
+		predicate: &PyAny,
+		mode: ScreenMode,
+	) -> PyResult<usize> {
+		let mut set = sprite_only_set(self);
+		let mut error = None;
+		let changed = set.set_screen_mode_where(
+			|name, sprite| {
+				if error.is_some() {
+					return false;
+				}
+				let matches = predicate
+					.call1((name, PySprite::from_sprite(sprite)))
+					.and_then(|matches| matches.is_true());
+				matches.unwrap_or_else(|err| {
+					error = Some(err);
+					false
+				})
+			},
+			mode,
+		);
+		if let Some(err) = error {
+			return Err(err);
+		}
+		for (name, sprite) in &set.sprites {
+			if let Some(target) = self.sprites.get_mut(name) {
+				target.screen_mode = sprite.screen_mode;
+			}
+		}
+		Ok(changed)
+	}
+
+	/// `SprSet::screen_mode_histogram` keyed by mode name, the modes themselves don't hash.
+	pub fn screen_mode_histogram(&self) -> HashMap<String, usize> {
+		sprite_only_set(self)
+			.screen_mode_histogram()
+			.into_iter()
+			.map(|(mode, count)| (format!("{mode:?}"), count))
+			.collect()
+	}
+
+	/// `SprSet::stats` as a dict. Formats and stored sizes are those the textures were read in.
+	pub fn stats(&self, py: Python) -> PyResult<PyObject> {
+		let stats = py_set_to_set(self)?.stats();
+		let dict = pyo3::types::PyDict::new(py);
+		dict.set_item("texture_count", stats.texture_count)?;
+		dict.set_item("sprite_count", stats.sprite_count)?;
+		dict.set_item("formats", stats.formats)?;
+		dict.set_item("texture_sizes", stats.texture_sizes)?;
+		dict.set_item("screen_modes", stats.screen_modes)?;
+		dict.set_item("stored_bytes", stats.stored_bytes)?;
+		dict.set_item("decoded_bytes", stats.decoded_bytes)?;
+		dict.set_item("sprite_coverage", stats.sprite_coverage)?;
+		Ok(dict.into())
+	}
+
 	pub fn save_to_raw(&self) -> PyResult<Vec<u8>> {
 		let sprset = py_set_to_set(self)?;
 		let mut data = vec![];
@@ -139,6 +221,47 @@ impl PySprSet {
 	}
 }
 
+impl PySprite {
+	fn from_sprite(sprite: &Sprite) -> Self {
+		Self {
+			texture: sprite.texture_name.clone(),
+			x: sprite.pixel_region.x,
+			y: sprite.pixel_region.y,
+			width: sprite.pixel_region.z,
+			height: sprite.pixel_region.w,
+			screen_mode: sprite.screen_mode,
+			rotate: sprite.rotate,
+		}
+	}
+
+	fn to_sprite(&self) -> Sprite {
+		Sprite {
+			screen_mode: self.screen_mode,
+			texel_region: Vec4::default(),
+			rotate: self.rotate,
+			texture_name: self.texture.clone(),
+			pixel_region: Vec4 {
+				x: self.x,
+				y: self.y,
+				z: self.width,
+				w: self.height,
+			},
+		}
+	}
+}
+
+/// The sprites of `pyset` without its textures, for the set.rs helpers that only look at sprites.
+fn sprite_only_set(pyset: &PySprSet) -> SprSet {
+	SprSet {
+		sprites: pyset
+			.sprites
+			.iter()
+			.map(|(name, sprite)| (name.clone(), sprite.to_sprite()))
+			.collect(),
+		..SprSet::new(&pyset.name)
+	}
+}
+
 fn py_set_to_set(pyset: &PySprSet) -> PyResult<SprSet> {
 	Ok(SprSet {
 		name: pyset.name.clone(),
@@ -156,8 +279,13 @@ fn py_set_to_set(pyset: &PySprSet) -> PyResult<SprSet> {
 						return None;
 					}
 				};
-				let image = DynamicImage::ImageRgba8(buffer);
-				Some((name.clone(), image))
+				let texture = Texture {
+					original_format: texture.original_format,
+					mip_count: texture.mip_count,
+					array_size: texture.array_size,
+					..Texture::new(DynamicImage::ImageRgba8(buffer))
+				};
+				Some((name.clone(), texture))
 			})
 			.collect::<Option<_>>()
 			.ok_or(PyErr::new::<PyException, _>("Failed to create textures"))?,
@@ -165,28 +293,25 @@ fn py_set_to_set(pyset: &PySprSet) -> PyResult<SprSet> {
 			.sprites
 			.iter()
 			.map(|(name, sprite)| {
-				(
-					name.clone(),
-					Sprite {
-						screen_mode: sprite.screen_mode,
-						texel_region: Vec4 {
-							x: 0.0,
-							y: 0.0,
-							z: 0.0,
-							w: 0.0,
-						},
-						rotate: 0,
-						texture_name: sprite.texture.clone(),
-						pixel_region: Vec4 {
-							x: sprite.x,
-							y: sprite.y,
-							z: sprite.width,
-							w: sprite.height,
-						},
-					},
-				)
+				let mut out = sprite.to_sprite();
+				// The Python side only edits pixels, so the UVs always follow them
+				if let Some(texture) = pyset.textures.get(&sprite.texture) {
+					out.recompute_texel_region(
+						texture.width,
+						texture.height,
+						TexelRounding::default(),
+					);
+				}
+				(name.clone(), out)
 			})
 			.collect(),
+		warnings: vec![],
+		usage: Default::default(),
+		original_names: Default::default(),
+		external_textures: HashMap::new(),
+		original_indices: Default::default(),
+		texture_order: None,
+		sprite_order: None,
 	})
 }
 
@@ -200,9 +325,12 @@ fn set_to_py_set(sprset: SprSet) -> PySprSet {
 				(
 					name.clone(),
 					PyImage {
-						width: texture.width(),
-						height: texture.height(),
-						data: texture.as_bytes().to_vec(),
+						width: texture.image.width(),
+						height: texture.image.height(),
+						data: texture.image.as_bytes().to_vec(),
+						original_format: texture.original_format,
+						mip_count: texture.mip_count,
+						array_size: texture.array_size,
 					},
 				)
 			})
@@ -210,19 +338,7 @@ fn set_to_py_set(sprset: SprSet) -> PySprSet {
 		sprites: sprset
 			.sprites
 			.iter()
-			.map(|(name, sprite)| {
-				(
-					name.clone(),
-					PySprite {
-						texture: sprite.texture_name.clone(),
-						x: sprite.pixel_region.x,
-						y: sprite.pixel_region.y,
-						width: sprite.pixel_region.z,
-						height: sprite.pixel_region.w,
-						screen_mode: sprite.screen_mode,
-					},
-				)
-			})
+			.map(|(name, sprite)| (name.clone(), PySprite::from_sprite(sprite)))
 			.collect(),
 	}
 }
@@ -236,8 +352,7 @@ fn read_from_raw(data: Vec<u8>) -> PyResult<PySprSet> {
 
 #[pyfunction]
 fn read_from_file(path: &str) -> PyResult<PySprSet> {
-	let sprset =
-		SprSet::read(path, None).ok_or(PyErr::new::<PyException, _>("Failed to read spr set"))?;
+	let sprset = SprSet::read(path, None)?;
 	Ok(set_to_py_set(sprset))
 }
 
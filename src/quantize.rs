@@ -0,0 +1,26 @@
+use color_quant::NeuQuant;
+
+/// Reduces `image` to at most `colors` colours in place. Alpha is left exactly as it was so
+/// cut-out edges don't get palettised into halos, only the colour is snapped to the palette.
+pub fn quantize(image: &mut image::RgbaImage, colors: usize) {
+	let colors = colors.clamp(2, 256);
+	// Fully transparent pixels have no visible colour, keep them out of the palette
+	let samples = image
+		.pixels()
+		.filter(|pixel| pixel.0[3] != 0)
+		.flat_map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2], 255])
+		.collect::<Vec<_>>();
+	if samples.is_empty() {
+		return;
+	}
+	let quant = NeuQuant::new(10, colors, &samples);
+	let palette = quant.color_map_rgba();
+	for pixel in image.pixels_mut() {
+		if pixel.0[3] == 0 {
+			continue;
+		}
+		let [r, g, b, a] = pixel.0;
+		let index = quant.index_of(&[r, g, b, 255]) * 4;
+		pixel.0 = [palette[index], palette[index + 1], palette[index + 2], a];
+	}
+}
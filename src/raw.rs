@@ -0,0 +1,161 @@
+//! Sets read without decoding a single texture, for jobs that only touch sprites. Texture bytes
+//! are written back exactly as read, so block compressed data never goes through a lossy
+//! decode and encode. The rest of the file is laid out again by the writer.
+use crate::*;
+
+/// One mip of a [`RawTexture`], as stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawMip {
+	pub width: u32,
+	pub height: u32,
+	pub format: TextureFormat,
+	pub index: u8,
+	pub array_index: u8,
+	pub data: Vec<u8>,
+}
+
+impl RawTexture {
+	/// Every mip of every layer, first layer first. Fails for TXP versions other than 4 and 5.
+	pub fn mips(&self, endian: Endian) -> Result<Vec<RawMip>, SpriteError> {
+		let texture: TexReader = Cursor::new(&self.data).read_type(endian)?;
+		let layers = match &texture {
+			TexReader::Tex2d(texture) => &texture.mip_map_array,
+			TexReader::TexCubeMap(cubemap) => &cubemap.mip_map_array,
+			TexReader::Unknown(version) => {
+				return Err(SpriteError::Malformed(format!(
+					"Texture uses unknown TXP version {version}"
+				)))
+			}
+		};
+		Ok(layers
+			.iter()
+			.flat_map(|layer| &layer.mip_maps)
+			.map(|mip| RawMip {
+				width: mip.width as u32,
+				height: mip.height as u32,
+				format: mip.format,
+				index: mip.index,
+				array_index: mip.array_index,
+				data: mip.data.clone(),
+			})
+			.collect())
+	}
+}
+
+/// A set whose textures are the bytes they were stored as. Textures and sprites are in file
+/// order, which is also the order they are written in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawSprSet {
+	pub name: String,
+	pub flags: u32,
+	pub textures: Vec<(String, RawTexture)>,
+	pub sprites: Vec<(String, Sprite)>,
+	pub warnings: Vec<Warning>,
+	/// See [`SprSet::external_textures`]
+	pub external_textures: HashMap<String, u32>,
+	/// Byte order of the file, which the stored texture bytes are in
+	pub endian: Endian,
+}
+
+impl SprSet {
+	/// Reads a set keeping every texture as stored bytes.
+	pub fn read_raw<R: io::Read + io::Seek>(
+		reader: &mut R,
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+	) -> Result<RawSprSet, SpriteError> {
+		let endian = detect_endianness(reader)?;
+		let options = LoadOptions {
+			keep_raw: true,
+			endianness: Some(endian),
+			..Default::default()
+		};
+		let set = Self::from_reader_with_options(reader, spr_db_set, &mut Hooks::new(), &options)?;
+		RawSprSet::from_set(set, endian)
+	}
+}
+
+impl RawSprSet {
+	fn from_set(mut set: SprSet, endian: Endian) -> Result<Self, SpriteError> {
+		let texture_order = set.texture_order().into_iter().cloned().collect::<Vec<_>>();
+		let sprite_order = set.sprite_order().into_iter().cloned().collect::<Vec<_>>();
+		let textures = texture_order
+			.into_iter()
+			.map(|name| {
+				let raw = set
+					.textures
+					.shift_remove(&name)
+					.and_then(|texture| texture.raw)
+					.ok_or_else(|| {
+						SpriteError::Malformed(format!(
+							"Texture {name} was decoded instead of kept as stored bytes"
+						))
+					})?;
+				Ok((name, raw))
+			})
+			.collect::<Result<_, SpriteError>>()?;
+		let sprites = sprite_order
+			.into_iter()
+			.filter_map(|name| Some((name.clone(), set.sprites.shift_remove(&name)?)))
+			.collect();
+		Ok(Self {
+			name: set.name,
+			flags: set.flags,
+			textures,
+			sprites,
+			warnings: set.warnings,
+			external_textures: set.external_textures,
+			endian,
+		})
+	}
+
+	/// A regular set holding the raw textures, for the writer or anything else taking a
+	/// [`SprSet`]. The textures' images are placeholders.
+	pub fn into_set(self) -> SprSet {
+		let texture_order = self.textures.iter().map(|(name, _)| name.clone()).collect();
+		let sprite_order = self.sprites.iter().map(|(name, _)| name.clone()).collect();
+		SprSet {
+			flags: self.flags,
+			textures: self
+				.textures
+				.into_iter()
+				.map(|(name, raw)| {
+					let texture = Texture {
+						cubemap: raw.version == 5,
+						raw: Some(raw),
+						..Texture::placeholder(0, 0)
+					};
+					(name, texture)
+				})
+				.collect(),
+			sprites: self.sprites.into_iter().collect(),
+			warnings: self.warnings,
+			external_textures: self.external_textures,
+			texture_order: Some(texture_order),
+			sprite_order: Some(sprite_order),
+			..SprSet::new(&self.name)
+		}
+	}
+
+	/// Writes the set with every texture byte for byte as read. Headers, offsets and padding are
+	/// laid out again with `options`, so the file as a whole can differ from the one read. The
+	/// stored textures can't change byte order, writing with an `endianness` other than
+	/// [`RawSprSet::endian`] fails.
+	pub fn to_writer_with_options<W: io::Write + io::Seek>(
+		&self,
+		writer: &mut W,
+		options: &WriteOptions,
+	) -> Result<WriteReport, SpriteError> {
+		if !self.textures.is_empty() && options.endianness != self.endian {
+			return Err(SpriteError::Validation(
+				ErrorCode::UnsupportedVariant,
+				format!(
+					"Set {} has textures stored {:?} endian and can't be written {:?} endian",
+					self.name, self.endian, options.endianness
+				),
+			));
+		}
+		self.clone()
+			.into_set()
+			.to_writer_with_options(writer, options)
+	}
+}
@@ -0,0 +1,129 @@
+//! Edit scripts saved as JSON and replayed on another set, usually a newer version of the one
+//! they were recorded on. Names that no longer exist are matched to the closest one left, so a
+//! sprite renamed from `SEL_LOGO` to `SEL_LOGO_01` by a game update still gets its edits.
+use crate::*;
+
+/// Names further apart than this many single character edits are never matched.
+const MAX_DISTANCE: usize = 3;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recipe {
+	pub name: String,
+	pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecipeReport {
+	pub applied: usize,
+	/// Names from the recipe and what they were matched to
+	pub remapped: Vec<(String, String)>,
+	/// Commands that couldn't be applied, with why
+	pub skipped: Vec<(Command, String)>,
+}
+
+fn distance(a: &str, b: &str) -> usize {
+	let b = b.chars().collect::<Vec<_>>();
+	let mut row = (0..=b.len()).collect::<Vec<_>>();
+	for (i, a) in a.chars().enumerate() {
+		let mut previous = row[0];
+		row[0] = i + 1;
+		for (j, b) in b.iter().enumerate() {
+			let substitute = previous + (a != *b) as usize;
+			previous = row[j + 1];
+			row[j + 1] = substitute.min(row[j] + 1).min(previous + 1);
+		}
+	}
+	row[b.len()]
+}
+
+fn normalize(name: &str) -> String {
+	name.chars()
+		.filter(|c| c.is_ascii_alphanumeric())
+		.map(|c| c.to_ascii_uppercase())
+		.collect()
+}
+
+/// `name` itself when present, otherwise the one name in `names` that matches it ignoring case
+/// and punctuation, or failing that the closest by edit distance. Ties match nothing.
+fn match_name<'a>(name: &str, names: impl Iterator<Item = &'a String> + Clone) -> Option<String> {
+	if names.clone().any(|candidate| candidate == name) {
+		return Some(name.to_string());
+	}
+	let normalized = normalize(name);
+	let mut same = names
+		.clone()
+		.filter(|candidate| normalize(candidate) == normalized);
+	if let (Some(candidate), None) = (same.next(), same.next()) {
+		return Some(candidate.clone());
+	}
+	let mut scored = names
+		.map(|candidate| (distance(&normalized, &normalize(candidate)), candidate))
+		.filter(|(distance, _)| *distance <= MAX_DISTANCE)
+		.collect::<Vec<_>>();
+	scored.sort();
+	match scored.as_slice() {
+		[(best, candidate), (next, _), ..] if best < next => Some(candidate.to_string()),
+		[(_, candidate)] => Some(candidate.to_string()),
+		_ => None,
+	}
+}
+
+impl Recipe {
+	pub fn new(name: &str, commands: Vec<Command>) -> Self {
+		Self {
+			name: name.to_string(),
+			commands,
+		}
+	}
+
+	/// Everything applied in `history`, in order.
+	pub fn from_history(name: &str, history: &CommandHistory) -> Self {
+		Self::new(name, history.commands().cloned().collect())
+	}
+
+	pub fn to_json(&self) -> Result<String, SpriteError> {
+		serde_json::to_string_pretty(self).map_err(|err| SpriteError::Import(err.to_string()))
+	}
+
+	pub fn from_json(json: &str) -> Result<Self, SpriteError> {
+		serde_json::from_str(json).map_err(|err| SpriteError::Import(err.to_string()))
+	}
+
+	/// Applies every command it can to `set`, matching names the set doesn't have. Commands whose
+	/// names match nothing, or that fail, are skipped and listed in the report.
+	pub fn apply(&self, set: &mut SprSet) -> RecipeReport {
+		let mut report = RecipeReport::default();
+		for command in &self.commands {
+			let mut resolved = command.clone();
+			let (name, is_sprite) = match &mut resolved {
+				Command::ReplaceTexture { texture, .. } => (texture, false),
+				Command::RenameTexture { old, .. } => (old, false),
+				Command::MoveSprite { sprite, .. } | Command::SetScreenMode { sprite, .. } => {
+					(sprite, true)
+				}
+				Command::RenameSprite { old, .. } => (old, true),
+			};
+			let found = if is_sprite {
+				match_name(name, set.sprites.keys())
+			} else {
+				match_name(name, set.textures.keys())
+			};
+			let Some(found) = found else {
+				report.skipped.push((
+					command.clone(),
+					format!("Nothing in the set matches {name}"),
+				));
+				continue;
+			};
+			if found != *name {
+				report.remapped.push((name.clone(), found.clone()));
+				*name = found;
+			}
+			match resolved.apply(set) {
+				Ok(_) => report.applied += 1,
+				Err(err) => report.skipped.push((command.clone(), err.to_string())),
+			}
+		}
+		report
+	}
+}
@@ -0,0 +1,116 @@
+//! Swapping one palette for another, for colourway variants of UI packs.
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteMap {
+	/// Source colour to the colour it becomes
+	pub pairs: Vec<([u8; 3], [u8; 3])>,
+	/// RGB distance beyond which pixels are left alone
+	pub tolerance: f32,
+}
+
+impl PaletteMap {
+	pub fn new(pairs: Vec<([u8; 3], [u8; 3])>) -> Self {
+		Self {
+			pairs,
+			tolerance: 96.0,
+		}
+	}
+
+	/// Shifts `pixel` by the moves of the palette colours near it, weighted by closeness. A pixel
+	/// halfway between two palette colours (an antialiased edge) moves halfway between their
+	/// targets, and shading relative to a palette colour is kept. Alpha is never touched.
+	pub fn map(&self, pixel: [u8; 4]) -> [u8; 4] {
+		let rgb = [pixel[0], pixel[1], pixel[2]].map(|value| value as f32);
+		let mut weights = 0.0;
+		let mut shift = [0.0f32; 3];
+		for (source, target) in &self.pairs {
+			let distance = (0..3)
+				.map(|c| (rgb[c] - source[c] as f32).powi(2))
+				.sum::<f32>()
+				.sqrt();
+			if distance > self.tolerance {
+				continue;
+			}
+			if distance == 0.0 {
+				return [target[0], target[1], target[2], pixel[3]];
+			}
+			let weight = 1.0 / (distance * distance);
+			weights += weight;
+			for (c, shift) in shift.iter_mut().enumerate() {
+				*shift += weight * (target[c] as f32 - source[c] as f32);
+			}
+		}
+		if weights == 0.0 {
+			return pixel;
+		}
+		let [r, g, b] =
+			[0, 1, 2].map(|c| (rgb[c] + shift[c] / weights).round().clamp(0.0, 255.0) as u8);
+		[r, g, b, pixel[3]]
+	}
+
+	pub fn apply(&self, image: &mut image::RgbaImage) {
+		for pixel in image.pixels_mut() {
+			pixel.0 = self.map(pixel.0);
+		}
+	}
+}
+
+impl SprSet {
+	/// Recolours every texture, or with `sprites` only the regions of those sprites. Returns how
+	/// many textures changed.
+	pub fn recolor(&mut self, map: &PaletteMap, sprites: Option<&[&str]>) -> usize {
+		let mut regions: HashMap<String, Vec<Vec4>> = HashMap::new();
+		match sprites {
+			Some(names) => {
+				for sprite in names.iter().filter_map(|name| self.sprites.get(*name)) {
+					regions
+						.entry(sprite.texture_name.clone())
+						.or_default()
+						.push(sprite.pixel_region);
+				}
+			}
+			None => {
+				for (name, texture) in &self.textures {
+					regions.entry(name.clone()).or_default().push(Vec4 {
+						x: 0.0,
+						y: 0.0,
+						z: texture.image.width() as f32,
+						w: texture.image.height() as f32,
+					});
+				}
+			}
+		}
+
+		let mut changed = 0;
+		for (name, regions) in regions {
+			let texture = match self.textures.get_mut(&name) {
+				Some(texture) if texture.raw.is_none() => texture,
+				_ => continue,
+			};
+			let mut image = texture.image.to_rgba8();
+			let (width, height) = image.dimensions();
+			// Sprites may overlap, so remember what was already recoloured
+			let mut done = vec![false; (width * height) as usize];
+			for region in regions {
+				let (x0, y0) = (region.x.max(0.0) as u32, region.y.max(0.0) as u32);
+				let x1 = ((region.x + region.z).max(0.0) as u32).min(width);
+				let y1 = ((region.y + region.w).max(0.0) as u32).min(height);
+				for y in y0..y1 {
+					for x in x0..x1 {
+						let index = (y * width + x) as usize;
+						if done[index] {
+							continue;
+						}
+						done[index] = true;
+						let pixel = image.get_pixel_mut(x, y);
+						pixel.0 = map.map(pixel.0);
+					}
+				}
+			}
+			texture.image = DynamicImage::ImageRgba8(image);
+			changed += 1;
+		}
+		changed
+	}
+}
@@ -0,0 +1,84 @@
+//! Reference-only sets: sets with sprites but no textures of their own, whose sprites index into
+//! another set's textures. The textures are looked up when cropping instead of being read.
+use crate::*;
+
+/// Name given to texture `index` of the other set, the file stores nothing better.
+pub(crate) fn external_texture_name(index: u32) -> String {
+	format!("texture_{index}")
+}
+
+impl SprSet {
+	/// True for a set with sprites and no textures, whose sprites live on another set's textures.
+	pub fn is_reference_only(&self) -> bool {
+		self.textures.is_empty() && !self.sprites.is_empty()
+	}
+
+	/// Textures from another set the sprites use, by name, with their index in that set.
+	pub fn external_textures(&self) -> &HashMap<String, u32> {
+		&self.external_textures
+	}
+
+	/// Makes `name` stand for texture `index` of another set. Sprites on it are written with that
+	/// index and aren't checked against a texture. Only sets without textures can be written
+	/// with external ones.
+	pub fn add_external_texture(&mut self, name: &str, index: u32) {
+		self.external_textures.insert(name.to_string(), index);
+	}
+
+	/// Crops `sprite` from its texture, upright the way the game shows it, asking `lookup` with the texture's name and external
+	/// index when the set doesn't have it.
+	pub fn crop_sprite_with<'a>(
+		&'a self,
+		sprite: &str,
+		lookup: impl FnOnce(&str, Option<u32>) -> Option<&'a DynamicImage>,
+	) -> Result<DynamicImage, SpriteError> {
+		let sprite_data = self
+			.sprites
+			.get(sprite)
+			.ok_or_else(|| SpriteError::not_in_set("Sprite", sprite))?;
+		let texture = match self.textures.get(&sprite_data.texture_name) {
+			Some(texture) => Some(&texture.image),
+			None => lookup(
+				&sprite_data.texture_name,
+				self.external_textures
+					.get(&sprite_data.texture_name)
+					.copied(),
+			),
+		}
+		.ok_or_else(|| {
+			SpriteError::Validation(
+				ErrorCode::MissingTexture,
+				format!(
+					"Sprite {sprite} is on texture {} which could not be found",
+					sprite_data.texture_name
+				),
+			)
+		})?;
+		Ok(extract::crop_sprite_oriented(
+			texture,
+			sprite_data,
+			SubPixel::Floor,
+		))
+	}
+
+	/// [`SprSet::crop_sprite_with`] taking external textures from `source`. A set as the source
+	/// hands out textures by their index in the file it was read from.
+	pub fn crop_sprite_from(
+		&self,
+		sprite: &str,
+		source: &dyn TextureProvider,
+	) -> Result<DynamicImage, SpriteError> {
+		let sprite_data = self
+			.sprites
+			.get(sprite)
+			.ok_or_else(|| SpriteError::not_in_set("Sprite", sprite))?;
+		let texture = self
+			.sprite_texture(sprite_data, Some(source))
+			.map_err(|err| err.in_sprite(sprite))?;
+		Ok(extract::crop_sprite_oriented(
+			&texture,
+			sprite_data,
+			SubPixel::Floor,
+		))
+	}
+}
@@ -0,0 +1,249 @@
+//! Parse, rewrite and compare, for locking in format correctness from test suites and fuzzers.
+//! Only available with the `test-support` feature.
+use crate::*;
+use std::fmt;
+
+/// Block compressed textures get re-encoded, so their pixels only need to come out close.
+const MIN_COMPRESSED_PSNR: f64 = 40.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoundtripMismatch {
+	/// The rewritten bytes could not be parsed again
+	Unreadable(String),
+	MissingSprite(String),
+	ExtraSprite(String),
+	SpriteChanged {
+		name: String,
+		field: &'static str,
+	},
+	MissingTexture(String),
+	ExtraTexture(String),
+	TextureChanged {
+		name: String,
+		field: &'static str,
+	},
+	TexturePixels {
+		name: String,
+		psnr: f64,
+	},
+}
+
+impl fmt::Display for RoundtripMismatch {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Unreadable(error) => write!(f, "rewritten set can't be read: {error}"),
+			Self::MissingSprite(name) => write!(f, "sprite {name} was lost"),
+			Self::ExtraSprite(name) => write!(f, "sprite {name} appeared"),
+			Self::SpriteChanged { name, field } => write!(f, "sprite {name} changed its {field}"),
+			Self::MissingTexture(name) => write!(f, "texture {name} was lost"),
+			Self::ExtraTexture(name) => write!(f, "texture {name} appeared"),
+			Self::TextureChanged { name, field } => {
+				write!(f, "texture {name} changed its {field}")
+			}
+			Self::TexturePixels { name, psnr } => {
+				write!(f, "texture {name} pixels changed ({psnr:.1} dB)")
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundtripReport {
+	pub original_len: usize,
+	pub rewritten_len: usize,
+	/// First offset where the bytes differ, `None` when the rewrite is byte identical
+	pub first_difference: Option<usize>,
+	pub mismatches: Vec<RoundtripMismatch>,
+}
+
+impl RoundtripReport {
+	/// Whether the parsed contents survived, the bytes are allowed to move around.
+	pub fn is_faithful(&self) -> bool {
+		self.mismatches.is_empty()
+	}
+}
+
+impl fmt::Display for RoundtripReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{} bytes rewritten as {}",
+			self.original_len, self.rewritten_len
+		)?;
+		if let Some(offset) = self.first_difference {
+			write!(f, ", first differing at {offset:#x}")?;
+		}
+		for mismatch in &self.mismatches {
+			write!(f, "\n  {mismatch}")?;
+		}
+		Ok(())
+	}
+}
+
+/// Options that keep as much of `set` as the writer can: each texture's format, and placeholders
+/// so lenient loads can be rewritten too.
+pub fn fidelity_options(set: &SprSet) -> WriteOptions {
+	let mut options = WriteOptions {
+		allow_placeholders: true,
+		original_order: true,
+		..Default::default()
+	};
+	options.preserve_formats(set);
+	options
+}
+
+/// Parses `bytes`, writes the set back with [`fidelity_options`] and compares the two.
+pub fn roundtrip(bytes: &[u8]) -> Result<RoundtripReport, SpriteError> {
+	let original = SprSet::from_reader(&mut Cursor::new(bytes), None)?;
+	let mut rewritten = Cursor::new(vec![]);
+	original.to_writer_with_options(&mut rewritten, &fidelity_options(&original))?;
+	let rewritten = rewritten.into_inner();
+
+	let first_difference = bytes
+		.iter()
+		.zip(&rewritten)
+		.position(|(a, b)| a != b)
+		.or_else(|| (bytes.len() != rewritten.len()).then(|| bytes.len().min(rewritten.len())));
+	let mismatches = match SprSet::from_reader(&mut Cursor::new(&rewritten), None) {
+		Ok(reread) => compare_sets(&original, &reread),
+		Err(error) => vec![RoundtripMismatch::Unreadable(format!("{error:?}"))],
+	};
+	Ok(RoundtripReport {
+		original_len: bytes.len(),
+		rewritten_len: rewritten.len(),
+		first_difference,
+		mismatches,
+	})
+}
+
+/// Panics with the report when `bytes` don't survive a rewrite.
+pub fn assert_roundtrip(bytes: &[u8]) -> RoundtripReport {
+	let report = match roundtrip(bytes) {
+		Ok(report) => report,
+		Err(error) => panic!("set can't be read: {error:?}"),
+	};
+	assert!(report.is_faithful(), "round trip mismatch: {report}");
+	report
+}
+
+fn compare_sets(a: &SprSet, b: &SprSet) -> Vec<RoundtripMismatch> {
+	let mut mismatches = vec![];
+	let mut sprite_names = a.sprites.keys().chain(b.sprites.keys()).collect::<Vec<_>>();
+	sprite_names.sort();
+	sprite_names.dedup();
+	for name in sprite_names {
+		let (sprite_a, sprite_b) = match (a.sprites.get(name), b.sprites.get(name)) {
+			(Some(sprite_a), Some(sprite_b)) => (sprite_a, sprite_b),
+			(Some(_), None) => {
+				mismatches.push(RoundtripMismatch::MissingSprite(name.clone()));
+				continue;
+			}
+			_ => {
+				mismatches.push(RoundtripMismatch::ExtraSprite(name.clone()));
+				continue;
+			}
+		};
+		let changed = [
+			("screen mode", sprite_a.screen_mode != sprite_b.screen_mode),
+			(
+				"pixel region",
+				sprite_a.pixel_region != sprite_b.pixel_region,
+			),
+			(
+				"texel region",
+				sprite_a.texel_region != sprite_b.texel_region,
+			),
+			("texture", sprite_a.texture_name != sprite_b.texture_name),
+			("rotation", sprite_a.rotate != sprite_b.rotate),
+		];
+		for (field, _) in changed.into_iter().filter(|(_, changed)| *changed) {
+			mismatches.push(RoundtripMismatch::SpriteChanged {
+				name: name.clone(),
+				field,
+			});
+		}
+	}
+
+	let mut texture_names = a
+		.textures
+		.keys()
+		.chain(b.textures.keys())
+		.collect::<Vec<_>>();
+	texture_names.sort();
+	texture_names.dedup();
+	for name in texture_names {
+		let (texture_a, texture_b) = match (a.textures.get(name), b.textures.get(name)) {
+			(Some(texture_a), Some(texture_b)) => (texture_a, texture_b),
+			(Some(_), None) => {
+				mismatches.push(RoundtripMismatch::MissingTexture(name.clone()));
+				continue;
+			}
+			_ => {
+				mismatches.push(RoundtripMismatch::ExtraTexture(name.clone()));
+				continue;
+			}
+		};
+		let changed = [
+			(
+				"size",
+				texture_a.image.width() != texture_b.image.width()
+					|| texture_a.image.height() != texture_b.image.height(),
+			),
+			(
+				"format",
+				texture_a.original_format != texture_b.original_format,
+			),
+			("raw data", texture_a.raw != texture_b.raw),
+		];
+		let mut same_size = true;
+		for (field, _) in changed.into_iter().filter(|(_, changed)| *changed) {
+			same_size &= field != "size";
+			mismatches.push(RoundtripMismatch::TextureChanged {
+				name: name.clone(),
+				field,
+			});
+		}
+		if !same_size || texture_a.placeholder {
+			continue;
+		}
+		let psnr = metrics::psnr(&texture_a.image.to_rgba8(), &texture_b.image.to_rgba8());
+		let tolerated =
+			texture_a.original_format.is_block_compressed() && psnr >= MIN_COMPRESSED_PSNR;
+		if psnr.is_finite() && !tolerated {
+			mismatches.push(RoundtripMismatch::TexturePixels {
+				name: name.clone(),
+				psnr,
+			});
+		}
+	}
+	mismatches
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fixtures_round_trip() {
+		let report = assert_roundtrip(
+			&SprSetFixture::simple()
+				.to_bytes(&WriteOptions::default())
+				.unwrap(),
+		);
+		assert_eq!(report.first_difference, None);
+		assert_roundtrip(
+			&SprSetFixture::multi_texture()
+				.to_bytes(&WriteOptions::default())
+				.unwrap(),
+		);
+	}
+
+	#[test]
+	fn compressed_fixture_round_trips() {
+		let options = WriteOptions {
+			texture_format: TextureFormat::DXT5,
+			..Default::default()
+		};
+		assert_roundtrip(&SprSetFixture::simple().to_bytes(&options).unwrap());
+	}
+}
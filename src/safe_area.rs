@@ -0,0 +1,105 @@
+//! Checking sprites against the part of the screen guaranteed to be visible on a TV.
+use crate::*;
+
+/// Margins as fractions of the screen size on each side. The defaults are the usual 5% action
+/// safe margins.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SafeArea {
+	pub margin_x: f32,
+	pub margin_y: f32,
+}
+
+impl Default for SafeArea {
+	fn default() -> Self {
+		Self {
+			margin_x: 0.05,
+			margin_y: 0.05,
+		}
+	}
+}
+
+impl SafeArea {
+	/// 10% title safe margins, for text and anything that has to be read.
+	pub const TITLE: Self = Self {
+		margin_x: 0.1,
+		margin_y: 0.1,
+	};
+
+	/// The safe rectangle as x, y, width, height in pixels of `mode`.
+	pub fn rect(&self, mode: ScreenMode) -> Option<Vec4> {
+		let (width, height) = mode.resolution()?;
+		let (width, height) = (width as f32, height as f32);
+		Some(Vec4 {
+			x: width * self.margin_x,
+			y: height * self.margin_y,
+			z: width * (1.0 - 2.0 * self.margin_x),
+			w: height * (1.0 - 2.0 * self.margin_y),
+		})
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SafeAreaIssue {
+	/// The sprite's screen mode has no known resolution
+	UnknownResolution { sprite: String },
+	/// Bigger than the safe area, so it can't be placed inside it at all
+	TooLarge {
+		sprite: String,
+		size: (f32, f32),
+		safe: Vec4,
+	},
+	/// Placed (top left corner at `position`) so part of it falls outside the safe area
+	Outside {
+		sprite: String,
+		position: (f32, f32),
+		safe: Vec4,
+	},
+}
+
+impl SprSet {
+	/// Flags sprites that can't or don't fit the safe area of their screen mode. The file doesn't
+	/// say where sprites go on screen, so `placements` gives the top left corner of the ones whose
+	/// placement is known; the rest are only checked for size. Sorted by sprite name.
+	pub fn check_safe_area(
+		&self,
+		area: &SafeArea,
+		placements: &HashMap<String, (f32, f32)>,
+	) -> Vec<SafeAreaIssue> {
+		let mut sprites = self.sprites.iter().collect::<Vec<_>>();
+		sprites.sort_by(|(a, _), (b, _)| a.cmp(b));
+		let mut issues = vec![];
+		for (name, sprite) in sprites {
+			let safe = match area.rect(sprite.screen_mode) {
+				Some(safe) => safe,
+				None => {
+					issues.push(SafeAreaIssue::UnknownResolution {
+						sprite: name.clone(),
+					});
+					continue;
+				}
+			};
+			let size = (sprite.pixel_region.z, sprite.pixel_region.w);
+			if size.0 > safe.z || size.1 > safe.w {
+				issues.push(SafeAreaIssue::TooLarge {
+					sprite: name.clone(),
+					size,
+					safe,
+				});
+				continue;
+			}
+			if let Some(&(x, y)) = placements.get(name) {
+				if x < safe.x
+					|| y < safe.y || x + size.0 > safe.x + safe.z
+					|| y + size.1 > safe.y + safe.w
+				{
+					issues.push(SafeAreaIssue::Outside {
+						sprite: name.clone(),
+						position: (x, y),
+						safe,
+					});
+				}
+			}
+		}
+		issues
+	}
+}
@@ -0,0 +1,262 @@
+use crate::*;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpriteLocation {
+	pub sprite: String,
+	pub set: String,
+	pub file: PathBuf,
+	/// Entry name when the set lives inside a FARC
+	pub farc_entry: Option<String>,
+	pub texture: String,
+	pub x: f32,
+	pub y: f32,
+	pub width: f32,
+	pub height: f32,
+	pub screen_mode: ScreenMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TextureLocation {
+	pub texture: String,
+	pub set: String,
+	pub file: PathBuf,
+	pub farc_entry: Option<String>,
+	pub width: u32,
+	pub height: u32,
+	pub format: String,
+	pub mip_count: u32,
+	pub array_size: u32,
+	/// Size of every mip in every layer, which is what the texture takes up in VRAM
+	pub data_size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScanFailure {
+	pub file: PathBuf,
+	pub farc_entry: Option<String>,
+	pub error: String,
+}
+
+/// Every sprite found under a game data folder, read from the set metadata only.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SpriteIndex {
+	pub root: PathBuf,
+	pub sprites: Vec<SpriteLocation>,
+	pub textures: Vec<TextureLocation>,
+	pub failures: Vec<ScanFailure>,
+}
+
+fn is_spr_file(name: &str) -> bool {
+	let name = name.to_ascii_lowercase();
+	name.starts_with("spr_") && name.ends_with(".bin")
+}
+
+impl SpriteIndex {
+	/// Walks `dir` recursively, indexing loose `spr_*.bin` files and the ones packed in FARCs.
+	/// Files that fail to parse are recorded in `failures` instead of stopping the scan.
+	pub fn scan(dir: &Path) -> Result<Self, SpriteError> {
+		Self::scan_with_config(dir, &SprConfig::default())
+	}
+
+	/// [`SpriteIndex::scan`] parsing one file per rayon task on `config`'s threads. The index
+	/// comes out in the same order either way.
+	pub fn scan_with_config(dir: &Path, config: &SprConfig) -> Result<Self, SpriteError> {
+		let mut files = vec![];
+		let mut dirs = vec![dir.to_path_buf()];
+		while let Some(dir) = dirs.pop() {
+			let mut entries = std::fs::read_dir(&dir)?
+				.filter_map(|entry| entry.ok())
+				.map(|entry| entry.path())
+				.collect::<Vec<_>>();
+			entries.sort();
+			for path in entries {
+				if path.is_dir() {
+					dirs.push(path);
+					continue;
+				}
+				let file_name = path
+					.file_name()
+					.map(|name| name.to_string_lossy().to_string())
+					.unwrap_or_default();
+				if is_spr_file(&file_name) || file_name.to_ascii_lowercase().ends_with(".farc") {
+					files.push((path, file_name));
+				}
+			}
+		}
+
+		let parts = config.install(|| {
+			files
+				.par_iter()
+				.map(|(path, file_name)| {
+					let mut part = Self::default();
+					if !is_spr_file(file_name) {
+						part.add_farc(path);
+						return part;
+					}
+					match std::fs::read(path) {
+						Ok(data) => part.add_file(path, None, file_name, &data),
+						Err(err) => part.push_failure(path, None, err.into()),
+					}
+					part
+				})
+				.collect::<Vec<_>>()
+		})?;
+		let mut index = Self {
+			root: dir.to_path_buf(),
+			..Default::default()
+		};
+		for part in parts {
+			index.sprites.extend(part.sprites);
+			index.textures.extend(part.textures);
+			index.failures.extend(part.failures);
+		}
+		Ok(index)
+	}
+
+	fn add_farc(&mut self, path: &Path) {
+		let farc = match farc::Farc::read(path) {
+			Ok(farc) => farc,
+			Err(err) => return self.push_failure(path, None, err),
+		};
+		for entry in farc.entries.iter().filter(|entry| is_spr_file(&entry.name)) {
+			self.add_file(path, Some(&entry.name), &entry.name, &entry.data);
+		}
+	}
+
+	fn push_failure(&mut self, path: &Path, farc_entry: Option<&str>, err: SpriteError) {
+		self.failures.push(ScanFailure {
+			file: path.to_path_buf(),
+			farc_entry: farc_entry.map(str::to_string),
+			error: err.to_string(),
+		});
+	}
+
+	fn add_file(&mut self, path: &Path, farc_entry: Option<&str>, file_name: &str, data: &[u8]) {
+		let set_name = project::set_name_from_filename(file_name);
+		let set = match SprSetRef::parse(data) {
+			Ok(set) => set,
+			Err(err) => return self.push_failure(path, farc_entry, err),
+		};
+		self.textures.extend(set.textures.iter().map(|texture| {
+			let first = texture.mips.first();
+			TextureLocation {
+				texture: texture.name.to_string(),
+				set: set_name.clone(),
+				file: path.to_path_buf(),
+				farc_entry: farc_entry.map(str::to_string),
+				width: first.map(|mip| mip.width).unwrap_or(0),
+				height: first.map(|mip| mip.height).unwrap_or(0),
+				format: first
+					.map(|mip| format!("{:?}", mip.format))
+					.unwrap_or_default(),
+				mip_count: texture.mip_count,
+				array_size: texture.array_size,
+				data_size: texture.mips.iter().map(|mip| mip.data.len() as u64).sum(),
+			}
+		}));
+		self.sprites.extend(set.sprites.iter().map(|sprite| {
+			SpriteLocation {
+				sprite: sprite.name.to_string(),
+				set: set_name.clone(),
+				file: path.to_path_buf(),
+				farc_entry: farc_entry.map(str::to_string),
+				texture: set
+					.texture_of(sprite)
+					.map(|texture| texture.name.to_string())
+					.unwrap_or_default(),
+				x: sprite.pixel_region.x,
+				y: sprite.pixel_region.y,
+				width: sprite.pixel_region.z,
+				height: sprite.pixel_region.w,
+				screen_mode: sprite.screen_mode,
+			}
+		}));
+	}
+
+	/// Sprites named exactly `name`, either bare or with the set prefix (`SPR_SEL_PV_LOGO`).
+	pub fn find<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SpriteLocation> {
+		self.sprites.iter().filter(move |location| {
+			location.sprite == name
+				|| name
+					.strip_prefix(location.set.as_str())
+					.and_then(|name| name.strip_prefix('_'))
+					== Some(location.sprite.as_str())
+		})
+	}
+
+	/// Sprites whose name contains `pattern`, ignoring case.
+	pub fn search<'a>(&'a self, pattern: &str) -> impl Iterator<Item = &'a SpriteLocation> {
+		let pattern = pattern.to_ascii_lowercase();
+		self.sprites
+			.iter()
+			.filter(move |location| location.sprite.to_ascii_lowercase().contains(&pattern))
+	}
+
+	pub fn to_json_writer<W: io::Write>(&self, writer: W) -> Result<(), SpriteError> {
+		serde_json::to_writer_pretty(writer, self).map_err(io::Error::from)?;
+		Ok(())
+	}
+
+	pub fn to_json(&self) -> Result<String, SpriteError> {
+		let mut out = vec![];
+		self.to_json_writer(&mut out)?;
+		Ok(String::from_utf8_lossy(&out).to_string())
+	}
+}
+
+/// Aggregate numbers over a [`SpriteIndex`]. The game version of a file is the first folder under
+/// the scanned root it sits in, so scanning a folder holding one dump per game groups by game.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScanStats {
+	pub set_count: usize,
+	pub texture_count: usize,
+	pub sprite_count: usize,
+	/// Texture count per format, per game version
+	pub formats_by_version: BTreeMap<String, BTreeMap<String, usize>>,
+	/// Texture count keyed by the larger side rounded up to a power of two
+	pub texture_sizes: BTreeMap<u32, usize>,
+	pub vram_by_set: BTreeMap<String, u64>,
+	pub total_vram: u64,
+}
+
+impl SpriteIndex {
+	fn version_of(&self, file: &Path) -> String {
+		let relative = file.strip_prefix(&self.root).unwrap_or(file);
+		let mut components = relative.components();
+		match (components.next(), components.next()) {
+			(Some(first), Some(_)) => first.as_os_str().to_string_lossy().to_string(),
+			_ => String::new(),
+		}
+	}
+
+	pub fn stats(&self) -> ScanStats {
+		let mut stats = ScanStats {
+			texture_count: self.textures.len(),
+			sprite_count: self.sprites.len(),
+			..Default::default()
+		};
+		let mut sets = HashSet::new();
+		for texture in &self.textures {
+			sets.insert((&texture.file, &texture.farc_entry));
+			*stats
+				.formats_by_version
+				.entry(self.version_of(&texture.file))
+				.or_default()
+				.entry(texture.format.clone())
+				.or_default() += 1;
+			let size = texture.width.max(texture.height).next_power_of_two();
+			*stats.texture_sizes.entry(size).or_default() += 1;
+			*stats.vram_by_set.entry(texture.set.clone()).or_default() += texture.data_size;
+			stats.total_vram += texture.data_size;
+		}
+		for sprite in &self.sprites {
+			sets.insert((&sprite.file, &sprite.farc_entry));
+		}
+		stats.set_count = sets.len();
+		stats
+	}
+}
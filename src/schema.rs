@@ -0,0 +1,228 @@
+//! Hex editor templates describing the file the same way the readers in [`crate::format`] do.
+//! Enum values are generated from `TextureFormat::ALL` and `ScreenMode::ALL`, which are defined
+//! together with their enums so they can't drift; the structures mirror `SprSetReader` and
+//! friends and need updating along with them.
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaFormat {
+	/// An ImHex `.hexpat` pattern
+	ImHex,
+	/// A 010 Editor `.bt` binary template
+	Template010,
+}
+
+fn enum_values() -> (Vec<String>, Vec<String>) {
+	let formats = TextureFormat::ALL
+		.iter()
+		.map(|format| format!("\t{format:?} = {},", *format as i32))
+		.collect();
+	let screen_modes = ScreenMode::ALL
+		.iter()
+		.map(|mode| format!("\t{mode:?} = {},", *mode as u32))
+		.collect();
+	(formats, screen_modes)
+}
+
+pub fn schema(format: SchemaFormat) -> String {
+	let (formats, screen_modes) = enum_values();
+	let (formats, screen_modes) = (formats.join("\n"), screen_modes.join("\n"));
+	match format {
+		SchemaFormat::ImHex => format!(
+			r#"// spr set, generated by the spr crate
+#pragma endian little
+import std.string;
+import std.ptr;
+
+enum TextureFormat : s32 {{
+{formats}
+}};
+
+enum ScreenMode : u32 {{
+{screen_modes}
+}};
+
+struct MipMap {{
+	char magic[4];
+	s32 width;
+	s32 height;
+	TextureFormat format;
+	u8 index;
+	u8 array_index;
+	u16 padding;
+	u32 data_size;
+	u8 data[data_size];
+}};
+
+struct MipPtr {{
+	MipMap *mip : u32 [[pointer_base("std::ptr::relative_to_parent")]];
+}};
+
+struct Texture {{
+	char magic[4];
+	u32 mip_maps;
+	u8 mip_levels;
+	u8 array_size;
+	u8 depth;
+	u8 dimensions;
+	// Cube maps count mip_levels over all faces, unknown versions are left alone
+	if (magic[3] == 4)
+		MipPtr mips[mip_levels * array_size];
+	else if (magic[3] == 5)
+		MipPtr mips[mip_levels];
+}};
+
+struct TexturePtr {{
+	Texture *texture : u32 [[pointer_base("std::ptr::relative_to_parent")]];
+}};
+
+struct TextureSet {{
+	char magic[4];
+	u32 texture_count;
+	u32 padding;
+	TexturePtr textures[texture_count];
+}};
+
+struct Sprite {{
+	s32 texture_index;
+	s32 rotate;
+	float texel_region[4];
+	float pixel_region[4];
+}};
+
+struct SpriteExtra {{
+	u32 flags;
+	ScreenMode screen_mode;
+}};
+
+struct NamePtr {{
+	std::string::NullString *name : u32;
+}};
+
+struct SprSet {{
+	u32 flags;
+	TextureSet *texture_set : u32;
+	u32 texture_count;
+	u32 sprite_count;
+	Sprite *sprites[sprite_count] : u32;
+	NamePtr *texture_names[texture_count] : u32;
+	NamePtr *sprite_names[sprite_count] : u32;
+	SpriteExtra *sprite_extras[sprite_count] : u32;
+}};
+
+SprSet set @ 0x00;
+"#
+		),
+		SchemaFormat::Template010 => format!(
+			r#"// spr set, generated by the spr crate
+LittleEndian();
+
+typedef enum <int> {{
+{formats}
+}} TEXTURE_FORMAT;
+
+typedef enum <uint> {{
+{screen_modes}
+}} SCREEN_MODE;
+
+typedef struct {{
+	char magic[4];
+	int width;
+	int height;
+	TEXTURE_FORMAT format;
+	ubyte index;
+	ubyte array_index;
+	ushort padding;
+	uint data_size;
+	ubyte data[data_size];
+}} MIPMAP;
+
+typedef struct {{
+	local int64 base = FTell();
+	char magic[4];
+	uint mip_maps;
+	ubyte mip_levels;
+	ubyte array_size;
+	ubyte depth;
+	ubyte dimensions;
+	// Cube maps count mip_levels over all faces, unknown versions are left alone
+	local uint count = 0;
+	if (magic[3] == 4)
+		count = mip_levels * array_size;
+	else if (magic[3] == 5)
+		count = mip_levels;
+	if (count > 0) {{
+		uint mip_offsets[count];
+		local int64 back = FTell();
+		local uint i;
+		for (i = 0; i < count; i++) {{
+			FSeek(base + mip_offsets[i]);
+			MIPMAP mip;
+		}}
+		FSeek(back);
+	}}
+}} TEXTURE;
+
+typedef struct {{
+	local int64 base = FTell();
+	char magic[4];
+	uint texture_count;
+	uint padding;
+	uint texture_offsets[texture_count];
+	local int64 back = FTell();
+	local uint i;
+	for (i = 0; i < texture_count; i++) {{
+		FSeek(base + texture_offsets[i]);
+		TEXTURE texture;
+	}}
+	FSeek(back);
+}} TEXTURE_SET;
+
+typedef struct {{
+	int texture_index;
+	int rotate;
+	float texel_region[4];
+	float pixel_region[4];
+}} SPRITE;
+
+typedef struct {{
+	uint flags;
+	SCREEN_MODE screen_mode;
+}} SPRITE_EXTRA;
+
+typedef struct (uint count) {{
+	uint pointers[count];
+	local int64 back = FTell();
+	local uint i;
+	for (i = 0; i < count; i++) {{
+		FSeek(pointers[i]);
+		string name;
+	}}
+	FSeek(back);
+}} NAMES;
+
+uint flags;
+uint texture_set_pos;
+uint texture_count;
+uint sprite_count;
+uint sprites_pos;
+uint texture_names_pos;
+uint sprite_names_pos;
+uint sprite_extras_pos;
+
+if (texture_set_pos != 0) {{
+	FSeek(texture_set_pos);
+	TEXTURE_SET texture_set;
+}}
+FSeek(sprites_pos);
+SPRITE sprites[sprite_count];
+FSeek(texture_names_pos);
+NAMES texture_names(texture_count);
+FSeek(sprite_names_pos);
+NAMES sprite_names(sprite_count);
+FSeek(sprite_extras_pos);
+SPRITE_EXTRA sprite_extras[sprite_count];
+"#
+		),
+	}
+}
@@ -0,0 +1,488 @@
+//! The editable in-memory model of a set.
+use crate::*;
+
+#[derive(Debug, Default, Clone)]
+pub struct SprSet {
+	pub name: String,
+	pub(crate) flags: u32,
+	/// In the order they were read or added
+	pub textures: IndexMap<String, Texture>,
+	/// In the order they were read or added
+	pub sprites: IndexMap<String, Sprite>,
+	/// Problems that were worked around while reading
+	pub warnings: Vec<Warning>,
+	/// Where the sprites are used, never read from or written to the file
+	pub usage: usage::UsageMap,
+	/// Names from the file that were normalized on load
+	pub original_names: name::OriginalNames,
+	/// Textures in another set that sprites of a reference-only set point at, by their index there
+	pub(crate) external_textures: HashMap<String, u32>,
+	/// Where textures and sprites were in the file, see [`SprSet::original_sprite_index`]
+	pub original_indices: OriginalIndices,
+	/// Write order set with [`SprSet::set_texture_order`], by name otherwise
+	pub(crate) texture_order: Option<Vec<String>>,
+	/// Write order set with [`SprSet::set_sprite_order`], by name otherwise
+	pub(crate) sprite_order: Option<Vec<String>>,
+}
+
+/// Indices textures and sprites had in the file they were read from, by the name they were read
+/// with. Sets built in memory have none.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OriginalIndices {
+	pub textures: HashMap<String, u32>,
+	pub sprites: HashMap<String, u32>,
+}
+
+/// `order` with names that are gone dropped and new ones appended by name.
+fn effective_order<'a, T>(
+	map: &'a IndexMap<String, T>,
+	order: &'a Option<Vec<String>>,
+) -> Vec<&'a String> {
+	let mut names = order
+		.iter()
+		.flatten()
+		.filter(|name| map.contains_key(*name))
+		.collect::<Vec<_>>();
+	let mut rest = map
+		.keys()
+		.filter(|name| !names.contains(name))
+		.collect::<Vec<_>>();
+	rest.sort();
+	names.extend(rest);
+	names
+}
+
+/// `map`'s names by where they were in the file, the ones added since after them in the order
+/// they were added.
+fn original_order<'a, T>(
+	map: &'a IndexMap<String, T>,
+	indices: &HashMap<String, u32>,
+) -> Vec<&'a String> {
+	let mut names = map.keys().collect::<Vec<_>>();
+	names.sort_by_key(|name| indices.get(*name).copied().unwrap_or(u32::MAX));
+	names
+}
+
+/// Moves the entry for `old` to `new`.
+fn rename_in_map<T>(map: &mut HashMap<String, T>, old: &str, new: &str) {
+	if let Some(value) = map.remove(old) {
+		map.insert(new.to_string(), value);
+	}
+}
+
+/// Puts `new` where `old` stands in an order set with [`SprSet::set_sprite_order`] or
+/// [`SprSet::set_texture_order`].
+fn rename_in_order(order: &mut Option<Vec<String>>, old: &str, new: &str) {
+	if let Some(name) = order.iter_mut().flatten().find(|name| *name == old) {
+		*name = new.to_string();
+	}
+}
+
+fn check_order<T>(
+	map: &IndexMap<String, T>,
+	order: &[String],
+	what: &str,
+) -> Result<(), SpriteError> {
+	let mut seen = std::collections::HashSet::new();
+	for name in order {
+		if !map.contains_key(name) {
+			return Err(SpriteError::Validation(
+				ErrorCode::InvalidOrder,
+				format!("{what} {name} is not in the set"),
+			));
+		}
+		if !seen.insert(name) {
+			return Err(SpriteError::Validation(
+				ErrorCode::InvalidOrder,
+				format!("{what} {name} is listed twice"),
+			));
+		}
+	}
+	if let Some(missing) = map.keys().find(|name| !seen.contains(name)) {
+		return Err(SpriteError::Validation(
+			ErrorCode::InvalidOrder,
+			format!("{what} {missing} is missing from the order"),
+		));
+	}
+	Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sprite {
+	pub screen_mode: ScreenMode,
+	/// UVs of `pixel_region`, 0 to 1 across the texture. Keep it in step with
+	/// [`Sprite::recompute_texel_region`] after moving the sprite.
+	pub texel_region: Vec4,
+	pub pixel_region: Vec4,
+	pub texture_name: String,
+	/// Quarter turns clockwise the game applies when drawing, 1 to 3 for 90 to 270 degrees
+	pub rotate: i32,
+}
+
+impl Sprite {
+	/// A sprite covering `pixel_region` (top-down) of `texture_name`. The texel region is filled
+	/// in by [`SprSet::from_parts`].
+	pub fn new(texture_name: &str, pixel_region: Vec4, screen_mode: ScreenMode) -> Self {
+		Self {
+			screen_mode,
+			texel_region: Vec4::default(),
+			pixel_region,
+			texture_name: texture_name.to_string(),
+			rotate: 0,
+		}
+	}
+}
+
+impl Sprite {
+	/// Derives the texel region from the pixel region for a `width`x`height` texture.
+	pub fn recompute_texel_region(&mut self, width: u32, height: u32, rounding: TexelRounding) {
+		self.texel_region = rounding.texel_region(&self.pixel_region, width, height);
+	}
+}
+
+impl SprSet {
+	/// A set with no sprites or textures.
+	pub fn new(name: &str) -> Self {
+		Self {
+			name: name.to_string(),
+			..Default::default()
+		}
+	}
+
+	/// A texture-only container, for sets other sets' sprites or 3D objects reference.
+	pub fn with_textures(
+		name: &str,
+		textures: impl IntoIterator<Item = (String, Texture)>,
+	) -> Self {
+		Self {
+			textures: textures.into_iter().collect(),
+			..Self::new(name)
+		}
+	}
+
+	/// Builds a set from parts, checking up front that every sprite's texture exists and its
+	/// region lies inside it, so mistakes show up here rather than when writing.
+	pub fn from_parts(
+		name: &str,
+		textures: impl IntoIterator<Item = (String, Texture)>,
+		sprites: impl IntoIterator<Item = (String, Sprite)>,
+		flags: u32,
+	) -> Result<Self, SpriteError> {
+		Self::from_parts_with_rounding(name, textures, sprites, flags, TexelRounding::default())
+	}
+
+	/// [`SprSet::from_parts`] filling in missing texel regions with `rounding`.
+	pub fn from_parts_with_rounding(
+		name: &str,
+		textures: impl IntoIterator<Item = (String, Texture)>,
+		sprites: impl IntoIterator<Item = (String, Sprite)>,
+		flags: u32,
+		rounding: TexelRounding,
+	) -> Result<Self, SpriteError> {
+		let mut set = Self {
+			flags,
+			textures: textures.into_iter().collect(),
+			sprites: sprites.into_iter().collect(),
+			..Self::new(name)
+		};
+		set.check_parts()?;
+		for sprite in set.sprites.values_mut() {
+			if sprite.texel_region != Vec4::default() {
+				continue;
+			}
+			if let Some(texture) = set.textures.get(&sprite.texture_name) {
+				sprite.recompute_texel_region(
+					texture.image.width(),
+					texture.image.height(),
+					rounding,
+				);
+			}
+		}
+		Ok(set)
+	}
+
+	fn check_parts(&self) -> Result<(), SpriteError> {
+		let invalid = |name: &str| name.is_empty() || name.contains('\0');
+		let mut names = self
+			.textures
+			.keys()
+			.chain(self.sprites.keys())
+			.collect::<Vec<_>>();
+		names.sort();
+		if let Some(name) = std::iter::once(&self.name)
+			.chain(names)
+			.find(|name| invalid(name))
+		{
+			return Err(SpriteError::Validation(
+				ErrorCode::InvalidName,
+				format!("Name {name:?} is empty or contains a nul byte"),
+			));
+		}
+
+		let mut sprites = self.sprites.iter().collect::<Vec<_>>();
+		sprites.sort_by(|(a, _), (b, _)| a.cmp(b));
+		for (name, sprite) in sprites {
+			if !self.textures.contains_key(&sprite.texture_name)
+				&& self.external_textures.contains_key(&sprite.texture_name)
+			{
+				continue;
+			}
+			let texture = self.textures.get(&sprite.texture_name).ok_or_else(|| {
+				SpriteError::Validation(
+					ErrorCode::MissingTexture,
+					format!(
+						"Sprite {name} is on texture {} which is not in the set",
+						sprite.texture_name
+					),
+				)
+			})?;
+			if texture.raw.is_some() {
+				continue;
+			}
+			let region = sprite.pixel_region;
+			let (width, height) = (texture.image.width(), texture.image.height());
+			if !extract::region_inside(&region, width, height) {
+				return Err(SpriteError::Validation(
+					ErrorCode::RegionOutOfBounds,
+					format!(
+						"Sprite {name} covers {}x{} at {}, {}, outside its {width}x{height} texture {}",
+						region.z, region.w, region.x, region.y, sprite.texture_name
+					),
+				));
+			}
+		}
+		Ok(())
+	}
+
+	/// Header flags, kept as read and passed to [`SprSet::from_parts`].
+	pub fn flags(&self) -> u32 {
+		self.flags
+	}
+
+	/// Fixes the order textures are written in, which is their index in the file. `order` must
+	/// list every texture once. Textures added later go after these, by name.
+	pub fn set_texture_order(&mut self, order: Vec<String>) -> Result<(), SpriteError> {
+		check_order(&self.textures, &order, "Texture")?;
+		self.texture_order = Some(order);
+		Ok(())
+	}
+
+	/// Fixes the order sprites are written in, see [`SprSet::set_texture_order`]. Pinned indices
+	/// from [`WriteOptions::sprite_indexing`] still win.
+	pub fn set_sprite_order(&mut self, order: Vec<String>) -> Result<(), SpriteError> {
+		check_order(&self.sprites, &order, "Sprite")?;
+		self.sprite_order = Some(order);
+		Ok(())
+	}
+
+	/// Texture names in the order they get written.
+	pub fn texture_order(&self) -> Vec<&String> {
+		effective_order(&self.textures, &self.texture_order)
+	}
+
+	/// Sprite names in the order they get written without pinned indices.
+	pub fn sprite_order(&self) -> Vec<&String> {
+		effective_order(&self.sprites, &self.sprite_order)
+	}
+
+	/// Texture names in the order `options` writes them, their index in the file.
+	pub fn written_texture_order(&self, options: &WriteOptions) -> Vec<&String> {
+		match &self.texture_order {
+			None if options.original_order => {
+				original_order(&self.textures, &self.original_indices.textures)
+			}
+			_ => self.texture_order(),
+		}
+	}
+
+	/// Sprite names in the order `options` writes them without pinned indices.
+	pub fn written_sprite_order(&self, options: &WriteOptions) -> Vec<&String> {
+		match &self.sprite_order {
+			None if options.original_order => {
+				original_order(&self.sprites, &self.original_indices.sprites)
+			}
+			_ => self.sprite_order(),
+		}
+	}
+
+	/// Renames sprite `old` where it stands, keeping its place in the write order and its
+	/// original index. The name it had in the file is forgotten, so
+	/// [`WriteOptions::restore_names`] writes the new one. False when there is no such sprite.
+	pub(crate) fn rename_sprite_key(&mut self, old: &str, new: &str) -> bool {
+		let Some((index, _, sprite)) = self.sprites.shift_remove_full(old) else {
+			return false;
+		};
+		self.sprites.shift_insert(index, new.to_string(), sprite);
+		rename_in_map(&mut self.original_indices.sprites, old, new);
+		self.original_names.sprites.remove(old);
+		rename_in_order(&mut self.sprite_order, old, new);
+		true
+	}
+
+	/// [`SprSet::rename_sprite_key`] for textures, sprites on it are not touched.
+	pub(crate) fn rename_texture_key(&mut self, old: &str, new: &str) -> bool {
+		let Some((index, _, texture)) = self.textures.shift_remove_full(old) else {
+			return false;
+		};
+		self.textures.shift_insert(index, new.to_string(), texture);
+		rename_in_map(&mut self.original_indices.textures, old, new);
+		self.original_names.textures.remove(old);
+		rename_in_order(&mut self.texture_order, old, new);
+		true
+	}
+
+	/// Index texture `name` had in the file it was read from.
+	pub fn original_texture_index(&self, name: &str) -> Option<u32> {
+		self.original_indices.textures.get(name).copied()
+	}
+
+	/// Index sprite `name` had in the file it was read from, which is what spr_db refers to.
+	pub fn original_sprite_index(&self, name: &str) -> Option<u32> {
+		self.original_indices.sprites.get(name).copied()
+	}
+
+	/// Works every sprite's texel region out again from its pixel region with `rounding`.
+	pub fn recompute_texel_regions(&mut self, rounding: TexelRounding) {
+		for sprite in self.sprites.values_mut() {
+			if let Some(texture) = self.textures.get(&sprite.texture_name) {
+				sprite.texel_region = rounding.texel_region(
+					&sprite.pixel_region,
+					texture.image.width(),
+					texture.image.height(),
+				);
+			}
+		}
+	}
+
+	/// Sets `mode` on every sprite matching `predicate`, returning how many sprites changed.
+	pub fn set_screen_mode_where<F: FnMut(&str, &Sprite) -> bool>(
+		&mut self,
+		mut predicate: F,
+		mode: ScreenMode,
+	) -> usize {
+		let mut changed = 0;
+		for (name, sprite) in self.sprites.iter_mut() {
+			if sprite.screen_mode != mode && predicate(name, sprite) {
+				sprite.screen_mode = mode;
+				changed += 1;
+			}
+		}
+		changed
+	}
+
+	pub fn screen_mode_histogram(&self) -> HashMap<ScreenMode, usize> {
+		let mut histogram = HashMap::new();
+		for sprite in self.sprites.values() {
+			*histogram.entry(sprite.screen_mode).or_insert(0) += 1;
+		}
+		histogram
+	}
+
+	/// Works out which sprite goes in each index slot. `None` slots are reserved or left free by
+	/// pinned sprites and get written as empty placeholders.
+	pub fn assign_sprite_indices(
+		&self,
+		indexing: &SpriteIndexing,
+	) -> Result<Vec<Option<String>>, SpriteError> {
+		let mut slots: Vec<Option<String>> = vec![];
+		let mut pinned = indexing
+			.pinned
+			.iter()
+			.filter(|(name, _)| self.sprites.contains_key(*name))
+			.collect::<Vec<_>>();
+		pinned.sort_by(|(a, _), (b, _)| a.cmp(b));
+		for (name, index) in pinned {
+			if indexing.is_reserved(*index) {
+				return Err(SpriteError::Validation(
+					ErrorCode::ReservedSpriteIndex,
+					format!("Sprite {name} is pinned to reserved index {index}"),
+				));
+			}
+			let index = *index as usize;
+			if slots.len() <= index {
+				slots.resize(index + 1, None);
+			}
+			if let Some(other) = &slots[index] {
+				return Err(SpriteError::Validation(
+					ErrorCode::DuplicatePinnedIndex,
+					format!("Sprites {other} and {name} are both pinned to index {index}"),
+				));
+			}
+			slots[index] = Some(name.clone());
+		}
+
+		let new_sprites = self
+			.sprite_order()
+			.into_iter()
+			.filter(|name| !indexing.pinned.contains_key(*name));
+		for name in new_sprites {
+			while indexing.is_reserved(slots.len() as u32) {
+				slots.push(None);
+			}
+			slots.push(Some(name.clone()));
+		}
+		Ok(slots)
+	}
+}
+
+/// How texel regions are worked out from pixel regions. Plain division keeps any fractional
+/// pixel edges, which a scaled sprite then samples half of its neighbour through, showing up as
+/// one pixel seams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum TexelRounding {
+	/// Pixel edges divided by the texture size as they are
+	#[default]
+	Exact,
+	/// Edges rounded to whole pixels first
+	Snapped,
+	/// Edges rounded to whole pixels then moved half a texel inwards, so bilinear filtering never
+	/// reaches past the sprite. For sprites drawn scaled.
+	HalfTexelInset,
+}
+
+impl TexelRounding {
+	pub fn texel_region(self, pixel_region: &Vec4, width: u32, height: u32) -> Vec4 {
+		let (width, height) = (width as f32, height as f32);
+		let (x0, y0) = (pixel_region.x, pixel_region.y);
+		let (x1, y1) = (x0 + pixel_region.z, y0 + pixel_region.w);
+		let [x0, y0, x1, y1] = match self {
+			Self::Exact => [x0, y0, x1, y1],
+			Self::Snapped => [x0, y0, x1, y1].map(f32::round),
+			Self::HalfTexelInset => {
+				let [x0, y0, x1, y1] = [x0, y0, x1, y1].map(f32::round);
+				// A sprite a pixel wide or less has no room to inset, sample its centre
+				let (x0, x1) = if x1 - x0 > 1.0 {
+					(x0 + 0.5, x1 - 0.5)
+				} else {
+					((x0 + x1) / 2.0, (x0 + x1) / 2.0)
+				};
+				let (y0, y1) = if y1 - y0 > 1.0 {
+					(y0 + 0.5, y1 - 0.5)
+				} else {
+					((y0 + y1) / 2.0, (y0 + y1) / 2.0)
+				};
+				[x0, y0, x1, y1]
+			}
+		};
+		Vec4 {
+			x: x0 / width,
+			y: y0 / height,
+			z: x1 / width,
+			w: y1 / height,
+		}
+	}
+}
+
+/// Crops `sprite` out of `texture` upright, turned by its 90, 180 or 270 degree rotation and
+/// mirrored where its region runs backwards. [`SprSet::replace_sprite_image`] undoes this.
+pub fn load_sprite_image(texture: image::DynamicImage, sprite: Sprite) -> image::DynamicImage {
+	extract::crop_sprite_oriented(&texture, &sprite, SubPixel::Floor)
+}
+
+pub fn load_sprite_image_with_mode(
+	texture: image::DynamicImage,
+	sprite: Sprite,
+	sub_pixel: SubPixel,
+) -> image::DynamicImage {
+	extract::crop_sprite_oriented(&texture, &sprite, sub_pixel)
+}
@@ -0,0 +1,191 @@
+use crate::*;
+use std::path::Path;
+
+const SNAPSHOT_VERSION: u32 = 4;
+const METADATA_FILE: &str = "snapshot.bin";
+const BLOB_FILE: &str = "textures.blob";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TextureEntry {
+	name: String,
+	width: u32,
+	height: u32,
+	original_format: TextureFormat,
+	mip_count: u32,
+	array_size: u32,
+	placeholder: bool,
+	raw: Option<RawTexture>,
+	mip_indices: Vec<MipIndex>,
+	/// Range of the decoded RGBA8 pixels in the blob file
+	offset: u64,
+	size: u64,
+	/// Ranges of the array layers after the first, all the same size
+	layers: Vec<u64>,
+	cubemap: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+	version: u32,
+	name: String,
+	flags: u32,
+	/// In the set's order, which is the order they were read in
+	textures: Vec<TextureEntry>,
+	texture_order: Option<Vec<String>>,
+	sprite_order: Option<Vec<String>>,
+	sprites: Vec<(String, Sprite)>,
+	external_textures: Vec<(String, u32)>,
+	original_texture_indices: Vec<(String, u32)>,
+	original_sprite_indices: Vec<(String, u32)>,
+	original_texture_names: Vec<(String, String)>,
+	original_sprite_names: Vec<(String, String)>,
+}
+
+/// `map`'s entries sorted, so the same set always gives the same snapshot.
+fn sorted_entries<T: Clone + Ord>(map: &HashMap<String, T>) -> Vec<(String, T)> {
+	let mut entries = map
+		.iter()
+		.map(|(name, value)| (name.clone(), value.clone()))
+		.collect::<Vec<_>>();
+	entries.sort();
+	entries
+}
+
+fn snapshot_error(message: impl ToString) -> SpriteError {
+	SpriteError::Io(io::Error::new(
+		io::ErrorKind::InvalidData,
+		message.to_string(),
+	))
+}
+
+fn truncated_blob(texture: &str) -> SpriteError {
+	snapshot_error(format!(
+		"Pixels of texture {texture} run past the end of {BLOB_FILE}"
+	))
+}
+
+impl SprSet {
+	/// Writes the parsed set to `dir` as a metadata file plus one blob of already decoded pixels,
+	/// so [`SprSet::load_snapshot`] can skip parsing and decoding entirely.
+	pub fn save_snapshot(&self, dir: &Path) -> Result<(), SpriteError> {
+		std::fs::create_dir_all(dir)?;
+		let mut blob = std::io::BufWriter::new(std::fs::File::create(dir.join(BLOB_FILE))?);
+		let mut offset = 0;
+		let mut textures = Vec::with_capacity(self.textures.len());
+		for (name, texture) in &self.textures {
+			let pixels = texture.image.to_rgba8();
+			io::Write::write_all(&mut blob, pixels.as_raw())?;
+			let size = pixels.as_raw().len() as u64;
+			let mut layers = vec![];
+			for layer in &texture.layers {
+				let layer = layer.to_rgba8();
+				io::Write::write_all(&mut blob, layer.as_raw())?;
+				layers.push(offset + size * (layers.len() as u64 + 1));
+			}
+			textures.push(TextureEntry {
+				name: name.clone(),
+				width: pixels.width(),
+				height: pixels.height(),
+				original_format: texture.original_format,
+				mip_count: texture.mip_count,
+				array_size: texture.array_size,
+				placeholder: texture.placeholder,
+				raw: texture.raw.clone(),
+				mip_indices: texture.mip_indices.clone(),
+				offset,
+				size,
+				layers: layers.clone(),
+				cubemap: texture.cubemap,
+			});
+			offset += size * (layers.len() as u64 + 1);
+		}
+		io::Write::flush(&mut blob)?;
+
+		let sprites = self
+			.sprites
+			.iter()
+			.map(|(name, sprite)| (name.clone(), sprite.clone()))
+			.collect::<Vec<_>>();
+		let snapshot = Snapshot {
+			version: SNAPSHOT_VERSION,
+			name: self.name.clone(),
+			flags: self.flags,
+			textures,
+			texture_order: self.texture_order.clone(),
+			sprite_order: self.sprite_order.clone(),
+			sprites,
+			external_textures: sorted_entries(&self.external_textures),
+			original_texture_indices: sorted_entries(&self.original_indices.textures),
+			original_sprite_indices: sorted_entries(&self.original_indices.sprites),
+			original_texture_names: sorted_entries(&self.original_names.textures),
+			original_sprite_names: sorted_entries(&self.original_names.sprites),
+		};
+		let metadata = std::io::BufWriter::new(std::fs::File::create(dir.join(METADATA_FILE))?);
+		bincode::serialize_into(metadata, &snapshot).map_err(snapshot_error)
+	}
+
+	pub fn load_snapshot(dir: &Path) -> Result<Self, SpriteError> {
+		let metadata = io::BufReader::new(std::fs::File::open(dir.join(METADATA_FILE))?);
+		let snapshot: Snapshot = bincode::deserialize_from(metadata).map_err(snapshot_error)?;
+		if snapshot.version != SNAPSHOT_VERSION {
+			return Err(snapshot_error(format!(
+				"Snapshot version {} is not supported",
+				snapshot.version
+			)));
+		}
+		let blob = std::fs::read(dir.join(BLOB_FILE))?;
+
+		let mut textures = IndexMap::with_capacity(snapshot.textures.len());
+		for entry in snapshot.textures {
+			let pixels = blob
+				.get(entry.offset as usize..(entry.offset + entry.size) as usize)
+				.ok_or_else(|| truncated_blob(&entry.name))?;
+			let image = image::RgbaImage::from_raw(entry.width, entry.height, pixels.to_vec())
+				.ok_or_else(|| truncated_blob(&entry.name))?;
+			let layers = entry
+				.layers
+				.iter()
+				.map(|offset| {
+					let pixels = blob.get(*offset as usize..(offset + entry.size) as usize)?;
+					image::RgbaImage::from_raw(entry.width, entry.height, pixels.to_vec())
+						.map(DynamicImage::ImageRgba8)
+				})
+				.collect::<Option<Vec<_>>>()
+				.ok_or_else(|| truncated_blob(&entry.name))?;
+			textures.insert(
+				entry.name,
+				Texture {
+					image: DynamicImage::ImageRgba8(image),
+					original_format: entry.original_format,
+					mip_count: entry.mip_count,
+					array_size: entry.array_size,
+					placeholder: entry.placeholder,
+					raw: entry.raw,
+					mip_indices: entry.mip_indices,
+					layers,
+					cubemap: entry.cubemap,
+				},
+			);
+		}
+
+		Ok(Self {
+			name: snapshot.name,
+			flags: snapshot.flags,
+			textures,
+			sprites: snapshot.sprites.into_iter().collect(),
+			warnings: vec![],
+			usage: Default::default(),
+			original_names: OriginalNames {
+				textures: snapshot.original_texture_names.into_iter().collect(),
+				sprites: snapshot.original_sprite_names.into_iter().collect(),
+			},
+			external_textures: snapshot.external_textures.into_iter().collect(),
+			original_indices: OriginalIndices {
+				textures: snapshot.original_texture_indices.into_iter().collect(),
+				sprites: snapshot.original_sprite_indices.into_iter().collect(),
+			},
+			texture_order: snapshot.texture_order,
+			sprite_order: snapshot.sprite_order,
+		})
+	}
+}
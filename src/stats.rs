@@ -0,0 +1,62 @@
+//! Summary numbers for a single set, the per-set counterpart of [`crate::scanner::ScanStats`].
+use crate::*;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SetStats {
+	pub texture_count: usize,
+	pub sprite_count: usize,
+	/// Texture count per original format
+	pub formats: BTreeMap<String, usize>,
+	/// Width and height of every texture
+	pub texture_sizes: BTreeMap<String, (u32, u32)>,
+	/// Sprite count per screen mode
+	pub screen_modes: BTreeMap<String, usize>,
+	/// What the textures take up in their original formats with every mip and layer, which is
+	/// roughly their VRAM cost
+	pub stored_bytes: u64,
+	/// What the decoded RGBA textures take up in memory
+	pub decoded_bytes: u64,
+	/// Fraction of all texture area covered by sprites, overlaps counted twice
+	pub sprite_coverage: f64,
+}
+
+impl SprSet {
+	pub fn stats(&self) -> SetStats {
+		let mut stats = SetStats {
+			texture_count: self.textures.len(),
+			sprite_count: self.sprites.len(),
+			..Default::default()
+		};
+		let mut texture_area = 0u64;
+		for (name, texture) in &self.textures {
+			let (width, height) = (texture.image.width(), texture.image.height());
+			*stats
+				.formats
+				.entry(format!("{:?}", texture.original_format))
+				.or_insert(0) += 1;
+			stats.texture_sizes.insert(name.clone(), (width, height));
+			stats.stored_bytes += match &texture.raw {
+				Some(raw) => raw.data.len() as u64,
+				None => texture
+					.original_format
+					.texture_size(width, height, texture.mip_count, texture.array_size)
+					.unwrap_or(0),
+			};
+			stats.decoded_bytes += width as u64 * height as u64 * 4;
+			texture_area += width as u64 * height as u64;
+		}
+		let mut sprite_area = 0.0;
+		for sprite in self.sprites.values() {
+			*stats
+				.screen_modes
+				.entry(format!("{:?}", sprite.screen_mode))
+				.or_insert(0) += 1;
+			sprite_area += (sprite.pixel_region.z * sprite.pixel_region.w).max(0.0) as f64;
+		}
+		if texture_area > 0 {
+			stats.sprite_coverage = sprite_area / texture_area as f64;
+		}
+		stats
+	}
+}
@@ -0,0 +1,438 @@
+use crate::*;
+use std::collections::VecDeque;
+
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureKind {
+	Texture2d,
+	CubeMap,
+	/// A TXP sub-version this crate doesn't know, its mips are not read
+	Unknown(u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SprEvent {
+	Header {
+		/// Byte order of the file, texture data chunks come through as stored
+		endian: Endian,
+		flags: u32,
+		texture_count: u32,
+		sprite_count: u32,
+	},
+	Texture {
+		index: u32,
+		name: String,
+		kind: TextureKind,
+		mip_count: u32,
+		array_size: u32,
+	},
+	MipMap {
+		texture: u32,
+		array_index: u32,
+		mip: u32,
+		width: i32,
+		height: i32,
+		format: TextureFormat,
+		/// The index bytes as stored, which need not match `array_index` and `mip`
+		indices: MipIndex,
+		data_size: u32,
+	},
+	TextureData {
+		texture: u32,
+		array_index: u32,
+		mip: u32,
+		offset: u32,
+		chunk: Vec<u8>,
+	},
+	Sprite {
+		index: u32,
+		name: String,
+		texture_index: i32,
+		rotate: i32,
+		texel_region: Vec4,
+		pixel_region: Vec4,
+		screen_mode: ScreenMode,
+	},
+}
+
+#[derive(Debug)]
+enum Pending {
+	Texture(u32),
+	MipMap {
+		texture: u32,
+		array_index: u32,
+		mip: u32,
+		pos: u64,
+	},
+	Data {
+		texture: u32,
+		array_index: u32,
+		mip: u32,
+		pos: u64,
+		offset: u32,
+		size: u32,
+	},
+	Sprite(u32),
+}
+
+/// A set's header and the pointer tables it leads to, without anything they point at. Shared
+/// with [`crate::borrowed::SprSetRef`].
+#[derive(Debug)]
+pub(crate) struct Tables {
+	pub(crate) flags: u32,
+	pub(crate) texture_count: u32,
+	pub(crate) sprite_count: u32,
+	/// Where every texture starts in the file
+	pub(crate) texture_offsets: Vec<u64>,
+	pub(crate) texture_names: Vec<u32>,
+	pub(crate) sprites_pos: u64,
+	pub(crate) sprite_names: Vec<u32>,
+	pub(crate) sprite_extras_pos: u64,
+}
+
+impl Tables {
+	/// Reads the header at the reader's position and the tables it points to.
+	pub(crate) fn read<R: io::Read + io::Seek>(
+		reader: &mut R,
+		endian: Endian,
+	) -> Result<Self, SpriteError> {
+		let flags: u32 = reader.read_type(endian)?;
+		let textures_pos: u32 = reader.read_type(endian)?;
+		let texture_count: u32 = reader.read_type(endian)?;
+		let sprite_count: u32 = reader.read_type(endian)?;
+		let sprites_pos: u32 = reader.read_type(endian)?;
+		let texture_names_pos: u32 = reader.read_type(endian)?;
+		let sprite_names_pos: u32 = reader.read_type(endian)?;
+		let sprite_extras_pos: u32 = reader.read_type(endian)?;
+
+		let texture_offsets = read_texture_offsets(reader, textures_pos, endian)?;
+		reader.seek(SeekFrom::Start(texture_names_pos as u64))?;
+		let texture_names = read_u32s(reader, texture_count, endian)?;
+		reader.seek(SeekFrom::Start(sprite_names_pos as u64))?;
+		let sprite_names = read_u32s(reader, sprite_count, endian)?;
+
+		Ok(Self {
+			flags,
+			texture_count,
+			sprite_count,
+			texture_offsets,
+			texture_names,
+			sprites_pos: sprites_pos as u64,
+			sprite_names,
+			sprite_extras_pos: sprite_extras_pos as u64,
+		})
+	}
+
+	/// Where sprite `index` and its screen mode entry are stored.
+	pub(crate) fn sprite_pos(&self, index: u32) -> (u64, u64) {
+		(
+			self.sprites_pos + index as u64 * 40,
+			self.sprite_extras_pos + index as u64 * 8,
+		)
+	}
+}
+
+/// A texture's `TXP` header and where each of its mips starts, every mip of the first layer
+/// first. Unknown sub-versions have no mips.
+#[derive(Debug)]
+pub(crate) struct TextureHeader {
+	pub(crate) kind: TextureKind,
+	pub(crate) mip_count: u32,
+	pub(crate) array_size: u32,
+	pub(crate) mip_offsets: Vec<u64>,
+}
+
+impl TextureHeader {
+	pub(crate) fn read<R: io::Read + io::Seek>(
+		reader: &mut R,
+		pos: u64,
+		endian: Endian,
+	) -> Result<Self, SpriteError> {
+		reader.seek(SeekFrom::Start(pos))?;
+		let magic: [u8; 4] = reader.read_type(endian)?;
+		let kind = match &magic {
+			b"TXP\x04" => TextureKind::Texture2d,
+			b"TXP\x05" => TextureKind::CubeMap,
+			[b'T', b'X', b'P', version] => TextureKind::Unknown(*version),
+			_ => return Err(SpriteError::bad_magic("Texture", pos, "TXP")),
+		};
+		if let TextureKind::Unknown(_) = kind {
+			return Ok(Self {
+				kind,
+				mip_count: 0,
+				array_size: 0,
+				mip_offsets: vec![],
+			});
+		}
+		let _mip_maps: u32 = reader.read_type(endian)?;
+		let mip_levels: u8 = reader.read_type(endian)?;
+		let array_size: u8 = reader.read_type(endian)?;
+		let _depth: u8 = reader.read_type(endian)?;
+		let _dimensions: u8 = reader.read_type(endian)?;
+		let mip_count = match kind {
+			TextureKind::Texture2d => mip_levels as u32,
+			TextureKind::CubeMap => (mip_levels / array_size.max(1)) as u32,
+			TextureKind::Unknown(_) => 0,
+		};
+		let mip_offsets = read_u32s(reader, mip_count * array_size as u32, endian)?
+			.into_iter()
+			.map(|offset| pos + offset as u64)
+			.collect();
+		Ok(Self {
+			kind,
+			mip_count,
+			array_size: array_size as u32,
+			mip_offsets,
+		})
+	}
+}
+
+/// A `TXP\x02` mip header. Its `data_size` bytes of data start at `data_pos`.
+#[derive(Debug)]
+pub(crate) struct MipHeader {
+	pub(crate) width: i32,
+	pub(crate) height: i32,
+	pub(crate) format: TextureFormat,
+	pub(crate) indices: MipIndex,
+	pub(crate) data_size: u32,
+	pub(crate) data_pos: u64,
+}
+
+impl MipHeader {
+	pub(crate) fn read<R: io::Read + io::Seek>(
+		reader: &mut R,
+		pos: u64,
+		endian: Endian,
+	) -> Result<Self, SpriteError> {
+		reader.seek(SeekFrom::Start(pos))?;
+		let magic: [u8; 4] = reader.read_type(endian)?;
+		if &magic != b"TXP\x02" {
+			return Err(SpriteError::bad_magic("Mip", pos, "TXP\\x02"));
+		}
+		let width: i32 = reader.read_type(endian)?;
+		let height: i32 = reader.read_type(endian)?;
+		let format: TextureFormat = reader.read_type(endian)?;
+		let index: u8 = reader.read_type(endian)?;
+		let array_index: u8 = reader.read_type(endian)?;
+		let _padding: u16 = reader.read_type(endian)?;
+		let data_size: u32 = reader.read_type(endian)?;
+		Ok(Self {
+			width,
+			height,
+			format,
+			indices: MipIndex { index, array_index },
+			data_size,
+			data_pos: reader.stream_position()?,
+		})
+	}
+}
+
+pub(crate) fn read_u32s<R: io::Read + io::Seek>(
+	reader: &mut R,
+	count: u32,
+	endian: Endian,
+) -> Result<Vec<u32>, SpriteError> {
+	(0..count)
+		.map(|_| reader.read_type::<u32>(endian).map_err(SpriteError::from))
+		.collect()
+}
+
+/// Pull based reader that walks a set one piece at a time, so only a single chunk of texture data
+/// is ever held in memory.
+#[derive(Debug)]
+pub struct SprEventReader<R> {
+	reader: R,
+	chunk_size: usize,
+	/// Detected when the header is read
+	endian: Endian,
+	tables: Option<Tables>,
+	pending: VecDeque<Pending>,
+	done: bool,
+}
+
+impl<R: io::Read + io::Seek> SprEventReader<R> {
+	pub fn new(reader: R) -> Self {
+		Self::with_chunk_size(reader, DEFAULT_CHUNK_SIZE)
+	}
+
+	pub fn with_chunk_size(reader: R, chunk_size: usize) -> Self {
+		Self {
+			reader,
+			chunk_size: chunk_size.max(1),
+			endian: Endian::Little,
+			tables: None,
+			pending: VecDeque::new(),
+			done: false,
+		}
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+
+	pub fn next_event(&mut self) -> Result<Option<SprEvent>, SpriteError> {
+		if self.done {
+			return Ok(None);
+		}
+		let Some(tables) = &self.tables else {
+			return self.read_header().map(Some);
+		};
+		let Some(pending) = self.pending.pop_front() else {
+			self.done = true;
+			return Ok(None);
+		};
+
+		match pending {
+			Pending::Texture(index) => {
+				let offset = *tables.texture_offsets.get(index as usize).ok_or_else(|| {
+					SpriteError::Malformed(format!("Texture {index} has no offset"))
+				})?;
+				let name_ptr = *tables.texture_names.get(index as usize).ok_or_else(|| {
+					SpriteError::Malformed(format!(
+						"Texture {index} has no entry in the name table"
+					))
+				})?;
+				let name = self.read_name(name_ptr)?;
+				let header = TextureHeader::read(&mut self.reader, offset, self.endian)?;
+				// Offsets run through every mip of a layer before the next layer
+				let mip_count = header.mip_count;
+				for (i, pos) in header.mip_offsets.iter().enumerate().rev() {
+					self.pending.push_front(Pending::MipMap {
+						texture: index,
+						array_index: i as u32 / mip_count,
+						mip: i as u32 % mip_count,
+						pos: *pos,
+					});
+				}
+
+				Ok(Some(SprEvent::Texture {
+					index,
+					name,
+					kind: header.kind,
+					mip_count: header.mip_count,
+					array_size: header.array_size,
+				}))
+			}
+			Pending::MipMap {
+				texture,
+				array_index,
+				mip,
+				pos,
+			} => {
+				let header = MipHeader::read(&mut self.reader, pos, self.endian)?;
+				if header.data_size > 0 {
+					self.pending.push_front(Pending::Data {
+						texture,
+						array_index,
+						mip,
+						pos: header.data_pos,
+						offset: 0,
+						size: header.data_size,
+					});
+				}
+
+				Ok(Some(SprEvent::MipMap {
+					texture,
+					array_index,
+					mip,
+					width: header.width,
+					height: header.height,
+					format: header.format,
+					indices: header.indices,
+					data_size: header.data_size,
+				}))
+			}
+			Pending::Data {
+				texture,
+				array_index,
+				mip,
+				pos,
+				offset,
+				size,
+			} => {
+				let len = (size - offset).min(self.chunk_size as u32);
+				self.reader.seek(SeekFrom::Start(pos + offset as u64))?;
+				let mut chunk = vec![0u8; len as usize];
+				self.reader.read_exact(&mut chunk)?;
+				if offset + len < size {
+					self.pending.push_front(Pending::Data {
+						texture,
+						array_index,
+						mip,
+						pos,
+						offset: offset + len,
+						size,
+					});
+				}
+
+				Ok(Some(SprEvent::TextureData {
+					texture,
+					array_index,
+					mip,
+					offset,
+					chunk,
+				}))
+			}
+			Pending::Sprite(index) => {
+				let name_ptr = *tables.sprite_names.get(index as usize).ok_or_else(|| {
+					SpriteError::Malformed(format!("Sprite {index} has no entry in the name table"))
+				})?;
+				let (sprite_pos, extra_pos) = tables.sprite_pos(index);
+				let name = self.read_name(name_ptr)?;
+
+				self.reader.seek(SeekFrom::Start(sprite_pos))?;
+				let sprite: SpriteReader = self.reader.read_type(self.endian)?;
+				self.reader.seek(SeekFrom::Start(extra_pos))?;
+				let (_, screen_mode): (u32, ScreenMode) = self.reader.read_type(self.endian)?;
+
+				Ok(Some(SprEvent::Sprite {
+					index,
+					name,
+					texture_index: sprite.texture_index,
+					rotate: sprite.rotate,
+					texel_region: sprite.texel_region,
+					pixel_region: sprite.pixel_region,
+					screen_mode,
+				}))
+			}
+		}
+	}
+
+	fn read_header(&mut self) -> Result<SprEvent, SpriteError> {
+		self.endian = detect_endianness(&mut self.reader)?;
+		let tables = Tables::read(&mut self.reader, self.endian)?;
+		self.pending
+			.extend((0..tables.texture_offsets.len() as u32).map(Pending::Texture));
+		self.pending
+			.extend((0..tables.sprite_count).map(Pending::Sprite));
+		let event = SprEvent::Header {
+			endian: self.endian,
+			flags: tables.flags,
+			texture_count: tables.texture_count,
+			sprite_count: tables.sprite_count,
+		};
+		self.tables = Some(tables);
+		Ok(event)
+	}
+
+	fn read_name(&mut self, ptr: u32) -> Result<String, SpriteError> {
+		self.reader.seek(SeekFrom::Start(ptr as u64))?;
+		let name: NullString = self.reader.read_type(self.endian)?;
+		Ok(name.to_string())
+	}
+}
+
+impl<R: io::Read + io::Seek> Iterator for SprEventReader<R> {
+	type Item = Result<SprEvent, SpriteError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self.next_event() {
+			Ok(event) => event.map(Ok),
+			Err(err) => {
+				self.done = true;
+				Some(Err(err))
+			}
+		}
+	}
+}
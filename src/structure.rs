@@ -0,0 +1,229 @@
+use crate::*;
+
+/// Deepest pointer chain the format has: header, texture set, texture, mip.
+pub const MAX_DEPTH: u32 = 4;
+
+struct Checker<'a, R> {
+	reader: &'a mut R,
+	endian: Endian,
+	len: u64,
+	visited: Vec<(Range<u64>, &'static str)>,
+}
+
+fn malformed(message: String) -> SpriteError {
+	SpriteError::Malformed(message)
+}
+
+impl<R: io::Read + io::Seek> Checker<'_, R> {
+	/// Claims `size` bytes at `pos` for `what`, failing if they run off the end of the file or
+	/// overlap anything claimed before.
+	fn claim(&mut self, what: &'static str, pos: u64, size: u64) -> Result<(), SpriteError> {
+		let end = pos
+			.checked_add(size)
+			.filter(|end| *end <= self.len)
+			.ok_or_else(|| {
+				malformed(format!(
+					"{what} at {pos:#x} ({size:#x} bytes) runs past the end of the file ({:#x})",
+					self.len
+				))
+			})?;
+		if size == 0 {
+			return Ok(());
+		}
+		// Name tables only hold pointers, some tools point both at the same strings or tables
+		let shareable = |what: &str| what.ends_with("names");
+		if let Some((range, other)) = self.visited.iter().find(|(range, other)| {
+			pos < range.end && range.start < end && !(shareable(what) && shareable(other))
+		}) {
+			return Err(malformed(format!(
+				"{what} at {pos:#x}..{end:#x} overlaps {other} at {:#x}..{:#x}",
+				range.start, range.end
+			)));
+		}
+		self.visited.push((pos..end, what));
+		Ok(())
+	}
+
+	/// Follows a pointer stored in a structure `parent_depth` levels deep ending at `parent_end`.
+	/// Pointers may only go forward and only `MAX_DEPTH` levels deep, counting the header.
+	fn follow(
+		&mut self,
+		what: &'static str,
+		target: u64,
+		parent_end: u64,
+		parent_depth: u32,
+	) -> Result<u64, SpriteError> {
+		let depth = parent_depth + 1;
+		if depth > MAX_DEPTH {
+			return Err(malformed(format!(
+				"{what} at {target:#x} is nested {depth} pointers deep, the format allows {MAX_DEPTH}"
+			)));
+		}
+		if target < parent_end {
+			return Err(malformed(format!(
+				"{what} points backwards to {target:#x} from a structure ending at {parent_end:#x}"
+			)));
+		}
+		if target >= self.len {
+			return Err(malformed(format!(
+				"{what} points to {target:#x}, past the end of the file ({:#x})",
+				self.len
+			)));
+		}
+		self.reader.seek(SeekFrom::Start(target))?;
+		Ok(target)
+	}
+
+	fn read_u32(&mut self) -> Result<u32, SpriteError> {
+		Ok(self.reader.read_type(self.endian)?)
+	}
+
+	fn table(&mut self, what: &'static str, pos: u64, count: u32) -> Result<(), SpriteError> {
+		self.claim(what, pos, count as u64 * 4)
+	}
+
+	fn check(&mut self) -> Result<(), SpriteError> {
+		let start = self.reader.stream_position()?;
+		self.claim("Header", start, 0x20)?;
+		let header_end = start + 0x20;
+		let depth = 1;
+		let _flags = self.read_u32()?;
+		let textures_pos = self.read_u32()? as u64;
+		let texture_count = self.read_u32()?;
+		let sprite_count = self.read_u32()?;
+		let sprites_pos = self.read_u32()? as u64;
+		let texture_names_pos = self.read_u32()? as u64;
+		let sprite_names_pos = self.read_u32()? as u64;
+		let sprite_extras_pos = self.read_u32()? as u64;
+
+		if sprite_count > 0 {
+			self.follow("Sprite table", sprites_pos, header_end, depth)?;
+			self.claim("Sprite table", sprites_pos, sprite_count as u64 * 40)?;
+			self.follow("Sprite extras", sprite_extras_pos, header_end, depth)?;
+			self.claim("Sprite extras", sprite_extras_pos, sprite_count as u64 * 8)?;
+			self.follow("Sprite names", sprite_names_pos, header_end, depth)?;
+			self.table("Sprite names", sprite_names_pos, sprite_count)?;
+		}
+		if texture_count > 0 {
+			self.follow("Texture names", texture_names_pos, header_end, depth)?;
+			self.table("Texture names", texture_names_pos, texture_count)?;
+		}
+
+		if texture_count == 0 && textures_pos == 0 {
+			return Ok(());
+		}
+		let tex_set = self.follow("Texture set", textures_pos, header_end, depth)?;
+		self.claim("Texture set header", tex_set, 12)?;
+		let mut magic = [0u8; 4];
+		self.reader.read_exact(&mut magic)?;
+		if &magic != b"TXP\x03" {
+			return Err(malformed(format!("No texture set at {tex_set:#x}")));
+		}
+		let tex_set_count = self.read_u32()?;
+		let _padding = self.read_u32()?;
+		if tex_set_count != texture_count {
+			return Err(malformed(format!(
+				"Header lists {texture_count} textures but the texture set has {tex_set_count}"
+			)));
+		}
+		self.table("Texture offsets", tex_set + 12, tex_set_count)?;
+		let offsets = (0..tex_set_count)
+			.map(|_| self.read_u32())
+			.collect::<Result<Vec<_>, _>>()?;
+		let tex_set_end = tex_set + 12 + tex_set_count as u64 * 4;
+		for offset in offsets {
+			self.check_texture(tex_set + offset as u64, tex_set_end, depth + 1)?;
+		}
+		Ok(())
+	}
+
+	fn check_texture(
+		&mut self,
+		pos: u64,
+		parent_end: u64,
+		parent_depth: u32,
+	) -> Result<(), SpriteError> {
+		let depth = parent_depth + 1;
+		self.follow("Texture", pos, parent_end, parent_depth)?;
+		self.claim("Texture header", pos, 12)?;
+		let mut magic = [0u8; 4];
+		self.reader.read_exact(&mut magic)?;
+		let _mip_maps = self.read_u32()?;
+		let mut counts = [0u8; 4];
+		self.reader.read_exact(&mut counts)?;
+		let [mip_levels, array_size, _, _] = counts;
+		let mip_count = match &magic {
+			b"TXP\x04" => mip_levels as u32,
+			b"TXP\x05" if array_size > 0 => (mip_levels / array_size) as u32,
+			b"TXP\x05" => return Err(malformed(format!("No texture at {pos:#x}"))),
+			// Other TXP versions are kept as raw bytes, their layout is unknown
+			[b'T', b'X', b'P', _] => return Ok(()),
+			_ => return Err(malformed(format!("No texture at {pos:#x}"))),
+		};
+		let count = mip_count * array_size as u32;
+		self.table("Mip offsets", pos + 12, count)?;
+		let offsets = (0..count)
+			.map(|_| self.read_u32())
+			.collect::<Result<Vec<_>, _>>()?;
+		let texture_end = pos + 12 + count as u64 * 4;
+		for offset in offsets {
+			let mip = self.follow("Mip", pos + offset as u64, texture_end, depth)?;
+			self.claim("Mip header", mip, 24)?;
+			self.reader.read_exact(&mut magic)?;
+			if &magic != b"TXP\x02" {
+				return Err(malformed(format!("No mip at {mip:#x}")));
+			}
+			self.reader.seek(SeekFrom::Current(16))?;
+			let data_size = self.read_u32()?;
+			self.claim("Mip data", mip + 24, data_size as u64)?;
+		}
+		Ok(())
+	}
+}
+
+/// Walks every pointer in a set before it is parsed, so crafted files with pointers that loop
+/// back, overlap other structures or run off the end fail with a clear message instead of
+/// sending the parser into huge or repeated reads. The reader is left where it started.
+pub fn check_structure<R: io::Read + io::Seek>(reader: &mut R) -> Result<(), SpriteError> {
+	structure_end(reader).map(|_| ())
+}
+
+/// [`check_structure`] for a set known to be stored in `endian`.
+pub fn check_structure_with_endian<R: io::Read + io::Seek>(
+	reader: &mut R,
+	endian: Endian,
+) -> Result<(), SpriteError> {
+	structure_end_with_endian(reader, endian).map(|_| ())
+}
+
+/// [`check_structure`], returning where the last structure it found ends. Name strings aren't
+/// walked so may lie past it.
+pub(crate) fn structure_end<R: io::Read + io::Seek>(reader: &mut R) -> Result<u64, SpriteError> {
+	let endian = detect_endianness(reader)?;
+	structure_end_with_endian(reader, endian)
+}
+
+fn structure_end_with_endian<R: io::Read + io::Seek>(
+	reader: &mut R,
+	endian: Endian,
+) -> Result<u64, SpriteError> {
+	let start = reader.stream_position()?;
+	let len = reader.seek(SeekFrom::End(0))?;
+	reader.seek(SeekFrom::Start(start))?;
+	let mut checker = Checker {
+		reader: &mut *reader,
+		endian,
+		len,
+		visited: vec![],
+	};
+	let result = checker.check().map(|_| {
+		checker
+			.visited
+			.iter()
+			.map(|(range, _)| range.end)
+			.max()
+			.unwrap_or(start)
+	});
+	reader.seek(SeekFrom::Start(start))?;
+	result
+}
@@ -0,0 +1,160 @@
+//! Rendering text straight into sprite regions, for regenerating labels from translations.
+//! Only available with the `text` feature.
+use crate::*;
+
+pub use fontdue::Font;
+
+pub fn load_font(data: &[u8]) -> Result<Font, SpriteError> {
+	Font::from_bytes(data, fontdue::FontSettings::default())
+		.map_err(|err| SpriteError::Import(format!("Could not load font: {err}")))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+	Left,
+	Center,
+	Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextStyle {
+	/// Pixel size to start from, text that doesn't fit is shrunk down to `min_size`
+	pub size: f32,
+	pub min_size: f32,
+	pub color: [u8; 4],
+	pub align: TextAlign,
+	/// Clear the region to transparent first instead of drawing over the old label
+	pub clear: bool,
+	/// Pixels kept free on every side of the region
+	pub padding: u32,
+	/// Which whole pixels a fractional region covers
+	pub sub_pixel: SubPixel,
+}
+
+impl Default for TextStyle {
+	fn default() -> Self {
+		Self {
+			size: 32.0,
+			min_size: 8.0,
+			color: [255, 255, 255, 255],
+			align: TextAlign::Center,
+			clear: true,
+			padding: 2,
+			sub_pixel: SubPixel::Floor,
+		}
+	}
+}
+
+fn line_width(font: &Font, text: &str, size: f32) -> f32 {
+	text.chars()
+		.map(|c| font.metrics(c, size).advance_width)
+		.sum()
+}
+
+impl SprSet {
+	/// Draws `text` as a single line into `sprite`'s region of its texture, vertically centred
+	/// and shrunk until it fits the width.
+	pub fn render_text(
+		&mut self,
+		font: &Font,
+		sprite: &str,
+		text: &str,
+		style: &TextStyle,
+	) -> Result<(), SpriteError> {
+		let name = sprite;
+		let sprite = self
+			.sprites
+			.get(name)
+			.ok_or_else(|| SpriteError::not_in_set("Sprite", name))?;
+		let texture = self
+			.textures
+			.get_mut(&sprite.texture_name)
+			.ok_or_else(|| SpriteError::missing_texture(name, &sprite.texture_name))?;
+		let (x0, y0, width, height) = style.sub_pixel.bounds(&sprite.pixel_region);
+		let inner_width = width.saturating_sub(style.padding * 2) as f32;
+		let inner_height = height.saturating_sub(style.padding * 2) as f32;
+
+		let mut size = style.size.min(inner_height);
+		while size > style.min_size && line_width(font, text, size) > inner_width {
+			size -= 1.0;
+		}
+		let (ascent, descent) = font
+			.horizontal_line_metrics(size)
+			.map_or((size, 0.0), |metrics| (metrics.ascent, metrics.descent));
+		let text_width = line_width(font, text, size);
+		let mut pen_x = style.padding as f32
+			+ match style.align {
+				TextAlign::Left => 0.0,
+				TextAlign::Center => (inner_width - text_width) / 2.0,
+				TextAlign::Right => inner_width - text_width,
+			};
+		let baseline = style.padding as f32 + (inner_height - (ascent - descent)) / 2.0 + ascent;
+
+		let mut image = texture.image.to_rgba8();
+		if style.clear {
+			for y in y0..(y0 + height).min(image.height()) {
+				for x in x0..(x0 + width).min(image.width()) {
+					image.put_pixel(x, y, image::Rgba([0, 0, 0, 0]));
+				}
+			}
+		}
+		for c in text.chars() {
+			let (metrics, coverage) = font.rasterize(c, size);
+			let glyph_x = (pen_x + metrics.xmin as f32).round() as i64;
+			let glyph_y = (baseline - metrics.height as f32 - metrics.ymin as f32).round() as i64;
+			for (i, alpha) in coverage.iter().enumerate() {
+				let x = glyph_x + (i % metrics.width.max(1)) as i64;
+				let y = glyph_y + (i / metrics.width.max(1)) as i64;
+				// Clip to the sprite so neighbours in the atlas are left alone
+				if *alpha == 0 || x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+					continue;
+				}
+				let (x, y) = (x0 + x as u32, y0 + y as u32);
+				if x >= image.width() || y >= image.height() {
+					continue;
+				}
+				let pixel = image.get_pixel_mut(x, y);
+				let alpha = *alpha as u32 * style.color[3] as u32 / 255;
+				for channel in 0..3 {
+					pixel.0[channel] = ((style.color[channel] as u32 * alpha
+						+ pixel.0[channel] as u32 * (255 - alpha))
+						/ 255) as u8;
+				}
+				pixel.0[3] = (alpha + pixel.0[3] as u32 * (255 - alpha) / 255) as u8;
+			}
+			pen_x += metrics.advance_width;
+		}
+		texture.image = DynamicImage::ImageRgba8(image);
+		Ok(())
+	}
+
+	/// Renders every `sprite,text` line of a CSV (the text may contain further commas; blank
+	/// lines and ones starting with `#` are skipped). Returns the sprites drawn into.
+	pub fn render_text_csv<R: std::io::BufRead>(
+		&mut self,
+		font: &Font,
+		reader: R,
+		style: &TextStyle,
+	) -> Result<Vec<String>, SpriteError> {
+		let mut rendered = vec![];
+		for (number, line) in reader.lines().enumerate() {
+			let line = line?;
+			if line.trim().is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let (sprite, text) = line.split_once(',').ok_or_else(|| {
+				SpriteError::Import(format!("Text line {} has no comma: {line}", number + 1))
+			})?;
+			let sprite = sprite.trim();
+			if !self.sprites.contains_key(sprite) {
+				return Err(SpriteError::Import(format!(
+					"Text line {} names unknown sprite {sprite}",
+					number + 1
+				)));
+			}
+			self.render_text(font, sprite, text.trim(), style)?;
+			rendered.push(sprite.to_string());
+		}
+		Ok(rendered)
+	}
+}
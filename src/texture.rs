@@ -0,0 +1,519 @@
+//! Decoding stored texture data to images and encoding images back.
+use crate::*;
+
+/// A decoded texture along with what it was stored as in the source file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+	pub image: DynamicImage,
+	pub original_format: TextureFormat,
+	pub mip_count: u32,
+	pub array_size: u32,
+	/// Stands in for texture data that could not be decoded, see [`LoadOptions::lenient`]
+	pub placeholder: bool,
+	/// Texture in a TXP version this crate can't decode, written back byte for byte
+	pub raw: Option<RawTexture>,
+	/// Index bytes of every mip as stored, every mip of the first layer then the second and so
+	/// on. Empty for new textures, the writer then fills them in itself.
+	pub mip_indices: Vec<MipIndex>,
+	/// Array layers after the first, each the same size as `image`
+	pub layers: Vec<DynamicImage>,
+	/// Written as a `TXP\x05` cube map, `image` and `layers` are the six faces
+	pub cubemap: bool,
+}
+
+/// The index and array index bytes of a mip header, some loaders check these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct MipIndex {
+	pub index: u8,
+	pub array_index: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawTexture {
+	pub version: u8,
+	/// Everything from the TXP magic up to the next structure in the file
+	pub data: Vec<u8>,
+}
+
+/// Which way up texture data is stored. Images in memory and sprite regions are always top-down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum FlipConvention {
+	/// Whatever the layout stores, bottom-up for the classic layout and top-down for the `SPRC`
+	/// ones, see [`FlipConvention::resolve`]
+	#[default]
+	Auto,
+	BottomUp,
+	/// No flipping at all, for data authored top-down
+	TopDown,
+}
+
+impl FlipConvention {
+	/// What `Auto` means for `variant`, forced conventions stay as they are.
+	pub fn resolve(self, variant: FormatVariant) -> Self {
+		match (self, variant) {
+			(Self::Auto, FormatVariant::Classic) => Self::BottomUp,
+			(Self::Auto, FormatVariant::Modern | FormatVariant::ModernX) => Self::TopDown,
+			(flip, _) => flip,
+		}
+	}
+
+	/// Whether rows are stored bottom-up. `Auto` is taken as the classic layout, which is all the
+	/// readers and writers outside [`FormatVariant`] conversion see.
+	pub fn is_bottom_up(self) -> bool {
+		self.resolve(FormatVariant::Classic) == Self::BottomUp
+	}
+
+	fn apply(self, image: image::RgbaImage) -> image::RgbaImage {
+		if self.is_bottom_up() {
+			image::imageops::flip_vertical(&image)
+		} else {
+			image
+		}
+	}
+}
+
+/// How hard the block compressor searches, slower finds better endpoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Encoder {
+	Fast,
+	#[default]
+	Balanced,
+	Best,
+}
+
+impl Encoder {
+	fn params(self) -> texpresso::Params {
+		let algorithm = match self {
+			Self::Fast => texpresso::Algorithm::RangeFit,
+			Self::Balanced => texpresso::Algorithm::ClusterFit,
+			Self::Best => texpresso::Algorithm::IterativeClusterFit,
+		};
+		texpresso::Params {
+			algorithm,
+			..Default::default()
+		}
+	}
+}
+
+impl Texture {
+	pub fn new(image: DynamicImage) -> Self {
+		Self {
+			image,
+			original_format: TextureFormat::RGBA8,
+			mip_count: 1,
+			array_size: 1,
+			placeholder: false,
+			raw: None,
+			mip_indices: vec![],
+			layers: vec![],
+			cubemap: false,
+		}
+	}
+
+	/// Magenta and black checkerboard in 8 pixel squares, hard to mistake for real art.
+	pub fn placeholder(width: u32, height: u32) -> Self {
+		let image = image::RgbaImage::from_fn(width.max(1), height.max(1), |x, y| {
+			if (x / 8 + y / 8) % 2 == 0 {
+				image::Rgba([255, 0, 255, 255])
+			} else {
+				image::Rgba([0, 0, 0, 255])
+			}
+		});
+		Self {
+			placeholder: true,
+			..Self::new(DynamicImage::ImageRgba8(image))
+		}
+	}
+}
+
+impl From<DynamicImage> for Texture {
+	fn from(value: DynamicImage) -> Self {
+		Self::new(value)
+	}
+}
+
+pub(crate) fn decode_tex(
+	tex: &TexReader,
+	flip: FlipConvention,
+) -> Result<DynamicImage, SpriteError> {
+	let (layers, depth, mip_maps, array_size, is_cubemap) = match &tex {
+		TexReader::Unknown(version) => {
+			return Err(SpriteError::Malformed(format!(
+				"Texture uses unknown TXP version {version}"
+			)))
+		}
+		TexReader::Tex2d(texture) => (
+			&texture.mip_map_array,
+			texture.depth as u32,
+			texture.mip_maps as u32,
+			texture.array_size as u32,
+			false,
+		),
+		TexReader::TexCubeMap(cubemap) => (
+			&cubemap.mip_map_array,
+			cubemap.depth as u32,
+			cubemap.mip_maps as u32,
+			cubemap.array_size as u32,
+			true,
+		),
+	};
+	let no_mips = || SpriteError::Malformed("Texture has a layer without mips".to_string());
+	let first = layers
+		.first()
+		.and_then(|layer| layer.mip_maps.first())
+		.ok_or_else(no_mips)?;
+	let params = ddsfile::NewDxgiParams {
+		height: first.height as u32,
+		width: first.width as u32,
+		depth: Some(depth),
+		format: first.format.to_dxgi_format(),
+		mipmap_levels: Some(mip_maps),
+		array_layers: Some(array_size),
+		caps2: is_cubemap.then_some(ddsfile::Caps2::CUBEMAP),
+		is_cubemap,
+		resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+		alpha_mode: ddsfile::AlphaMode::Unknown,
+	};
+	let mut dds = new_dds(params, "decoded texture")?;
+	for (i, layer) in layers.iter().enumerate() {
+		let dest = dds.get_mut_data(i as u32)?;
+		let src = &layer.mip_maps.first().ok_or_else(no_mips)?.data;
+		check_mip_len(src, dest)?;
+		dest[..src.len()].copy_from_slice(src);
+	}
+	dds_to_dynamic(&dds, flip)
+		.ok_or_else(|| SpriteError::Dds(format!("Cannot decode {:?} textures", first.format)))
+}
+
+/// A block format either texpresso or [`bptc`] decodes.
+#[derive(Debug, Clone, Copy)]
+enum BlockFormat {
+	Texpresso(texpresso::Format),
+	Bptc(bptc::Bptc),
+}
+
+impl BlockFormat {
+	fn from_format(format: TextureFormat) -> Option<Self> {
+		let format = match format {
+			TextureFormat::DXT1 | TextureFormat::DXT1a => texpresso::Format::Bc1,
+			TextureFormat::DXT3 => texpresso::Format::Bc2,
+			TextureFormat::DXT5 => texpresso::Format::Bc3,
+			TextureFormat::ATI1 => texpresso::Format::Bc4,
+			TextureFormat::ATI2 => texpresso::Format::Bc5,
+			_ => return bptc::Bptc::from_format(format).map(Self::Bptc),
+		};
+		Some(Self::Texpresso(format))
+	}
+
+	fn block_size(self) -> usize {
+		match self {
+			Self::Texpresso(format) => format.block_size(),
+			Self::Bptc(format) => format.block_size(),
+		}
+	}
+
+	fn decompress(self, data: &[u8], width: usize, height: usize, output: &mut [u8]) {
+		match self {
+			Self::Texpresso(format) => format.decompress(data, width, height, output),
+			Self::Bptc(format) => format.decompress(data, width, height, output),
+		}
+	}
+
+	/// `None` for BC6H, which can't be encoded.
+	fn compress(self, image: &image::RgbaImage, encoder: Encoder) -> Option<Vec<u8>> {
+		match self {
+			Self::Texpresso(format) => {
+				let (width, height) = (image.width() as usize, image.height() as usize);
+				let mut out = vec![0u8; format.compressed_size(width, height)];
+				format.compress(image.as_raw(), width, height, encoder.params(), &mut out);
+				Some(out)
+			}
+			Self::Bptc(bptc::Bptc::Bc7) => Some(bptc::encode_bc7(image, encoder)),
+			Self::Bptc(bptc::Bptc::Bc6h { .. }) => None,
+		}
+	}
+}
+
+fn dds_block_format(format: &DxgiFormat) -> Option<BlockFormat> {
+	let format = match format {
+		DxgiFormat::BC1_UNorm => texpresso::Format::Bc1,
+		DxgiFormat::BC2_UNorm => texpresso::Format::Bc2,
+		DxgiFormat::BC3_UNorm => texpresso::Format::Bc3,
+		DxgiFormat::BC4_UNorm => texpresso::Format::Bc4,
+		DxgiFormat::BC5_UNorm => texpresso::Format::Bc5,
+		_ => return bptc::Bptc::from_dxgi_format(format).map(BlockFormat::Bptc),
+	};
+	Some(BlockFormat::Texpresso(format))
+}
+
+pub(crate) fn dds_to_dynamic(texture: &Dds, flip: FlipConvention) -> Option<image::DynamicImage> {
+	let (width, height) = (texture.header.width, texture.header.height);
+	if matches!(texture.get_dxgi_format()?, DxgiFormat::R8G8B8A8_UNorm) {
+		let image = decode_region(
+			&texture.data,
+			TextureFormat::RGBA8,
+			width,
+			height,
+			(0, 0, width, height),
+			flip,
+		)?;
+		return Some(DynamicImage::ImageRgba8(image));
+	}
+	let format = dds_block_format(&texture.get_dxgi_format()?)?;
+	let mut decompressed =
+		vec![0u8; 4 * texture.header.width as usize * texture.header.height as usize];
+	format.decompress(
+		&texture.data,
+		texture.header.width as usize,
+		texture.header.height as usize,
+		&mut decompressed,
+	);
+	let buffer =
+		image::RgbaImage::from_raw(texture.header.width, texture.header.height, decompressed)?;
+	Some(DynamicImage::ImageRgba8(flip.apply(buffer)))
+}
+
+/// Decodes just the 4x4 blocks covering `region` (x, y, width, height in the same top-down
+/// coordinates as sprite pixel regions) out of a mip's raw data stored the way `flip` says.
+pub fn decode_region(
+	data: &[u8],
+	format: TextureFormat,
+	width: u32,
+	height: u32,
+	region: (u32, u32, u32, u32),
+	flip: FlipConvention,
+) -> Option<image::RgbaImage> {
+	let (x, y, region_width, region_height) = region;
+	if region_width == 0
+		|| region_height == 0
+		|| x + region_width > width
+		|| y + region_height > height
+	{
+		return None;
+	}
+	let stored_y = if flip.is_bottom_up() {
+		height - y - region_height
+	} else {
+		y
+	};
+
+	if format == TextureFormat::RGBA8 {
+		let mut out = Vec::with_capacity((region_width * region_height * 4) as usize);
+		for row in stored_y..stored_y + region_height {
+			let start = ((row * width + x) * 4) as usize;
+			out.extend_from_slice(data.get(start..start + (region_width * 4) as usize)?);
+		}
+		let image = image::RgbaImage::from_raw(region_width, region_height, out)?;
+		return Some(flip.apply(image));
+	}
+
+	let bc_format = BlockFormat::from_format(format)?;
+	let block_size = bc_format.block_size();
+	let blocks_wide = ((width + 3) / 4) as usize;
+	let (block_x0, block_x1) = (x / 4, (x + region_width + 3) / 4);
+	let (block_y0, block_y1) = (stored_y / 4, (stored_y + region_height + 3) / 4);
+
+	let mut blocks = vec![];
+	for block_y in block_y0..block_y1 {
+		let start = (block_y as usize * blocks_wide + block_x0 as usize) * block_size;
+		let end = start + (block_x1 - block_x0) as usize * block_size;
+		blocks.extend_from_slice(data.get(start..end)?);
+	}
+	let decoded_width = (block_x1 - block_x0) * 4;
+	let decoded_height = (block_y1 - block_y0) * 4;
+	let mut decoded = vec![0u8; (decoded_width * decoded_height * 4) as usize];
+	bc_format.decompress(
+		&blocks,
+		decoded_width as usize,
+		decoded_height as usize,
+		&mut decoded,
+	);
+	let decoded = image::RgbaImage::from_raw(decoded_width, decoded_height, decoded)?;
+	let cropped = image::imageops::crop_imm(
+		&decoded,
+		x - block_x0 * 4,
+		stored_y - block_y0 * 4,
+		region_width,
+		region_height,
+	)
+	.to_image();
+	Some(flip.apply(cropped))
+}
+
+impl TextureFormat {
+	/// Whether whole textures stored in this format can be decoded when reading a set.
+	pub fn can_decode(&self) -> bool {
+		dds_block_format(&self.to_dxgi_format()).is_some()
+	}
+
+	/// Whether [`decode_region`] can decode this format, which [`SprSetRef`] and single sprite
+	/// reads rely on.
+	pub fn can_decode_region(&self) -> bool {
+		*self == Self::RGBA8 || BlockFormat::from_format(*self).is_some()
+	}
+
+	/// Whether [`SprSet::to_writer_with_options`] can encode to this format.
+	pub fn can_encode(&self) -> bool {
+		matches!(
+			self,
+			Self::RGBA8
+				| Self::DXT1 | Self::DXT1a
+				| Self::DXT3 | Self::DXT5
+				| Self::ATI1 | Self::ATI2
+				| Self::BC7
+		)
+	}
+}
+
+pub(crate) fn encode_texture(
+	texture: &image::DynamicImage,
+	format: TextureFormat,
+	flip: FlipConvention,
+	encoder: Encoder,
+) -> Result<Dds, SpriteError> {
+	let bc_format = match format {
+		TextureFormat::RGBA8 => return dynamic_to_dds(texture, flip),
+		TextureFormat::BC7 => {
+			let buf = bptc::encode_bc7(&flip.apply(texture.to_rgba8()), encoder);
+			return compressed_dds(buf, texture.width(), texture.height(), format);
+		}
+		TextureFormat::DXT1 | TextureFormat::DXT1a => texpresso::Format::Bc1,
+		TextureFormat::DXT3 => texpresso::Format::Bc2,
+		TextureFormat::DXT5 => texpresso::Format::Bc3,
+		TextureFormat::ATI1 => texpresso::Format::Bc4,
+		TextureFormat::ATI2 => texpresso::Format::Bc5,
+		_ => {
+			return Err(SpriteError::Validation(
+				ErrorCode::UnsupportedTextureFormat,
+				format!("Cannot encode textures as {format:?}"),
+			))
+		}
+	};
+	let rgba8 = flip.apply(texture.to_rgba8());
+	let rgba = rgba8.as_bytes();
+
+	let width = texture.width() as usize;
+	let height = texture.height() as usize;
+
+	let compressed_size = bc_format.compressed_size(width, height);
+	let params = encoder.params();
+
+	let mut buf = vec![0u8; compressed_size];
+	bc_format.compress(rgba, width, height, params, &mut buf);
+	compressed_dds(buf, width as u32, height as u32, format)
+}
+
+/// Encodes `blocks` (column and row in stored order) of `texture` again with [`Encoder::Best`]
+/// over the ones in `dds`, which [`encode_texture`] made from the same image.
+pub(crate) fn refine_blocks(
+	dds: &mut Dds,
+	texture: &image::DynamicImage,
+	format: TextureFormat,
+	flip: FlipConvention,
+	blocks: &[(u32, u32)],
+) {
+	let Some(block_format) = BlockFormat::from_format(format) else {
+		return;
+	};
+	let rgba = flip.apply(texture.to_rgba8());
+	let (width, height) = rgba.dimensions();
+	if width == 0 || height == 0 {
+		return;
+	}
+	let blocks_wide = (width + 3) / 4;
+	let block_size = block_format.block_size();
+	for &(column, row) in blocks {
+		let pixels = image::RgbaImage::from_fn(4, 4, |x, y| {
+			*rgba.get_pixel(
+				(column * 4 + x).min(width - 1),
+				(row * 4 + y).min(height - 1),
+			)
+		});
+		let Some(encoded) = block_format.compress(&pixels, Encoder::Best) else {
+			return;
+		};
+		let start = (row * blocks_wide + column) as usize * block_size;
+		if let Some(dest) = dds.data.get_mut(start..start + block_size) {
+			dest.copy_from_slice(&encoded);
+		}
+	}
+}
+
+fn compressed_dds(
+	buf: Vec<u8>,
+	width: u32,
+	height: u32,
+	format: TextureFormat,
+) -> Result<Dds, SpriteError> {
+	let mut dds = new_dds(
+		ddsfile::NewDxgiParams {
+			height,
+			width,
+			depth: None,
+			format: format.to_dxgi_format(),
+			mipmap_levels: None,
+			array_layers: None,
+			caps2: None,
+			is_cubemap: false,
+			resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+			alpha_mode: ddsfile::AlphaMode::Straight,
+		},
+		"encoded texture",
+	)?;
+	dds.data = buf;
+	Ok(dds)
+}
+
+/// `Dds::new_dxgi`, refusing sizes it can't describe and saying what was being built when it fails.
+pub(crate) fn new_dds(params: ddsfile::NewDxgiParams, what: &str) -> Result<Dds, SpriteError> {
+	let (width, height, format) = (params.width, params.height, params.format);
+	if width == 0 || height == 0 {
+		return Err(SpriteError::Dds(format!(
+			"Cannot build {what}, it is {width}x{height}"
+		)));
+	}
+	Dds::new_dxgi(params).map_err(|err| {
+		SpriteError::Dds(format!(
+			"Cannot build {what} ({width}x{height} {format:?}): {err}"
+		))
+	})
+}
+
+fn check_mip_len(src: &[u8], dest: &[u8]) -> Result<(), SpriteError> {
+	if src.len() > dest.len() {
+		return Err(SpriteError::Malformed(format!(
+			"Mip has {} bytes where its size allows {}",
+			src.len(),
+			dest.len()
+		)));
+	}
+	Ok(())
+}
+
+pub(crate) fn dynamic_to_dds(
+	texture: &image::DynamicImage,
+	flip: FlipConvention,
+) -> Result<Dds, SpriteError> {
+	let rgba8 = flip.apply(texture.to_rgba8());
+	let rgba = rgba8.as_bytes();
+
+	let width = texture.width();
+	let height = texture.height();
+	let mut dds = new_dds(
+		ddsfile::NewDxgiParams {
+			height: height as u32,
+			width: width as u32,
+			depth: None,
+			format: ddsfile::DxgiFormat::R8G8B8A8_UNorm,
+			mipmap_levels: None,
+			array_layers: None,
+			caps2: None,
+			is_cubemap: false,
+			resource_dimension: ddsfile::D3D10ResourceDimension::Texture2D,
+			alpha_mode: ddsfile::AlphaMode::PreMultiplied,
+		},
+		"RGBA texture",
+	)?;
+	dds.data = rgba.to_vec();
+	Ok(dds)
+}
@@ -0,0 +1,111 @@
+//! Where sprites are used, as far as outside data (menu layouts, scripts, spreadsheets) says.
+//! Nothing in the file format records this, so it is only ever filled in by the caller.
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Sprite name to the places (free-form strings such as `"menu/song_select"`) that use it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageMap {
+	usages: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl UsageMap {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn add(&mut self, sprite: &str, context: &str) {
+		self.usages
+			.entry(sprite.to_string())
+			.or_default()
+			.insert(context.to_string());
+	}
+
+	pub fn rename_sprite(&mut self, old: &str, new: &str) {
+		if let Some(contexts) = self.usages.remove(old) {
+			self.usages
+				.entry(new.to_string())
+				.or_default()
+				.extend(contexts);
+		}
+	}
+
+	pub fn remove_context(&mut self, context: &str) {
+		for contexts in self.usages.values_mut() {
+			contexts.remove(context);
+		}
+		self.usages.retain(|_, contexts| !contexts.is_empty());
+	}
+
+	pub fn contexts_of(&self, sprite: &str) -> impl Iterator<Item = &str> {
+		self.usages
+			.get(sprite)
+			.into_iter()
+			.flatten()
+			.map(String::as_str)
+	}
+
+	pub fn sprites_in<'a>(&'a self, context: &'a str) -> impl Iterator<Item = &'a str> {
+		self.usages
+			.iter()
+			.filter(move |(_, contexts)| contexts.contains(context))
+			.map(|(sprite, _)| sprite.as_str())
+	}
+
+	pub fn is_used(&self, sprite: &str) -> bool {
+		self.usages.contains_key(sprite)
+	}
+
+	pub fn sprites(&self) -> impl Iterator<Item = &str> {
+		self.usages.keys().map(String::as_str)
+	}
+
+	/// Reads `context,sprite` lines, skipping blank lines and ones starting with `#`. `map` sees
+	/// every row first and can rewrite it (strip set prefixes, rename columns) or drop it by
+	/// returning `None`. Returns how many usages were added.
+	pub fn read_csv<R: std::io::BufRead, F: FnMut(&str, &str) -> Option<(String, String)>>(
+		&mut self,
+		reader: R,
+		mut map: F,
+	) -> Result<usize, SpriteError> {
+		let mut added = 0;
+		for (number, line) in reader.lines().enumerate() {
+			let line = line?;
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let (context, sprite) = line.split_once(',').ok_or_else(|| {
+				SpriteError::Import(format!("Usage line {} has no comma: {line}", number + 1))
+			})?;
+			if let Some((context, sprite)) = map(context.trim(), sprite.trim()) {
+				self.add(&sprite, &context);
+				added += 1;
+			}
+		}
+		Ok(added)
+	}
+}
+
+impl SprSet {
+	/// Sprites no recorded usage mentions, sorted.
+	pub fn unused_sprites(&self) -> Vec<&str> {
+		let mut unused = self
+			.sprites
+			.keys()
+			.filter(|name| !self.usage.is_used(name))
+			.map(String::as_str)
+			.collect::<Vec<_>>();
+		unused.sort();
+		unused
+	}
+
+	/// Names the usage data mentions that aren't sprites of this set, usually typos or sprites
+	/// that were renamed or removed.
+	pub fn unknown_usages(&self) -> Vec<&str> {
+		self.usage
+			.sprites()
+			.filter(|name| !self.sprites.contains_key(*name))
+			.collect()
+	}
+}
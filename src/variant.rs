@@ -0,0 +1,580 @@
+//! Which generation of the layout a file uses. Everything else in the crate reads and writes the
+//! classic layout; the modern one (F 2nd, X and later) wraps the same set in `SPRC` sections
+//! with the textures in their own `TXPC` section and relocation tables for every pointer, so it
+//! is converted to and from the classic layout here.
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FormatVariant {
+	/// Bare header at the start of the file with 32 bit pointers, as in AFT, FT and MM+
+	#[default]
+	Classic,
+	/// `SPRC` section wrapped with 32 bit pointers, as in F 2nd
+	Modern,
+	/// `SPRC` section wrapped with 64 bit pointers and larger section headers, as in X
+	ModernX,
+}
+
+const SPRITE_SIZE: usize = 0x28;
+const EXTRA_SIZE: usize = 8;
+const SECTION_FLAGS: u32 = 0x1000_0000;
+const BIG_ENDIAN_FLAG: u32 = 0x0800_0000;
+const BODY_ALIGNMENT: usize = 16;
+
+impl FormatVariant {
+	pub const ALL: [FormatVariant; 3] = [Self::Classic, Self::Modern, Self::ModernX];
+
+	pub fn detect(data: &[u8]) -> Self {
+		match data.get(..4) {
+			Some(b"SPRC") if data.get(8..12) == Some(&0x40u32.to_le_bytes()) => Self::ModernX,
+			Some(b"SPRC") => Self::Modern,
+			_ => Self::Classic,
+		}
+	}
+
+	fn pointer_size(&self) -> usize {
+		match self {
+			Self::ModernX => 8,
+			_ => 4,
+		}
+	}
+
+	fn section_header_size(&self) -> usize {
+		match self {
+			Self::ModernX => 0x40,
+			_ => 0x20,
+		}
+	}
+}
+
+/// Everything in a set that doesn't depend on the layout around it, as stored bytes in the set's
+/// byte order.
+struct SetParts<'a> {
+	flags: u32,
+	texture_count: u32,
+	sprites: &'a [u8],
+	extras: &'a [u8],
+	texture_names: Vec<&'a [u8]>,
+	sprite_names: Vec<&'a [u8]>,
+	/// The `TXP\x03` texture set, offsets inside are relative to its start
+	textures: &'a [u8],
+}
+
+impl SetParts<'_> {
+	fn sprite_count(&self) -> usize {
+		self.sprite_names.len()
+	}
+}
+
+/// A laid out set and where its pieces ended up.
+struct Body {
+	data: Vec<u8>,
+	header_size: usize,
+	/// Every pointer that needs relocating, in file order
+	pointers: Vec<usize>,
+	sprites: usize,
+	texture_names: usize,
+	sprite_names: usize,
+	extras: usize,
+}
+
+fn truncated(what: &str, pos: usize) -> SpriteError {
+	SpriteError::Malformed(format!("{what} at {pos:#x} runs past the end of the file"))
+}
+
+fn read_slice<'a>(
+	data: &'a [u8],
+	pos: usize,
+	len: usize,
+	what: &str,
+) -> Result<&'a [u8], SpriteError> {
+	pos.checked_add(len)
+		.and_then(|end| data.get(pos..end))
+		.ok_or_else(|| truncated(what, pos))
+}
+
+fn read_uint(data: &[u8], pos: usize, size: usize, endian: Endian) -> Result<u64, SpriteError> {
+	let bytes = read_slice(data, pos, size, "Field")?;
+	let fold = |value: u64, byte: &u8| value << 8 | *byte as u64;
+	Ok(match endian {
+		Endian::Big => bytes.iter().fold(0, fold),
+		_ => bytes.iter().rev().fold(0, fold),
+	})
+}
+
+fn write_uint(out: &mut [u8], pos: usize, value: u64, size: usize, endian: Endian) {
+	let bytes = value.to_le_bytes();
+	let field = &mut out[pos..pos + size];
+	field.copy_from_slice(&bytes[..size]);
+	if endian == Endian::Big {
+		field.reverse();
+	}
+}
+
+fn pad(out: &mut Vec<u8>, alignment: usize) {
+	out.resize(out.len().next_multiple_of(alignment), 0);
+}
+
+fn read_pointers(
+	data: &[u8],
+	table: usize,
+	count: usize,
+	pointer_size: usize,
+	endian: Endian,
+) -> Result<Vec<usize>, SpriteError> {
+	(0..count)
+		.map(|i| Ok(read_uint(data, table + i * pointer_size, pointer_size, endian)? as usize))
+		.collect()
+}
+
+fn read_names<'a>(data: &'a [u8], pointers: &[usize]) -> Result<Vec<&'a [u8]>, SpriteError> {
+	pointers
+		.iter()
+		.map(|&pos| {
+			let rest = data.get(pos..).ok_or_else(|| truncated("Name", pos))?;
+			let len = rest
+				.iter()
+				.position(|byte| *byte == 0)
+				.ok_or_else(|| truncated("Name", pos))?;
+			Ok(&rest[..len])
+		})
+		.collect()
+}
+
+/// Splits a set body with `pointer_size` byte pointers into its parts. The texture set is only
+/// looked for when `inline_textures`, classic files usually keep it between the header and the
+/// sprites.
+fn parse_body(
+	data: &[u8],
+	endian: Endian,
+	pointer_size: usize,
+	inline_textures: bool,
+) -> Result<SetParts<'_>, SpriteError> {
+	let flags = read_uint(data, 0, 4, endian)? as u32;
+	let texture_pos = read_uint(data, 4, 4, endian)? as usize;
+	let texture_count = read_uint(data, 8, 4, endian)? as u32;
+	let sprite_count = read_uint(data, 12, 4, endian)? as usize;
+	let pointer = |i: usize| -> Result<usize, SpriteError> {
+		Ok(read_uint(data, 0x10 + i * pointer_size, pointer_size, endian)? as usize)
+	};
+	let tables = (0..4).map(pointer).collect::<Result<Vec<_>, _>>()?;
+	let texture_names = read_pointers(
+		data,
+		tables[1],
+		texture_count as usize,
+		pointer_size,
+		endian,
+	)?;
+	let sprite_names = read_pointers(data, tables[2], sprite_count, pointer_size, endian)?;
+	// The texture set runs up to whatever comes next in the file, names included
+	let textures = if inline_textures && texture_pos != 0 {
+		let end = tables
+			.iter()
+			.chain(&texture_names)
+			.chain(&sprite_names)
+			.copied()
+			.filter(|start| *start > texture_pos)
+			.min()
+			.unwrap_or(data.len());
+		data.get(texture_pos..end)
+			.ok_or_else(|| truncated("Texture set", texture_pos))?
+	} else {
+		&[]
+	};
+	Ok(SetParts {
+		flags,
+		texture_count,
+		sprites: read_slice(data, tables[0], sprite_count * SPRITE_SIZE, "Sprites")?,
+		extras: read_slice(data, tables[3], sprite_count * EXTRA_SIZE, "Sprite extras")?,
+		texture_names: read_names(data, &texture_names)?,
+		sprite_names: read_names(data, &sprite_names)?,
+		textures,
+	})
+}
+
+/// Lays `parts` out with `pointer_size` byte pointers, putting the texture set right after the
+/// header when `inline_textures` and leaving its pointer null otherwise.
+fn write_body(
+	parts: &SetParts,
+	endian: Endian,
+	pointer_size: usize,
+	inline_textures: bool,
+) -> Body {
+	let header_size = 0x10 + 4 * pointer_size;
+	let mut data = vec![0; header_size];
+	write_uint(&mut data, 0, parts.flags as u64, 4, endian);
+	write_uint(&mut data, 8, parts.texture_count as u64, 4, endian);
+	write_uint(&mut data, 12, parts.sprite_count() as u64, 4, endian);
+	let mut pointers = vec![];
+	let mut set_pointer = |data: &mut Vec<u8>, pos: usize| {
+		let target = data.len() as u64;
+		write_uint(data, pos, target, pointer_size, endian);
+		pointers.push(pos);
+	};
+
+	if inline_textures && !parts.textures.is_empty() {
+		pad(&mut data, BODY_ALIGNMENT);
+		let pos = data.len() as u64;
+		write_uint(&mut data, 4, pos, 4, endian);
+		data.extend_from_slice(parts.textures);
+	}
+
+	pad(&mut data, BODY_ALIGNMENT);
+	let sprites = data.len();
+	set_pointer(&mut data, 0x10);
+	data.extend_from_slice(parts.sprites);
+
+	let mut name_tables = [0; 2];
+	for (i, names) in [&parts.texture_names, &parts.sprite_names]
+		.into_iter()
+		.enumerate()
+	{
+		name_tables[i] = data.len();
+		set_pointer(&mut data, 0x10 + (i + 1) * pointer_size);
+		data.resize(data.len() + names.len() * pointer_size, 0);
+		for (j, name) in names.iter().enumerate() {
+			set_pointer(&mut data, name_tables[i] + j * pointer_size);
+			data.extend_from_slice(name);
+			data.push(0);
+		}
+	}
+
+	pad(&mut data, BODY_ALIGNMENT);
+	let extras = data.len();
+	set_pointer(&mut data, 0x10 + 3 * pointer_size);
+	data.extend_from_slice(parts.extras);
+	pad(&mut data, BODY_ALIGNMENT);
+
+	pointers.sort_unstable();
+	Body {
+		data,
+		header_size,
+		pointers,
+		sprites,
+		texture_names: name_tables[0],
+		sprite_names: name_tables[1],
+		extras,
+	}
+}
+
+/// Packs `value` big endian into 1, 2 or 4 bytes, the top two bits saying which. A `kind` takes
+/// the two bits after those, as swap table fields do.
+fn write_packed(out: &mut Vec<u8>, value: u32, kind: Option<u8>) {
+	let (kind, bits) = match kind {
+		Some(kind) => (kind as u32 & 3, 4),
+		None => (0, 6),
+	};
+	if value < 1 << bits {
+		out.push((kind << bits | value) as u8);
+	} else if value < 1 << (bits + 8) {
+		out.extend_from_slice(&(0x4000 | kind << (bits + 8) | value).to_be_bytes()[2..]);
+	} else {
+		out.extend_from_slice(&(0x8000_0000 | kind << (bits + 24) | value).to_be_bytes());
+	}
+}
+
+/// `POF0`/`POF1` data: the distance from each pointer to the one before it in pointer sized
+/// units, so the game can rebase them after loading.
+fn relocation_table(pointers: &[usize], pointer_size: usize) -> Vec<u8> {
+	let mut out = vec![0; 4];
+	let mut last = 0;
+	for pointer in pointers {
+		let distance = ((pointer - last) / pointer_size) as u32;
+		last = *pointer;
+		match distance {
+			0..=0x3f => out.push(0x40 | distance as u8),
+			0x40..=0x3fff => out.extend_from_slice(&(0x8000 | distance as u16).to_be_bytes()),
+			_ => out.extend_from_slice(&(0xc000_0000 | distance).to_be_bytes()),
+		}
+	}
+	pad(&mut out, 4);
+	let len = out.len() as u32;
+	out[..4].copy_from_slice(&len.to_le_bytes());
+	out
+}
+
+/// `ENRS` data: runs of identical structures and the width of each field in them, which tells
+/// tools moving the file to the other byte order what to swap. Names and texture data are left
+/// alone.
+fn swap_table(body: &Body, parts: &SetParts, pointer_size: usize) -> Vec<u8> {
+	let pointer_kind = if pointer_size == 8 { 2 } else { 1 };
+	let sprite_count = parts.sprite_count();
+	// (offset, fields as (kind, count), structure size, repeats)
+	let runs = [
+		(0, vec![(1, 4), (pointer_kind, 4)], body.header_size, 1),
+		(body.sprites, vec![(1, 10)], SPRITE_SIZE, sprite_count),
+		(
+			body.texture_names,
+			vec![(pointer_kind, parts.texture_names.len())],
+			parts.texture_names.len() * pointer_size,
+			1,
+		),
+		(
+			body.sprite_names,
+			vec![(pointer_kind, sprite_count)],
+			sprite_count * pointer_size,
+			1,
+		),
+		(body.extras, vec![(1, 2)], EXTRA_SIZE, sprite_count),
+	];
+	let runs = runs
+		.into_iter()
+		.filter(|(_, _, size, repeats)| *size != 0 && *repeats != 0)
+		.collect::<Vec<_>>();
+
+	let mut out = vec![0; 16];
+	out[4..8].copy_from_slice(&(runs.len() as u32).to_le_bytes());
+	let mut last = 0;
+	for (offset, fields, size, repeats) in runs {
+		write_packed(&mut out, (offset - last) as u32, None);
+		write_packed(&mut out, fields.len() as u32, None);
+		write_packed(&mut out, size as u32, None);
+		write_packed(&mut out, repeats as u32, None);
+		for (kind, count) in fields {
+			// Fields follow each other with no gap
+			write_packed(&mut out, 0, Some(kind));
+			write_packed(&mut out, count as u32, None);
+		}
+		last = offset + size * repeats;
+	}
+	pad(&mut out, BODY_ALIGNMENT);
+	out
+}
+
+/// A section header and its data padded out, followed by its already built subsections. Headers
+/// are always little endian, the flags say which order the data is in.
+fn section(
+	signature: &[u8; 4],
+	data: &[u8],
+	subsections: &[u8],
+	depth: u32,
+	header_size: usize,
+	endian: Endian,
+) -> Vec<u8> {
+	let data_size = data.len().next_multiple_of(BODY_ALIGNMENT);
+	let mut flags = SECTION_FLAGS;
+	if endian == Endian::Big {
+		flags |= BIG_ENDIAN_FLAG;
+	}
+	let mut out = Vec::with_capacity(header_size + data_size + subsections.len());
+	out.extend_from_slice(signature);
+	out.extend_from_slice(&((data_size + subsections.len()) as u32).to_le_bytes());
+	out.extend_from_slice(&(header_size as u32).to_le_bytes());
+	out.extend_from_slice(&flags.to_le_bytes());
+	out.extend_from_slice(&depth.to_le_bytes());
+	out.extend_from_slice(&(data_size as u32).to_le_bytes());
+	out.resize(header_size, 0);
+	out.extend_from_slice(data);
+	out.resize(header_size + data_size, 0);
+	out.extend_from_slice(subsections);
+	out
+}
+
+struct SectionHeader {
+	signature: [u8; 4],
+	section_size: usize,
+	data_offset: usize,
+	flags: u32,
+	data_size: usize,
+}
+
+impl SectionHeader {
+	fn read(data: &[u8], pos: usize) -> Result<Self, SpriteError> {
+		let header = read_slice(data, pos, 0x18, "Section header")?;
+		let field = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+		Ok(Self {
+			signature: header[..4].try_into().unwrap(),
+			section_size: field(4) as usize,
+			data_offset: field(8) as usize,
+			flags: field(12),
+			data_size: field(20) as usize,
+		})
+	}
+
+	fn endian(&self) -> Endian {
+		if self.flags & BIG_ENDIAN_FLAG != 0 {
+			Endian::Big
+		} else {
+			Endian::Little
+		}
+	}
+}
+
+/// Pulls the set body and the textures out of a modern file.
+fn parse_modern(
+	data: &[u8],
+	variant: FormatVariant,
+) -> Result<(SetParts<'_>, Endian), SpriteError> {
+	let header = SectionHeader::read(data, 0)?;
+	let endian = header.endian();
+	let body = read_slice(data, header.data_offset, header.data_size, "SPRC data")?;
+	let mut parts = parse_body(body, endian, variant.pointer_size(), false)?;
+
+	let end = header.data_offset + header.section_size;
+	let mut pos = header.data_offset + header.data_size.next_multiple_of(BODY_ALIGNMENT);
+	while pos < end {
+		let section = SectionHeader::read(data, pos)?;
+		match &section.signature {
+			b"EOFC" => break,
+			b"TXPC" => {
+				parts.textures = read_slice(
+					data,
+					pos + section.data_offset,
+					section.data_size,
+					"TXPC data",
+				)?;
+			}
+			_ => {}
+		}
+		pos += section.data_offset + section.section_size;
+	}
+	if parts.textures.is_empty() && parts.texture_count != 0 {
+		return Err(SpriteError::Malformed(format!(
+			"SPRC set lists {} textures but has no TXPC section",
+			parts.texture_count
+		)));
+	}
+	Ok((parts, endian))
+}
+
+impl SprSet {
+	/// Writes the set in `variant`'s layout.
+	pub fn convert(
+		&self,
+		variant: FormatVariant,
+		options: &WriteOptions,
+	) -> Result<Vec<u8>, SpriteError> {
+		let options = &WriteOptions {
+			flip: options.flip.resolve(variant),
+			..options.clone()
+		};
+		let mut out = Cursor::new(vec![]);
+		self.to_writer_with_options(&mut out, options)?;
+		let classic = out.into_inner();
+		if variant == FormatVariant::Classic {
+			return Ok(classic);
+		}
+
+		let endian = options.endianness;
+		let parts = parse_body(&classic, endian, 4, true)?;
+		let pointer_size = variant.pointer_size();
+		let header_size = variant.section_header_size();
+		let body = write_body(&parts, endian, pointer_size, false);
+		let relocations = match variant {
+			FormatVariant::ModernX => b"POF1",
+			_ => b"POF0",
+		};
+		let mut subsections = section(
+			relocations,
+			&relocation_table(&body.pointers, pointer_size),
+			&[],
+			1,
+			header_size,
+			endian,
+		);
+		subsections.extend(section(
+			b"ENRS",
+			&swap_table(&body, &parts, pointer_size),
+			&[],
+			1,
+			header_size,
+			endian,
+		));
+		if !parts.textures.is_empty() {
+			subsections.extend(section(
+				b"TXPC",
+				parts.textures,
+				&[],
+				1,
+				header_size,
+				endian,
+			));
+		}
+		subsections.extend(section(b"EOFC", &[], &[], 1, header_size, endian));
+
+		let mut out = section(b"SPRC", &body.data, &subsections, 0, header_size, endian);
+		out.extend(section(b"EOFC", &[], &[], 0, header_size, endian));
+		Ok(out)
+	}
+
+	/// Reads a set in any layout [`FormatVariant::detect`] recognizes.
+	pub fn from_variant_bytes(
+		data: &[u8],
+		spr_db_set: Option<&diva_db::spr::SprDbSet>,
+		options: &LoadOptions,
+	) -> Result<Self, SpriteError> {
+		let variant = FormatVariant::detect(data);
+		if variant == FormatVariant::Classic {
+			return Self::from_reader_with_options(
+				&mut Cursor::new(data),
+				spr_db_set,
+				&mut Hooks::new(),
+				options,
+			);
+		}
+		let (parts, endian) = parse_modern(data, variant)?;
+		let classic = write_body(&parts, endian, 4, true).data;
+		let options = LoadOptions {
+			endianness: Some(endian),
+			flip: options.flip.resolve(variant),
+			..options.clone()
+		};
+		Self::from_reader_with_options(
+			&mut Cursor::new(classic),
+			spr_db_set,
+			&mut Hooks::new(),
+			&options,
+		)
+	}
+}
+
+/// Reads a file of either layout and writes it back as `target`.
+pub fn convert_bytes(
+	data: &[u8],
+	target: FormatVariant,
+	options: &WriteOptions,
+) -> Result<Vec<u8>, SpriteError> {
+	SprSet::from_variant_bytes(data, None, &LoadOptions::default())?.convert(target, options)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn texture_set_after_the_sprites() {
+		let region = Vec4 {
+			x: 0.0,
+			y: 0.0,
+			z: 8.0,
+			w: 8.0,
+		};
+		let set = SprSet::from_parts(
+			"test",
+			[(
+				"tex".to_string(),
+				Texture::new(DynamicImage::new_rgba8(8, 8)),
+			)],
+			[(
+				"icon".to_string(),
+				Sprite::new("tex", region, ScreenMode::HDTV1080),
+			)],
+			0,
+		)
+		.unwrap();
+		let mut out = Cursor::new(vec![]);
+		set.to_writer(&mut out).unwrap();
+		let data = out.into_inner();
+		let textures = parse_body(&data, Endian::Little, 4, true)
+			.unwrap()
+			.textures
+			.to_vec();
+		let mut moved = data.clone();
+		pad(&mut moved, BODY_ALIGNMENT);
+		let pos = moved.len() as u64;
+		write_uint(&mut moved, 4, pos, 4, Endian::Little);
+		moved.extend_from_slice(&textures);
+		let parts = parse_body(&moved, Endian::Little, 4, true).unwrap();
+		assert_eq!(parts.textures, &textures[..]);
+	}
+}
@@ -0,0 +1,130 @@
+//! State for editor and viewer UIs, kept free of any toolkit so egui, iced or anything else can
+//! draw it. Thumbnails and crops come out as plain RGBA images ready to upload as textures.
+use crate::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureItem {
+	pub name: String,
+	pub width: u32,
+	pub height: u32,
+	pub format: TextureFormat,
+	/// Fits in the thumbnail size the view was built with, aspect ratio kept
+	pub thumbnail: image::RgbaImage,
+	pub sprite_count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteItem {
+	pub name: String,
+	pub texture: String,
+	pub pixel_region: Vec4,
+	pub screen_mode: ScreenMode,
+}
+
+/// Texture and sprite lists for a set, with a sprite filter and a selection. Rebuild it with
+/// [`SetView::refresh`] after the set changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetView {
+	pub textures: Vec<TextureItem>,
+	/// Sorted by name
+	pub sprites: Vec<SpriteItem>,
+	/// How [`SetView::selected_crop`] cuts out fractional regions
+	pub sub_pixel: SubPixel,
+	filter: String,
+	selected: Option<String>,
+	thumbnail_size: u32,
+}
+
+impl SetView {
+	pub fn new(set: &SprSet, thumbnail_size: u32) -> Self {
+		let mut view = Self {
+			textures: vec![],
+			sprites: vec![],
+			sub_pixel: Default::default(),
+			filter: String::new(),
+			selected: None,
+			thumbnail_size,
+		};
+		view.refresh(set);
+		view
+	}
+
+	/// Rebuilds both lists from `set`, keeping the filter and the selection if the sprite still
+	/// exists.
+	pub fn refresh(&mut self, set: &SprSet) {
+		let size = self.thumbnail_size.max(1);
+		self.textures = set
+			.texture_order()
+			.into_iter()
+			.map(|name| {
+				let texture = &set.textures[name];
+				TextureItem {
+					name: name.clone(),
+					width: texture.image.width(),
+					height: texture.image.height(),
+					format: texture.original_format,
+					thumbnail: texture.image.thumbnail(size, size).to_rgba8(),
+					sprite_count: set
+						.sprites
+						.values()
+						.filter(|sprite| &sprite.texture_name == name)
+						.count(),
+				}
+			})
+			.collect();
+		self.sprites = set
+			.sprites
+			.iter()
+			.map(|(name, sprite)| SpriteItem {
+				name: name.clone(),
+				texture: sprite.texture_name.clone(),
+				pixel_region: sprite.pixel_region,
+				screen_mode: sprite.screen_mode,
+			})
+			.collect();
+		self.sprites.sort_by(|a, b| a.name.cmp(&b.name));
+		if self
+			.selected
+			.as_ref()
+			.is_some_and(|selected| !set.sprites.contains_key(selected))
+		{
+			self.selected = None;
+		}
+	}
+
+	pub fn filter(&self) -> &str {
+		&self.filter
+	}
+
+	/// Case insensitive substring match against sprite and texture names.
+	pub fn set_filter(&mut self, filter: &str) {
+		self.filter = filter.to_string();
+	}
+
+	pub fn visible_sprites(&self) -> impl Iterator<Item = &SpriteItem> {
+		let filter = self.filter.to_lowercase();
+		self.sprites.iter().filter(move |sprite| {
+			sprite.name.to_lowercase().contains(&filter)
+				|| sprite.texture.to_lowercase().contains(&filter)
+		})
+	}
+
+	pub fn selected(&self) -> Option<&SpriteItem> {
+		let selected = self.selected.as_ref()?;
+		self.sprites.iter().find(|sprite| &sprite.name == selected)
+	}
+
+	/// Selects `name`, or clears the selection with `None`. Unknown names clear it too.
+	pub fn select(&mut self, name: Option<&str>) {
+		self.selected = name
+			.filter(|name| self.sprites.iter().any(|sprite| sprite.name == *name))
+			.map(str::to_string);
+	}
+
+	/// The selected sprite cut out of its texture, mirrored and turned the way the game shows it.
+	pub fn selected_crop(&self, set: &SprSet) -> Option<image::RgbaImage> {
+		let sprite = set.sprites.get(self.selected.as_ref()?)?;
+		let texture = set.textures.get(&sprite.texture_name)?;
+		Some(extract::crop_sprite_oriented(&texture.image, sprite, self.sub_pixel).to_rgba8())
+	}
+}
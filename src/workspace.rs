@@ -0,0 +1,211 @@
+//! Many sets loaded side by side with the spr_db they share, for queries across a whole game.
+use crate::*;
+use rayon::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteHit<'a> {
+	pub set_id: u32,
+	pub set: &'a str,
+	pub sprite: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SetTexture {
+	pub set_id: u32,
+	pub texture: String,
+}
+
+pub struct Workspace {
+	pub spr_db: diva_db::spr::SprDb,
+	/// Loaded sets by spr_db id
+	pub sets: BTreeMap<u32, SprSet>,
+	/// Sets [`Workspace::load`] couldn't read, with the reason
+	pub failures: BTreeMap<u32, String>,
+}
+
+impl Workspace {
+	pub fn new(spr_db: diva_db::spr::SprDb) -> Self {
+		Self {
+			spr_db,
+			sets: BTreeMap::new(),
+			failures: BTreeMap::new(),
+		}
+	}
+
+	/// Loads every set `spr_db` lists from a game data folder, see [`SprSet::read_by_id`]. Sets
+	/// that fail end up in `failures` rather than failing the whole load.
+	pub fn load(data_root: &Path, spr_db: diva_db::spr::SprDb) -> Self {
+		let mut workspace = Self::new(spr_db);
+		let ids = workspace
+			.spr_db
+			.sets
+			.iter()
+			.map(|(id, _)| u32::from(*id))
+			.collect::<Vec<_>>();
+		for id in ids {
+			let set = SprSet::read_by_id(data_root, id, &workspace.spr_db);
+			workspace.add_loaded(id, set);
+		}
+		workspace
+	}
+
+	/// [`Workspace::load`] reading with `config`'s load options, one set per rayon task on
+	/// `config`'s threads. Only failing to start the threads fails the load.
+	pub fn load_with_config(
+		data_root: &Path,
+		spr_db: diva_db::spr::SprDb,
+		config: &SprConfig,
+	) -> Result<Self, SpriteError> {
+		let mut workspace = Self::new(spr_db);
+		let ids = workspace
+			.spr_db
+			.sets
+			.iter()
+			.map(|(id, _)| u32::from(*id))
+			.collect::<Vec<_>>();
+		let spr_db = &workspace.spr_db;
+		let loaded = config.install(|| {
+			ids.par_iter()
+				.map(|id| {
+					let set = SprSet::read_by_id_with_config(data_root, *id, spr_db, config);
+					(*id, set)
+				})
+				.collect::<Vec<_>>()
+		})?;
+		for (id, set) in loaded {
+			workspace.add_loaded(id, set);
+		}
+		Ok(workspace)
+	}
+
+	fn add_loaded(&mut self, set_id: u32, set: Result<SprSet, SpriteError>) {
+		match set {
+			Ok(set) => {
+				self.sets.insert(set_id, set);
+			}
+			Err(err) => {
+				self.failures.insert(set_id, err.to_string());
+			}
+		}
+	}
+
+	pub fn insert(&mut self, set_id: u32, set: SprSet) -> Option<SprSet> {
+		self.failures.remove(&set_id);
+		self.sets.insert(set_id, set)
+	}
+
+	pub fn get_by_name(&self, name: &str) -> Option<(u32, &SprSet)> {
+		self.sets
+			.iter()
+			.find(|(_, set)| set.name == name)
+			.map(|(id, set)| (*id, set))
+	}
+
+	/// Every sprite named `name`, bare or with its set prefix (`SPR_SEL_PV_LOGO`), in any set.
+	pub fn find_sprite<'a>(&'a self, name: &'a str) -> Vec<SpriteHit<'a>> {
+		let mut hits = vec![];
+		for (id, set) in &self.sets {
+			let bare = name
+				.strip_prefix(set.name.as_str())
+				.and_then(|name| name.strip_prefix('_'))
+				.unwrap_or(name);
+			if let Some((sprite, _)) = set.sprites.get_key_value(bare) {
+				hits.push(SpriteHit {
+					set_id: *id,
+					set: &set.name,
+					sprite,
+				});
+			}
+		}
+		hits
+	}
+
+	/// Groups of textures with identical pixels found in more than one place, e.g. shared UI
+	/// parts copied into every song's set.
+	pub fn duplicate_textures(&self) -> Vec<Vec<SetTexture>> {
+		let mut by_hash: HashMap<u64, Vec<(SetTexture, &Texture)>> = HashMap::new();
+		for (id, set) in &self.sets {
+			for (name, texture) in &set.textures {
+				let mut hasher = DefaultHasher::new();
+				(texture.image.width(), texture.image.height()).hash(&mut hasher);
+				texture.image.as_bytes().hash(&mut hasher);
+				by_hash.entry(hasher.finish()).or_default().push((
+					SetTexture {
+						set_id: *id,
+						texture: name.clone(),
+					},
+					texture,
+				));
+			}
+		}
+
+		let mut groups = vec![];
+		for candidates in by_hash.into_values() {
+			// Hashes only narrow it down, split by actual contents
+			let mut split: Vec<(&Texture, Vec<SetTexture>)> = vec![];
+			for (location, texture) in candidates {
+				match split
+					.iter_mut()
+					.find(|(other, _)| other.image == texture.image)
+				{
+					Some((_, locations)) => locations.push(location),
+					None => split.push((texture, vec![location])),
+				}
+			}
+			groups.extend(
+				split
+					.into_iter()
+					.map(|(_, mut locations)| {
+						locations.sort();
+						locations
+					})
+					.filter(|locations| locations.len() > 1),
+			);
+		}
+		groups.sort();
+		groups
+	}
+
+	/// Renames a sprite of set `set_id` both in the set and in the spr_db, refusing names any set
+	/// in the spr_db already uses.
+	pub fn rename_sprite(&mut self, set_id: u32, old: &str, new: &str) -> Result<(), SpriteError> {
+		let set = self
+			.sets
+			.get_mut(&set_id)
+			.ok_or_else(|| SpriteError::Import(format!("Set {set_id} is not loaded")))?;
+		let old_name = format!("{}_{old}", set.name);
+		let new_name = format!("{}_{new}", set.name);
+		if set.sprites.contains_key(new)
+			|| self.spr_db.sets.iter().any(|(_, db_set)| {
+				db_set
+					.sprites
+					.iter()
+					.any(|(_, entry)| entry.name == new_name)
+			}) {
+			return Err(SpriteError::Validation(
+				ErrorCode::NameInUse,
+				format!("Sprite name {new_name} is already used"),
+			));
+		}
+		if !set.rename_sprite_key(old, new) {
+			return Err(SpriteError::not_in_set("Sprite", old));
+		}
+		set.usage.rename_sprite(old, new);
+
+		for (id, db_set) in self.spr_db.sets.iter_mut() {
+			if u32::from(*id) != set_id {
+				continue;
+			}
+			for (_, entry) in db_set.sprites.iter_mut() {
+				if entry.name == old_name {
+					entry.name = new_name.clone();
+				}
+			}
+		}
+		Ok(())
+	}
+}